@@ -27,6 +27,25 @@ pub enum Command {
         /// Reset task before starting (auto reset+start in one step)
         #[arg(long)]
         reset: bool,
+        /// Wait for incomplete dependencies instead of failing
+        #[arg(long)]
+        when_ready: bool,
+        /// Set a variable for this run (repeatable; shadows config vars)
+        #[arg(long = "var", value_name = "KEY=VALUE")]
+        vars: Vec<String>,
+        /// JSON object of variables for this run (--var wins on conflict)
+        #[arg(long, value_name = "FILE")]
+        vars_file: Option<String>,
+    },
+
+    /// Start every ready task, dependencies first, until nothing else can run
+    RunAll {
+        /// Maximum tasks running at once (default: 4)
+        #[arg(short, long, default_value = "4")]
+        jobs: usize,
+        /// Poll interval in milliseconds (default: 200)
+        #[arg(long, default_value = "200")]
+        interval: u64,
     },
 
     /// Show task status
@@ -48,6 +67,12 @@ pub enum Command {
         /// Only reset current step (retry) instead of full task reset
         #[arg(long)]
         step: bool,
+        /// Also reset every started task that depends on it, directly or not
+        #[arg(long)]
+        cascade: bool,
+        /// With --cascade: list the tasks that would be reset, change nothing
+        #[arg(long)]
+        dry_run: bool,
     },
 
     /// Wait for task(s) to reach a specific status
@@ -101,6 +126,58 @@ pub enum Command {
         message: Option<String>,
     },
 
+    /// Reject a waiting step (routed through on_fail like a verify failure)
+    Reject {
+        /// Task name
+        task: String,
+        /// Rejection reason (passed to the retry as last_verify_output)
+        #[arg(short, long)]
+        message: String,
+    },
+
+    /// Report the current in_viewport step as failed
+    Fail {
+        /// Task name
+        task: String,
+        /// Failure reason (passed to the next attempt as last_verify_output)
+        #[arg(short, long)]
+        message: Option<String>,
+        /// Exit code to record (non-zero)
+        #[arg(long, default_value = "1")]
+        exit_code: i32,
+    },
+
+    /// Run compensate commands for the completed steps, newest first
+    Rollback {
+        /// Task name
+        task: String,
+    },
+
+    /// Move a paused run onto the current workflow definition
+    Migrate {
+        /// Task name
+        task: String,
+        /// Map a renamed step (repeatable)
+        #[arg(long = "map", value_name = "OLD=NEW")]
+        map: Vec<String>,
+    },
+
+    /// Validate workflow files and report problems as JSON
+    Check {
+        /// Treat warnings as errors (e.g. `${vars}` nothing defines)
+        #[arg(long)]
+        strict: bool,
+    },
+
+    /// Archive finished runs out of task logs and checkpoint their replay
+    Compact {
+        /// Task names (default: every task with a log)
+        tasks: Vec<String>,
+        /// Change nothing; check that a full replay (archive included) matches the checkpointed state
+        #[arg(long)]
+        verify: bool,
+    },
+
     /// Start HTTP API server
     Serve {
         /// Port to listen on
@@ -0,0 +1,344 @@
+use anyhow::Result;
+use indexmap::IndexMap;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::error::PawlError;
+use crate::model::config::{Foreach, Lint, Severity, Step};
+use crate::model::event::HOOK_VARS;
+use crate::model::graph::TaskGraph;
+use crate::model::Config;
+use crate::util::project::get_project_root;
+use crate::util::variable::{CONTEXT_VARS, RETRY_VARS};
+
+use super::common::{Project, PAWL_DIR};
+
+/// A problem in a workflow file
+#[derive(Serialize)]
+struct Diagnostic {
+    severity: Severity,
+    file: String,
+    path: String,
+    message: String,
+}
+
+#[derive(Serialize)]
+struct CheckReport {
+    valid: bool,
+    errors: usize,
+    warnings: usize,
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// Validate every workflow file and report all problems as JSON.
+/// Exits with Validation (6) if any problem is an error.
+pub fn run(strict: bool) -> Result<()> {
+    let project_root = get_project_root()?;
+    let workflows_dir = PathBuf::from(&project_root).join(PAWL_DIR).join("workflows");
+    if !workflows_dir.exists() {
+        return Err(PawlError::NotFound {
+            message: "No .pawl/workflows/ directory found. Run 'pawl init' first.".into(),
+        }.into());
+    }
+
+    let mut diagnostics = Vec::new();
+    match Project::load_quiet() {
+        Ok(project) => check_workflows(project.all_workflows(), &mut diagnostics),
+        Err(load_error) => {
+            // Attribute the failure to the file(s) that don't parse, or to a
+            // project-level problem (e.g. a dependency cycle) found below
+            let mut parsed = IndexMap::new();
+            let mut entries: Vec<_> = fs::read_dir(&workflows_dir)?
+                .filter_map(|e| e.ok())
+                .map(|e| e.path())
+                .filter(|p| p.extension().map(|x| x == "json").unwrap_or(false))
+                .collect();
+            entries.sort();
+            for path in entries {
+                let wf_name = path.file_stem().and_then(|s| s.to_str()).unwrap_or("").to_string();
+                match Config::load_from(&path) {
+                    Ok(config) => {
+                        parsed.insert(wf_name, config);
+                    }
+                    Err(e) => diagnostics.push(Diagnostic {
+                        severity: Severity::Error,
+                        file: workflow_file(&wf_name),
+                        path: "$".into(),
+                        message: format!("{:#}", e),
+                    }),
+                }
+            }
+            if diagnostics.is_empty() {
+                check_workflows(&parsed, &mut diagnostics);
+                if !diagnostics.iter().any(|d| d.severity == Severity::Error) {
+                    diagnostics.push(Diagnostic {
+                        severity: Severity::Error,
+                        file: format!("{}/workflows", PAWL_DIR),
+                        path: "$".into(),
+                        message: load_error.to_string(),
+                    });
+                }
+            } else {
+                // Dependencies may point into a broken file, so only check each file on its own
+                for (wf_name, config) in &parsed {
+                    push_lints(&mut diagnostics, wf_name, config.lint());
+                    push_lints(&mut diagnostics, wf_name, check_vars(&parsed, wf_name, config));
+                }
+            }
+        }
+    }
+
+    if strict {
+        for d in &mut diagnostics {
+            d.severity = Severity::Error;
+        }
+    }
+    let errors = diagnostics.iter().filter(|d| d.severity == Severity::Error).count();
+    let warnings = diagnostics.len() - errors;
+    let report = CheckReport { valid: errors == 0, errors, warnings, diagnostics };
+    println!("{}", serde_json::to_string(&report)?);
+
+    if errors > 0 {
+        return Err(PawlError::Validation {
+            message: format!("pawl check: {} error(s), {} warning(s)", errors, warnings),
+        }.into());
+    }
+    eprintln!("pawl check: no errors, {} warning(s)", warnings);
+    Ok(())
+}
+
+fn workflow_file(wf_name: &str) -> String {
+    format!("{}/workflows/{}.json", PAWL_DIR, wf_name)
+}
+
+fn push_lints(diagnostics: &mut Vec<Diagnostic>, wf_name: &str, lints: Vec<Lint>) {
+    diagnostics.extend(lints.into_iter().map(|lint| Diagnostic {
+        severity: lint.severity,
+        file: workflow_file(wf_name),
+        path: lint.path,
+        message: lint.message,
+    }));
+}
+
+/// Per-workflow lints, then the checks that need every workflow: task dependencies
+/// and variables that never resolve.
+fn check_workflows(workflows: &IndexMap<String, Config>, diagnostics: &mut Vec<Diagnostic>) {
+    for (wf_name, config) in workflows {
+        push_lints(diagnostics, wf_name, config.lint());
+    }
+    for (wf_name, config) in workflows {
+        push_lints(diagnostics, wf_name, check_depends(workflows, config));
+        push_lints(diagnostics, wf_name, check_wait_for(workflows, config));
+    }
+    push_dependency_cycles(workflows, diagnostics);
+    for (wf_name, config) in workflows {
+        push_lints(diagnostics, wf_name, check_vars(workflows, wf_name, config));
+    }
+}
+
+fn check_depends(workflows: &IndexMap<String, Config>, config: &Config) -> Vec<Lint> {
+    let mut lints = Vec::new();
+    for (name, tc) in &config.tasks {
+        for (k, dep) in tc.depends.iter().enumerate() {
+            if !workflows.values().any(|c| c.tasks.contains_key(dep.task())) {
+                lints.push(Lint {
+                    severity: Severity::Error,
+                    path: format!("$.tasks.{}.depends[{}]", name, k),
+                    message: format!("task '{}' depends on '{}', which is not declared in any workflow", name, dep.task()),
+                });
+            }
+        }
+    }
+    lints
+}
+
+/// `wait_for` targets must name a declared task and a step of that task's workflow
+fn check_wait_for(workflows: &IndexMap<String, Config>, config: &Config) -> Vec<Lint> {
+    let mut lints = Vec::new();
+    for (i, step) in config.workflow.iter().enumerate() {
+        for (k, target) in step.wait_for.iter().enumerate() {
+            // Malformed targets are reported by `Config::lint`
+            let Some((task, name)) = target.split_once(':') else { continue };
+            let message = match workflows.values().find(|c| c.tasks.contains_key(task)) {
+                None => format!("step '{}' waits for task '{}', which is not declared in any workflow", step.name, task),
+                Some(other) if other.step_index(name).is_none() => {
+                    format!("step '{}' waits for '{}', which is not a step of task '{}'", step.name, name, task)
+                }
+                Some(_) => continue,
+            };
+            lints.push(Lint {
+                severity: Severity::Error,
+                path: format!("$.workflow[{}].wait_for[{}]", i, k),
+                message,
+            });
+        }
+    }
+    lints
+}
+
+/// Report each cycle in the task dependency graph once, at the task that closes it
+fn push_dependency_cycles(workflows: &IndexMap<String, Config>, diagnostics: &mut Vec<Diagnostic>) {
+    for cycle in TaskGraph::new(workflows).cycles() {
+        let closing = cycle[cycle.len() - 2];
+        let Some(wf_name) = workflows.iter()
+            .find(|(_, config)| config.tasks.contains_key(closing))
+            .map(|(name, _)| name)
+        else {
+            continue;
+        };
+        diagnostics.push(Diagnostic {
+            severity: Severity::Error,
+            file: workflow_file(wf_name),
+            path: format!("$.tasks.{}.depends", closing),
+            message: format!("dependency cycle: {}", cycle.join(" → ")),
+        });
+    }
+}
+
+/// `${name}` references in a command or value that pawl would expand. Shell-style
+/// references (`${HOME}`, `${PORT:-80}`) are left to the shell and not reported.
+fn var_refs(text: &str) -> Vec<&str> {
+    let mut refs = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("${") {
+        let Some(len) = rest[start + 2..].find('}') else { break };
+        let name = &rest[start + 2..start + 2 + len];
+        if name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+            && name.chars().any(|c| c.is_ascii_lowercase())
+        {
+            refs.push(name);
+        }
+        rest = &rest[start + 2 + len + 1..];
+    }
+    refs
+}
+
+/// Warn about `${vars}` no context of this workflow defines (they stay literal at runtime)
+fn check_vars(workflows: &IndexMap<String, Config>, wf_name: &str, config: &Config) -> Vec<Lint> {
+    // Every command sees the context; step commands and hooks also see the retry state
+    let mut known: HashSet<&str> = CONTEXT_VARS.into_iter().chain(RETRY_VARS).collect();
+    known.extend(config.vars.keys().map(String::as_str));
+    for tc in config.tasks.values() {
+        known.extend(tc.vars.keys().map(String::as_str));
+    }
+    // Vars passed in by sub-workflow steps that run this workflow
+    for other in workflows.values() {
+        for step in other.workflow.iter().filter(|s| s.workflow.as_deref() == Some(wf_name)) {
+            known.extend(step.vars.keys().map(String::as_str));
+        }
+    }
+
+    let mut lints = Vec::new();
+    let mut check = |path: String, text: &str, extra: &[&str]| {
+        for name in var_refs(text) {
+            if !known.contains(name) && !extra.contains(&name) && !name.starts_with("steps.") {
+                lints.push(Lint {
+                    severity: Severity::Warning,
+                    path: path.clone(),
+                    message: format!("'${{{}}}' is never defined (set it in vars, or pass it with --var)", name),
+                });
+            }
+        }
+    };
+
+    for (key, value) in &config.vars {
+        check(format!("$.vars.{}", key), value, &[]);
+    }
+    for (name, tc) in &config.tasks {
+        for (key, value) in &tc.vars {
+            check(format!("$.tasks.{}.vars.{}", name, key), value, &[]);
+        }
+    }
+    for (i, step) in config.workflow.iter().enumerate() {
+        let extra: &[&str] = if step.is_foreach() { &["item", "item_index"] } else { &[] };
+        for (field, text) in step_texts(step) {
+            check(format!("$.workflow[{}].{}", i, field), &text, extra);
+        }
+        for (j, sub) in step.parallel.iter().enumerate() {
+            if let Some(run) = &sub.run {
+                check(format!("$.workflow[{}].parallel[{}].run", i, j), run, &["sub_step"]);
+            }
+        }
+    }
+    for (i, step) in config.finally.iter().enumerate() {
+        if let Some(run) = &step.run {
+            check(format!("$.finally[{}].run", i), run, &["status"]);
+        }
+    }
+    let mut hooks: Vec<_> = config.on.iter().collect();
+    hooks.sort();
+    for (event, command) in hooks {
+        check(format!("$.on.{}", event), command, HOOK_VARS);
+    }
+
+    lints
+}
+
+/// The expandable strings of a step, by field
+fn step_texts(step: &Step) -> Vec<(String, String)> {
+    let mut texts: Vec<(String, String)> = [
+        ("run", &step.run),
+        ("verify", &step.verify),
+        ("compensate", &step.compensate),
+        ("when", &step.when),
+    ]
+    .into_iter()
+    .filter_map(|(field, text)| text.clone().map(|t| (field.to_string(), t)))
+    .collect();
+    for (key, value) in &step.vars {
+        texts.push((format!("vars.{}", key), value.clone()));
+    }
+    match &step.foreach {
+        Some(Foreach::List(items)) => {
+            for (k, item) in items.iter().enumerate() {
+                texts.push((format!("foreach[{}]", k), item.clone()));
+            }
+        }
+        Some(Foreach::Source(source)) => {
+            if let Some(command) = &source.command {
+                texts.push(("foreach.command".to_string(), command.clone()));
+            }
+        }
+        None => {}
+    }
+    for (name, spec) in &step.outputs {
+        if let Some(file) = &spec.file {
+            texts.push((format!("outputs.{}.file", name), file.clone()));
+        }
+    }
+    texts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_var_refs() {
+        assert_eq!(var_refs("echo ${task} ${steps.build.v} $HOME ${HOME} ${PORT:-80} ${x"), ["task", "steps.build.v"]);
+    }
+
+    #[test]
+    fn test_dependency_cycle_and_unknown_vars() {
+        let config = Config::from_str(r#"{
+            "vars": {"wt": "${project_root}/${branch}"},
+            "tasks": {"a": {"depends": ["c"]}, "b": {"depends": ["a", "ghost"]}, "c": {"depends": ["b"]}},
+            "on": {"step_finished": "echo ${exit_code} ${nope}"},
+            "workflow": [{"name": "build", "run": "make ${wt} ${item}"}]
+        }"#).unwrap();
+        let workflows = IndexMap::from([("default".to_string(), config)]);
+        let mut diagnostics = Vec::new();
+        check_workflows(&workflows, &mut diagnostics);
+
+        let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert!(messages.contains(&"task 'b' depends on 'ghost', which is not declared in any workflow"));
+        assert!(messages.contains(&"dependency cycle: a → c → b → a"));
+        let unknown: Vec<&str> = diagnostics.iter()
+            .filter(|d| d.severity == Severity::Warning)
+            .map(|d| d.path.as_str())
+            .collect();
+        assert_eq!(unknown, ["$.vars.wt", "$.workflow[0].run", "$.on.step_finished"]);
+    }
+}
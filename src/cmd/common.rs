@@ -1,44 +1,67 @@
 use anyhow::Result;
 use fs2::FileExt;
 use indexmap::IndexMap;
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::fs::{self, OpenOptions};
-use std::io::{BufRead, BufReader, Write};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
 use std::path::PathBuf;
+use std::time::Duration;
 
 use crate::error::PawlError;
-use crate::model::config::TaskConfig;
-use crate::model::event::{event_timestamp, replay, Event};
+use crate::model::config::{Dependency, DependsOn, Step, TaskConfig};
+use crate::model::event::{
+    apply_events, apply_migrations, event_timestamp, open_sub_step_groups, pinned_hash, replay_from,
+    run_vars, step_outputs, step_owner, sub_workflow_run, Event, BLOCKED, INTERRUPTED,
+};
+use crate::model::graph::TaskGraph;
+use crate::model::state::{Checkpoint, StepStatus};
 use crate::model::{Config, TaskState, TaskStatus};
+use crate::util::process::ProcessOwner;
 use crate::util::project::get_project_root;
-use crate::util::shell::spawn_background;
-use crate::util::variable::Context;
+use crate::util::shell::{spawn_background, terminate_group};
+use crate::util::variable::{Context, CONTEXT_VARS};
 use crate::viewport::{self, Viewport};
 
 /// Extract retry_count and last_feedback for the current step from events.
 pub fn extract_step_context(events: &[Event], step_idx: usize) -> (usize, Option<String>) {
     let retry_count = crate::model::event::count_auto_retries(events, step_idx);
     let mut last_feedback: Option<String> = None;
+    // An on_fail jump into this step carries the failing step's feedback
+    let mut feedback_step = step_idx;
 
     for event in events.iter().rev() {
         match event {
             Event::TaskStarted { .. } | Event::TaskReset { .. } => break,
+            Event::StepReset { resumed: true, .. } => {}
             Event::StepReset { step, auto: false, .. } if *step == step_idx => break,
-            Event::StepFinished { step, success, stdout, stderr, verify_output, .. }
-                if *step == step_idx && !*success =>
+            Event::StepJumped { step, to, reason, .. }
+                if *to == feedback_step && last_feedback.is_none() =>
             {
-                if last_feedback.is_none() {
-                    let mut parts = Vec::new();
-                    if let Some(vo) = verify_output
-                        && !vo.is_empty() { parts.push(vo.as_str()); }
-                    if let Some(out) = stdout
-                        && !out.is_empty() { parts.push(out.as_str()); }
-                    if let Some(err) = stderr
-                        && !err.is_empty() { parts.push(err.as_str()); }
-                    if !parts.is_empty() {
-                        last_feedback = Some(parts.join("\n"));
-                    }
+                if reason != "on_fail" {
+                    break;
                 }
+                feedback_step = *step;
+            }
+            Event::StepFinished { step, success, stdout, stderr, verify_output, timed_out, .. }
+                if *step == feedback_step && !*success && last_feedback.is_none() =>
+            {
+                let mut parts = Vec::new();
+                if *timed_out && verify_output.is_none() { parts.push("Timed out"); }
+                if let Some(vo) = verify_output
+                    && !vo.is_empty() { parts.push(vo.as_str()); }
+                if let Some(out) = stdout
+                    && !out.is_empty() { parts.push(out.as_str()); }
+                if let Some(err) = stderr
+                    && !err.is_empty() { parts.push(err.as_str()); }
+                if !parts.is_empty() {
+                    last_feedback = Some(parts.join("\n"));
+                }
+            }
+            Event::StepRejected { step, message: Some(msg), .. }
+                if *step == feedback_step && last_feedback.is_none() =>
+            {
+                last_feedback = Some(msg.clone());
             }
             _ => {}
         }
@@ -47,19 +70,151 @@ pub fn extract_step_context(events: &[Event], step_idx: usize) -> (usize, Option
     (retry_count, last_feedback)
 }
 
+/// Parse JSONL event lines (blank lines are ignored)
+fn parse_events(reader: impl BufRead) -> Result<Vec<Event>> {
+    let mut events = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        events.push(serde_json::from_str(&line)?);
+    }
+    Ok(events)
+}
+
 pub const PAWL_DIR: &str = ".pawl";
 
+/// Events replayed past a checkpoint before `replay_task` saves a new one
+const CHECKPOINT_INTERVAL: usize = 100;
+
+/// Task name of the child run started by a parent task's sub-workflow step
+pub fn child_task_name(parent: &str, step_name: &str) -> String {
+    format!("{}/{}", parent, step_name)
+}
+
+/// Reject sub-workflow steps naming an unknown workflow, or workflows that
+/// (indirectly) run themselves.
+fn validate_sub_workflows(workflows: &IndexMap<String, Config>) -> Result<()> {
+    fn visit<'a>(
+        workflows: &'a IndexMap<String, Config>,
+        name: &'a str,
+        path: &mut Vec<&'a str>,
+    ) -> Result<()> {
+        if path.contains(&name) {
+            path.push(name);
+            anyhow::bail!("sub-workflow cycle: {}", path.join(" → "));
+        }
+        path.push(name);
+        for target in workflows[name].sub_workflows() {
+            if !workflows.contains_key(target) {
+                anyhow::bail!("workflow '{}' runs unknown sub-workflow '{}'", name, target);
+            }
+            visit(workflows, target, path)?;
+        }
+        path.pop();
+        Ok(())
+    }
+
+    for name in workflows.keys() {
+        visit(workflows, name, &mut Vec::new()).map_err(|e| PawlError::Validation {
+            message: e.to_string(),
+        })?;
+    }
+    Ok(())
+}
+
+/// Run a workflow's `tasks_from` command and add the tasks it prints.
+/// Output is JSON: a list of task names, or an object shaped like `tasks` (templates allowed).
+fn add_generated_tasks(project_root: &str, wf_name: &str, command: &str, config: &mut Config) -> Result<()> {
+    let invalid = |message: String| PawlError::Validation { message };
+    let output = std::process::Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .current_dir(project_root)
+        .env("PAWL_PROJECT_ROOT", project_root)
+        .env("PAWL_WORKFLOW", wf_name)
+        .output()?;
+    if !output.status.success() {
+        return Err(invalid(format!(
+            "workflow '{}' tasks_from failed (exit code {}): {}",
+            wf_name,
+            output.status.code().unwrap_or(-1),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )).into());
+    }
+
+    #[derive(serde::Deserialize)]
+    #[serde(untagged)]
+    enum Generated {
+        Names(Vec<String>),
+        Tasks(IndexMap<String, TaskConfig>),
+    }
+    let generated: Generated = serde_json::from_slice(&output.stdout).map_err(|e| {
+        invalid(format!("workflow '{}' tasks_from printed invalid JSON: {}", wf_name, e))
+    })?;
+    let tasks = match generated {
+        Generated::Names(names) => names.into_iter().map(|n| (n, TaskConfig::default())).collect(),
+        Generated::Tasks(tasks) => tasks,
+    };
+    let tasks = crate::model::config::expand_task_templates(tasks)
+        .map_err(|e| invalid(format!("workflow '{}' tasks_from: {}", wf_name, e)))?;
+    for (name, tc) in tasks {
+        if config.tasks.contains_key(&name) {
+            return Err(invalid(format!(
+                "workflow '{}' tasks_from generated '{}', which is already declared",
+                wf_name, name
+            )).into());
+        }
+        config.tasks.insert(name, tc);
+    }
+    Ok(())
+}
+
+/// Read pinned workflow definitions; unreadable snapshots are skipped (runs fall back to the live config)
+fn load_snapshots(dir: &std::path::Path) -> HashMap<String, Config> {
+    let Ok(entries) = fs::read_dir(dir) else { return HashMap::new() };
+    entries
+        .filter_map(|e| e.ok())
+        .filter_map(|e| {
+            let path = e.path();
+            let hash = path.file_stem()?.to_str()?.to_string();
+            let config = serde_json::from_str(&fs::read_to_string(&path).ok()?).ok()?;
+            Some((hash, config))
+        })
+        .collect()
+}
+
 /// Project context with loaded workflows
 pub struct Project {
     pub project_root: String,
     pub pawl_dir: PathBuf,
     workflows: IndexMap<String, Config>,
     task_index: HashMap<String, String>,
+    /// Dependencies between declared tasks (acyclic)
+    graph: TaskGraph,
+    /// Definition hash of each live workflow
+    definition_hashes: HashMap<String, String>,
+    /// Pinned workflow definitions by hash (`.pawl/snapshots/<hash>.json`)
+    snapshots: HashMap<String, Config>,
+    /// Definition hash each task's current run is pinned to, read from its log on first use
+    pins: RefCell<HashMap<String, Option<String>>>,
 }
 
 impl Project {
     /// Load project from current directory — scans .pawl/workflows/*.json
     pub fn load() -> Result<Self> {
+        let project = Self::load_quiet()?;
+        for config in project.workflows.values() {
+            for lint in config.lint() {
+                eprintln!("Warning: {}", lint.message);
+            }
+        }
+        Ok(project)
+    }
+
+    /// Like `load`, without printing config warnings (`pawl check` reports them itself)
+    pub fn load_quiet() -> Result<Self> {
         let project_root = get_project_root()?;
         let pawl_dir = PathBuf::from(&project_root).join(PAWL_DIR);
         let workflows_dir = pawl_dir.join("workflows");
@@ -89,7 +244,10 @@ impl Project {
                 continue;
             }
 
-            let config = Config::load_from(&path)?;
+            let mut config = Config::load_from(&path)?;
+            if let Some(command) = config.tasks_from.clone() {
+                add_generated_tasks(&project_root, &wf_name, &command, &mut config)?;
+            }
 
             // Validate task name uniqueness across workflows
             for task_name in config.tasks.keys() {
@@ -112,22 +270,93 @@ impl Project {
                 message: "No workflow files found in .pawl/workflows/. Run 'pawl init' first.".into(),
             }.into());
         }
+        validate_sub_workflows(&workflows)?;
+        let graph = TaskGraph::new(&workflows);
+        if let Some(cycle) = graph.cycles().first() {
+            return Err(PawlError::Validation {
+                message: format!("dependency cycle: {}", cycle.join(" → ")),
+            }.into());
+        }
+
+        let definition_hashes = workflows.iter()
+            .map(|(name, config)| (name.clone(), config.definition_hash()))
+            .collect();
+        let snapshots = load_snapshots(&pawl_dir.join("snapshots"));
 
         Ok(Self {
             project_root,
             pawl_dir,
             workflows,
             task_index,
+            graph,
+            definition_hashes,
+            snapshots,
+            pins: RefCell::new(HashMap::new()),
         })
     }
 
-    /// Find the workflow name and config for a given task.
-    /// Falls back to the first workflow if the task is undeclared (e.g. ad-hoc tasks).
+    /// Snapshot the live definition of a workflow for a run to pin; returns its hash.
+    pub fn pin_workflow(&self, wf_name: &str) -> Result<String> {
+        let hash = self.definition_hashes.get(wf_name).cloned().unwrap_or_default();
+        let path = self.pawl_dir.join("snapshots").join(format!("{}.json", hash));
+        if !path.exists() {
+            fs::create_dir_all(path.parent().unwrap())?;
+            let definition = self.workflows[wf_name].definition();
+            fs::write(&path, serde_json::to_string_pretty(&definition)?)?;
+        }
+        Ok(hash)
+    }
+
+    /// Definition hash the task's current run is pinned to (None: unpinned or not started)
+    pub fn pin_of(&self, task_name: &str) -> Option<String> {
+        if let Some(pin) = self.pins.borrow().get(task_name) {
+            return pin.clone();
+        }
+        let pin = self.read_since_checkpoint(task_name, usize::MAX).ok()
+            .and_then(|(checkpoint, events)| pinned_hash(&events).unwrap_or(checkpoint.pin));
+        self.pins.borrow_mut().insert(task_name.to_string(), pin.clone());
+        pin
+    }
+
+    /// Whether the task's run is pinned to a definition other than the live one
+    pub fn workflow_drifted(&self, task_name: &str) -> bool {
+        let Ok((wf_name, _)) = self.live_workflow_for(task_name) else { return false };
+        matches!(self.pin_of(task_name), Some(pin) if Some(&pin) != self.definition_hashes.get(wf_name))
+    }
+
+    /// Find the workflow name and config for a given task: the definition its
+    /// current run is pinned to, or the live one (see `live_workflow_for`).
     pub fn workflow_for(&self, task_name: &str) -> Result<(&str, &Config)> {
+        let (wf_name, config) = self.live_workflow_for(task_name)?;
+        if let Some(pin) = self.pin_of(task_name)
+            && Some(&pin) != self.definition_hashes.get(wf_name)
+            && let Some(pinned) = self.snapshots.get(&pin)
+        {
+            return Ok((wf_name, pinned));
+        }
+        Ok((wf_name, config))
+    }
+
+    /// Pinned definition by hash, if its snapshot exists
+    pub fn snapshot(&self, hash: &str) -> Option<&Config> {
+        self.snapshots.get(hash)
+    }
+
+    /// Find the workflow name and live config for a given task.
+    /// A child run (`<parent>/<step>`) uses the workflow its parent's step names.
+    /// Falls back to the first workflow if the task is undeclared (e.g. ad-hoc tasks).
+    pub fn live_workflow_for(&self, task_name: &str) -> Result<(&str, &Config)> {
         if let Some(wf_name) = self.task_index.get(task_name) {
             let config = self.workflows.get(wf_name).unwrap();
             return Ok((wf_name, config));
         }
+        if let Some((parent, step_name)) = task_name.rsplit_once('/')
+            && let Ok((_, parent_config)) = self.workflow_for(parent)
+            && let Some(step) = parent_config.workflow.iter().find(|s| s.name == step_name)
+            && let Some((wf_name, config)) = step.workflow.as_deref().and_then(|w| self.workflows.get_key_value(w))
+        {
+            return Ok((wf_name, config));
+        }
         // Undeclared task: use the first workflow (backward-compatible with single-workflow projects)
         if self.workflows.len() == 1 {
             let (name, config) = self.workflows.first().unwrap();
@@ -153,6 +382,13 @@ impl Project {
         &self.workflows
     }
 
+    /// Grace period between SIGTERM and SIGKILL for a task's step processes
+    pub fn grace_period_for(&self, task_name: &str) -> Duration {
+        self.workflow_for(task_name)
+            .map(|(_, c)| c.effective_grace_period())
+            .unwrap_or(Duration::from_secs(5))
+    }
+
     /// Get session name for a task's workflow
     pub fn session_name_for(&self, task_name: &str) -> Result<String> {
         let (_, config) = self.workflow_for(task_name)?;
@@ -161,7 +397,7 @@ impl Project {
 
     /// Build a Context for variable expansion / env vars.
     /// Intrinsic vars first, then user vars from the task's workflow config.vars (expanded in order).
-    pub fn context_for(&self, task_name: &str, step_idx: Option<usize>, run_id: &str) -> Context {
+    pub fn context_for(&self, task_name: &str, step_idx: Option<usize>, run_id: &str) -> Result<Context> {
         let (wf_name, config) = self.workflow_for(task_name).unwrap_or_else(|_| {
             let (name, config) = self.workflows.first().unwrap();
             (name, config)
@@ -174,31 +410,52 @@ impl Project {
 
         let session = config.session_name(&self.project_root);
 
-        let mut ctx = Context::build()
-            .var("task", task_name)
-            .var("workflow", wf_name)
-            .var("session", session)
-            .var("project_root", &self.project_root)
-            .var("step", step_name)
-            .var("step_index", step_idx.map(|i| i.to_string()).unwrap_or_default())
-            .var("log_file", self.log_file(task_name).to_string_lossy())
-            .var("run_id", run_id);
+        // In CONTEXT_VARS order
+        let values = [
+            task_name.to_string(),
+            wf_name.to_string(),
+            session,
+            self.project_root.clone(),
+            step_name.to_string(),
+            step_idx.map(|i| i.to_string()).unwrap_or_default(),
+            self.log_file(task_name).to_string_lossy().into_owned(),
+            run_id.to_string(),
+        ];
+        let mut ctx = CONTEXT_VARS.into_iter()
+            .zip(values)
+            .fold(Context::build(), |ctx, (name, value)| ctx.var(name, value));
+
+        // Outputs captured by earlier steps of this run (recorded in the log, so they survive crashes)
+        let events = self.read_events(task_name)?;
+        for (idx, outputs) in step_outputs(&events) {
+            let Some(step) = config.workflow.get(idx) else { continue };
+            for (name, value) in outputs {
+                ctx = ctx.var_owned(format!("steps.{}.{}", step.name, name), value);
+            }
+        }
+
+        // Vars the run was started with (`--var`, or a sub-workflow step's vars) are final:
+        // they shadow config vars of the same name, and config vars can reference them
+        let run_vars = run_vars(&events);
+        for (key, value) in &run_vars {
+            ctx = ctx.var_owned(key.clone(), value.clone());
+        }
 
         // Expand workflow-level vars in definition order
-        for (key, value) in &config.vars {
+        for (key, value) in config.vars.iter().filter(|(k, _)| !run_vars.contains_key(*k)) {
             let expanded = ctx.expand(value);
             ctx = ctx.var_owned(key.clone(), expanded);
         }
 
         // Expand task-local vars (shadow workflow vars)
-        if let Some(tc) = config.tasks.get(task_name) {
-            for (key, value) in &tc.vars {
+        if let Some(tc) = self.task_config(task_name) {
+            for (key, value) in tc.vars.iter().filter(|(k, _)| !run_vars.contains_key(*k)) {
                 let expanded = ctx.expand(value);
                 ctx = ctx.var_owned(key.clone(), expanded);
             }
         }
 
-        ctx
+        Ok(ctx)
     }
 
     /// Get step name by index for a task, returns "done" if past end.
@@ -227,15 +484,17 @@ impl Project {
         }
     }
 
-    /// Discover all declared tasks across workflows, sorted
+    /// Discover all declared tasks across workflows, in dependency order (ties by name)
     pub fn discover_tasks(&self) -> Result<Vec<String>> {
-        let mut names: std::collections::BTreeSet<String> = std::collections::BTreeSet::new();
-        for config in self.workflows.values() {
-            names.extend(config.tasks.keys().cloned());
-        }
-        Ok(names.into_iter().collect())
+        Ok(self.graph.order().into_iter().map(String::from).collect())
     }
 
+    /// Task dependency graph across all workflows
+    pub fn graph(&self) -> &TaskGraph {
+        &self.graph
+    }
+
+
     /// Resolve task name from name or 1-based index
     pub fn resolve_task_name(&self, name_or_index: &str) -> Result<String> {
         if let Ok(index) = name_or_index.parse::<usize>() {
@@ -250,23 +509,126 @@ impl Project {
         Ok(name_or_index.to_string())
     }
 
-    /// Check if all dependencies of a task are completed
-    pub fn check_dependencies(&self, task_name: &str) -> Result<Vec<String>> {
-        let depends = match self.task_config(task_name) {
-            Some(tc) => &tc.depends,
-            None => return Ok(Vec::new()),
+    /// Dependencies of a task whose condition is not met yet, as declared
+    pub fn check_dependencies(&self, task_name: &str) -> Result<Vec<Dependency>> {
+        let mut blocking = Vec::new();
+        for dep in self.dependencies(task_name) {
+            if !self.dependency_met(dep)? {
+                blocking.push(dep.clone());
+            }
+        }
+        Ok(blocking)
+    }
+
+    /// Declared `depends` entries of a task (empty for undeclared tasks)
+    fn dependencies(&self, task_name: &str) -> &[Dependency] {
+        self.task_config(task_name).map(|tc| tc.depends.as_slice()).unwrap_or_default()
+    }
+
+    /// Whether a dependency's task has reached the state its condition asks for
+    pub fn dependency_met(&self, dep: &Dependency) -> Result<bool> {
+        if self.is_completed(dep.task())? {
+            return Ok(dep.on() != DependsOn::Failed);
+        }
+        let Some(state) = self.replay_task(dep.task())? else {
+            return Ok(false);
         };
+        Ok(match dep.on() {
+            DependsOn::Completed => false,
+            DependsOn::Failed => state.status == TaskStatus::Failed,
+            DependsOn::Finished => match state.status {
+                TaskStatus::Failed => true,
+                TaskStatus::Stopped => state.message.as_deref() != Some(INTERRUPTED),
+                _ => false,
+            },
+        })
+    }
+
+    /// The status a dependency's task settled in the other way, ruling its
+    /// condition out for good (e.g. completed, for `on: failed`)
+    pub fn dependency_settled(&self, dep: &Dependency) -> Result<Option<TaskStatus>> {
+        let Some(state) = self.replay_task(dep.task())? else {
+            return Ok(None);
+        };
+        let settled = match dep.on() {
+            DependsOn::Completed => matches!(state.status, TaskStatus::Failed | TaskStatus::Stopped),
+            DependsOn::Failed => matches!(state.status, TaskStatus::Completed | TaskStatus::Stopped),
+            DependsOn::Finished => false,
+        };
+        Ok(settled.then_some(state.status))
+    }
+
+    /// Tasks a task waits on (dependencies first): direct dependencies whose
+    /// condition is unmet, and incomplete tasks further upstream
+    pub fn blockers(&self, task_name: &str) -> Result<Vec<String>> {
+        let direct = self.dependencies(task_name);
         let mut blocking = Vec::new();
-        for dep in depends {
-            let state = self.replay_task(dep)?;
-            match state {
-                Some(s) if s.status == TaskStatus::Completed => {}
-                _ => blocking.push(dep.clone()),
+        for dep in self.graph.ancestors(task_name) {
+            let blocks = match direct.iter().find(|d| d.task() == dep) {
+                Some(d) => !self.dependency_met(d)?,
+                None => !self.is_completed(dep)?,
+            };
+            if blocks {
+                blocking.push(dep.to_string());
             }
         }
         Ok(blocking)
     }
 
+    /// Pending tasks that become ready once this (incomplete) task completes
+    pub fn unlocks(&self, task_name: &str) -> Result<Vec<String>> {
+        let mut ready = Vec::new();
+        if self.is_completed(task_name)? {
+            return Ok(ready);
+        }
+        for dependent in self.graph.dependents(task_name) {
+            if self.replay_task(dependent)?.is_some() {
+                continue;
+            }
+            let deps = self.dependencies(dependent);
+            if deps.iter().any(|d| d.task() == task_name && d.on() == DependsOn::Failed) {
+                continue;
+            }
+            let mut others_done = true;
+            for dep in deps.iter().filter(|d| d.task() != task_name) {
+                others_done &= self.dependency_met(dep)?;
+            }
+            if others_done {
+                ready.push(dependent.to_string());
+            }
+        }
+        Ok(ready)
+    }
+
+    /// Completed, and not stale
+    fn is_completed(&self, task_name: &str) -> Result<bool> {
+        let completed = self.replay_task(task_name)?.is_some_and(|s| s.status == TaskStatus::Completed);
+        Ok(completed && !self.is_stale(task_name)?)
+    }
+
+    /// A completed task with `invalidate_on_upstream_reset` whose dependency was reset
+    /// or started a new run after it completed
+    pub fn is_stale(&self, task_name: &str) -> Result<bool> {
+        if !self.task_config(task_name).is_some_and(|tc| tc.invalidate_on_upstream_reset) {
+            return Ok(false);
+        }
+        let Some(completed_at) = self.replay_task(task_name)?
+            .filter(|s| s.status == TaskStatus::Completed)
+            .and_then(|s| s.updated_at)
+        else {
+            return Ok(false);
+        };
+        for dep in self.graph.depends(task_name) {
+            let changed = self.read_events(dep)?.iter().rev()
+                .find(|e| matches!(e, Event::TaskStarted { .. } | Event::TaskReset { .. }))
+                .is_some_and(|e| e.ts() > completed_at);
+            if changed {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
     /// Get the JSONL log file path for a task
     pub fn log_file(&self, task_name: &str) -> PathBuf {
         self.pawl_dir.join("logs").join(format!("{}.jsonl", task_name))
@@ -277,6 +639,16 @@ impl Project {
         self.pawl_dir.join("streams").join(format!("{}.stream", task_name))
     }
 
+    /// Get the replay checkpoint path for a task
+    pub fn checkpoint_file(&self, task_name: &str) -> PathBuf {
+        self.pawl_dir.join("checkpoints").join(format!("{}.json", task_name))
+    }
+
+    /// Get the archive path for a task (earlier runs moved out of its log by `pawl compact`)
+    pub fn archive_file(&self, task_name: &str) -> PathBuf {
+        self.pawl_dir.join("archive").join(format!("{}.jsonl", task_name))
+    }
+
     /// Append an event to the task's JSONL log file (with exclusive file lock),
     /// then auto-fire any matching hook from the task's workflow config.on.
     pub fn append_event(&self, task_name: &str, event: &Event) -> Result<()> {
@@ -296,6 +668,7 @@ impl Project {
         writeln!(file, "{}", json)?;
 
         file.unlock()?;
+        self.forget_pin(task_name, event);
 
         // Auto-fire hook if configured
         self.spawn_event_hook(task_name, event);
@@ -303,6 +676,42 @@ impl Project {
         Ok(())
     }
 
+    /// Append an event only if `condition` holds for the log as it stands, checked
+    /// under the same exclusive lock (so concurrent callers can't both claim).
+    /// Returns whether the event was appended.
+    pub fn append_event_if(
+        &self,
+        task_name: &str,
+        event: &Event,
+        condition: impl FnOnce(&[Event]) -> bool,
+    ) -> Result<bool> {
+        let log_file = self.log_file(task_name);
+        fs::create_dir_all(log_file.parent().unwrap())?;
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(&log_file)?;
+
+        file.lock_exclusive()?;
+
+        let events = apply_migrations(parse_events(BufReader::new(&file))?);
+        if !condition(&events) {
+            file.unlock()?;
+            return Ok(false);
+        }
+        let json = serde_json::to_string(event)?;
+        writeln!(file, "{}", json)?;
+
+        file.unlock()?;
+        self.forget_pin(task_name, event);
+
+        self.spawn_event_hook(task_name, event);
+
+        Ok(true)
+    }
+
     /// Read all events from the task's JSONL log file
     pub fn read_events(&self, task_name: &str) -> Result<Vec<Event>> {
         let log_file = self.log_file(task_name);
@@ -313,28 +722,163 @@ impl Project {
 
         let file = fs::File::open(&log_file)?;
         file.lock_shared()?;
-        let reader = BufReader::new(file);
-        let mut events = Vec::new();
+        Ok(apply_migrations(parse_events(BufReader::new(file))?))
+    }
 
-        for line in reader.lines() {
-            let line = line?;
-            if line.trim().is_empty() {
-                continue;
-            }
-            let event: Event = serde_json::from_str(&line)?;
-            events.push(event);
+    /// Drop the cached pin when an event may change which definition the run uses
+    fn forget_pin(&self, task_name: &str, event: &Event) {
+        if matches!(event, Event::TaskStarted { .. } | Event::TaskReset { .. } | Event::WorkflowMigrated { .. }) {
+            self.pins.borrow_mut().remove(task_name);
         }
-
-        Ok(events)
     }
 
-    /// Replay events to reconstruct current TaskState
+    /// Replay events to reconstruct current TaskState, picking up from the task's checkpoint
     pub fn replay_task(&self, task_name: &str) -> Result<Option<TaskState>> {
-        let events = self.read_events(task_name)?;
+        let (checkpoint, events) = self.read_since_checkpoint(task_name, CHECKPOINT_INTERVAL)?;
         let workflow_len = self.workflow_for(task_name)
             .map(|(_, c)| c.workflow.len())
             .unwrap_or(0);
-        Ok(replay(&events, workflow_len))
+        Ok(replay_from(checkpoint.state, &events, workflow_len))
+    }
+
+    /// Events after the task's checkpoint, with that checkpoint (an empty one when there
+    /// is none or it no longer fits the log). Once `checkpoint_after` or more events follow
+    /// it, they are folded into a new checkpoint at the end of the log.
+    fn read_since_checkpoint(&self, task_name: &str, checkpoint_after: usize) -> Result<(Checkpoint, Vec<Event>)> {
+        let log_file = self.log_file(task_name);
+        if !log_file.exists() {
+            return Ok((Checkpoint::default(), Vec::new()));
+        }
+
+        let mut file = fs::File::open(&log_file)?;
+        file.lock_shared()?;
+
+        let mut checkpoint = self.load_checkpoint(task_name, &mut file)?.unwrap_or_default();
+        let mut tail = Vec::new();
+        file.seek(SeekFrom::Start(checkpoint.offset))?;
+        file.read_to_end(&mut tail)?;
+        let mut events = parse_events(&tail[..])?;
+        // A migration rewrites the events before it, checkpointed ones included
+        if checkpoint.offset > 0 && events.iter().any(|e| matches!(e, Event::WorkflowMigrated { .. })) {
+            checkpoint = Checkpoint::default();
+            tail.clear();
+            file.seek(SeekFrom::Start(0))?;
+            file.read_to_end(&mut tail)?;
+            events = parse_events(&tail[..])?;
+        }
+        let events = apply_migrations(events);
+
+        if !events.is_empty() && events.len() >= checkpoint_after && tail.ends_with(b"\n") {
+            checkpoint = Checkpoint {
+                offset: checkpoint.offset + tail.len() as u64,
+                pin: pinned_hash(&events).unwrap_or(checkpoint.pin),
+                state: apply_events(checkpoint.state, &events),
+            };
+            // Saved under the shared lock, so a compaction can't rewrite the log meanwhile
+            self.save_checkpoint(task_name, &checkpoint);
+            return Ok((checkpoint, Vec::new()));
+        }
+        Ok((checkpoint, events))
+    }
+
+    /// The task's checkpoint, if it still fits the log: within it, and ending at a line break
+    fn load_checkpoint(&self, task_name: &str, log: &mut fs::File) -> Result<Option<Checkpoint>> {
+        let Some(checkpoint) = fs::read_to_string(self.checkpoint_file(task_name)).ok()
+            .and_then(|content| serde_json::from_str::<Checkpoint>(&content).ok())
+        else {
+            return Ok(None);
+        };
+        if checkpoint.offset == 0 {
+            return Ok(Some(checkpoint));
+        }
+        if checkpoint.offset > log.metadata()?.len() {
+            return Ok(None);
+        }
+        let mut last = [0u8];
+        log.seek(SeekFrom::Start(checkpoint.offset - 1))?;
+        log.read_exact(&mut last)?;
+        Ok((last[0] == b'\n').then_some(checkpoint))
+    }
+
+    /// Best-effort: a checkpoint only saves replay work, so failing to write one is no error
+    fn save_checkpoint(&self, task_name: &str, checkpoint: &Checkpoint) {
+        let path = self.checkpoint_file(task_name);
+        let tmp = path.with_extension(format!("json.{}", std::process::id()));
+        let Ok(json) = serde_json::to_string(checkpoint) else { return };
+        let written = fs::create_dir_all(path.parent().unwrap())
+            .and_then(|_| fs::write(&tmp, json))
+            .and_then(|_| fs::rename(&tmp, &path));
+        if written.is_err() {
+            let _ = fs::remove_file(&tmp);
+        }
+    }
+
+    /// Checkpoint the task's log as it stands now
+    pub fn checkpoint(&self, task_name: &str) -> Result<()> {
+        self.read_since_checkpoint(task_name, 0)?;
+        Ok(())
+    }
+
+    /// Move the runs before the last `task_reset` out of the task's log and onto the end
+    /// of its archive. The log keeps that reset and everything after it, so it replays to
+    /// the same state. Returns how many events were archived and how many kept.
+    pub fn compact_log(&self, task_name: &str) -> Result<(usize, usize)> {
+        let log_file = self.log_file(task_name);
+        if !log_file.exists() {
+            return Ok((0, 0));
+        }
+
+        let mut file = OpenOptions::new().read(true).write(true).open(&log_file)?;
+        file.lock_exclusive()?;
+
+        let mut content = String::new();
+        file.read_to_string(&mut content)?;
+        let mut lines = Vec::new();
+        let mut cut = 0;
+        for line in content.lines().filter(|l| !l.trim().is_empty()) {
+            if matches!(serde_json::from_str::<Event>(line)?, Event::TaskReset { .. }) {
+                cut = lines.len();
+            }
+            lines.push(line);
+        }
+        if cut == 0 {
+            file.unlock()?;
+            return Ok((0, lines.len()));
+        }
+
+        let archive_file = self.archive_file(task_name);
+        fs::create_dir_all(archive_file.parent().unwrap())?;
+        let mut archive = OpenOptions::new().create(true).append(true).open(&archive_file)?;
+        archive.write_all(lines[..cut].iter().map(|l| format!("{}\n", l)).collect::<String>().as_bytes())?;
+        archive.sync_all()?;
+
+        // Rewritten in place rather than replaced: processes waiting for the lock
+        // hold this same file, and append to the compacted log once they get it
+        file.set_len(0)?;
+        file.seek(SeekFrom::Start(0))?;
+        file.write_all(lines[cut..].iter().map(|l| format!("{}\n", l)).collect::<String>().as_bytes())?;
+        file.sync_all()?;
+        // Its offset pointed into the old log
+        let _ = fs::remove_file(self.checkpoint_file(task_name));
+
+        file.unlock()?;
+        Ok((cut, lines.len() - cut))
+    }
+
+    /// Every event recorded for a task: its archive, then its log
+    pub fn read_full_history(&self, task_name: &str) -> Result<Vec<Event>> {
+        let mut events = Vec::new();
+        let archive_file = self.archive_file(task_name);
+        if archive_file.exists() {
+            events = parse_events(BufReader::new(fs::File::open(&archive_file)?))?;
+        }
+        let log_file = self.log_file(task_name);
+        if log_file.exists() {
+            let file = fs::File::open(&log_file)?;
+            file.lock_shared()?;
+            events.extend(parse_events(BufReader::new(file))?);
+        }
+        Ok(apply_migrations(events))
     }
 
     /// Check viewport health. If a Running in_viewport step's viewport is gone, emit ViewportLost.
@@ -345,29 +889,93 @@ impl Project {
         if let Some(ref s) = state
             && s.status == TaskStatus::Running {
                 let step_idx = s.current_step;
-                if let Ok((_, config)) = self.workflow_for(task_name) {
-                    if step_idx < config.workflow.len()
-                        && config.workflow[step_idx].in_viewport
-                    {
-                        if let Ok(vp) = self.viewport_for(task_name) {
-                            if !vp.exists(task_name) {
-                                self.append_event(
-                                    task_name,
-                                    &Event::ViewportLost {
-                                        ts: event_timestamp(),
-                                        step: step_idx,
-                                    },
-                                )?;
-                                return Ok(false);
-                            }
-                        }
-                    }
+                if let Ok((_, config)) = self.workflow_for(task_name)
+                    && step_idx < config.workflow.len()
+                    && config.workflow[step_idx].in_viewport
+                    && let Ok(vp) = self.viewport_for(task_name)
+                    && !vp.exists(task_name)
+                {
+                    self.append_event(
+                        task_name,
+                        &Event::ViewportLost {
+                            ts: event_timestamp(),
+                            step: step_idx,
+                        },
+                    )?;
+                    return Ok(false);
                 }
             }
 
         Ok(true)
     }
 
+    /// Check step ownership. If a Running synchronous step's owner process is gone
+    /// (killed, crashed, rebooted), emit StepInterrupted.
+    /// Returns true = healthy (or not applicable), false = StepInterrupted emitted.
+    pub fn detect_owner_loss(&self, task_name: &str) -> Result<bool> {
+        let Some(state) = self.replay_task(task_name)? else {
+            return Ok(true);
+        };
+        if state.status != TaskStatus::Running {
+            return Ok(true);
+        }
+
+        let events = self.read_events(task_name)?;
+        let Some(owner) = step_owner(&events) else {
+            // A sub-workflow step is driven by its child run: lost with it
+            if let Some(child) = self.active_child(task_name)? {
+                self.detect_owner_loss(&child)?;
+                if self.replay_task(&child)?.is_some_and(|s| {
+                    s.status == TaskStatus::Stopped && s.message.as_deref() == Some(INTERRUPTED)
+                }) {
+                    self.append_event(task_name, &Event::StepInterrupted {
+                        ts: event_timestamp(),
+                        step: state.current_step,
+                    })?;
+                    return Ok(false);
+                }
+            }
+            return Ok(true);
+        };
+        let process = ProcessOwner {
+            pid: owner.pid,
+            hostname: owner.hostname,
+            boot_id: owner.boot_id,
+        };
+        if !process.is_dead() {
+            return Ok(true);
+        }
+
+        // The step's process group may have outlived its owner — don't leave it running
+        // (after a reboot the recorded pgid means nothing)
+        if process.same_boot() {
+            let grace = self.grace_period_for(task_name);
+            for pgid in owner.pgid.into_iter().chain(open_sub_step_groups(&events)) {
+                terminate_group(pgid, grace);
+            }
+        }
+
+        self.append_event(task_name, &Event::StepInterrupted {
+            ts: event_timestamp(),
+            step: state.current_step,
+        })?;
+        Ok(false)
+    }
+
+    /// The child run started by the task's current step, if that is a sub-workflow
+    /// step and the child's log still holds that run.
+    pub fn active_child(&self, task_name: &str) -> Result<Option<String>> {
+        let Some(state) = self.replay_task(task_name)? else {
+            return Ok(None);
+        };
+        let events = self.read_events(task_name)?;
+        let Some((child, run_id)) = sub_workflow_run(&events, state.current_step) else {
+            return Ok(None);
+        };
+        let current = self.replay_task(&child)?.is_some_and(|s| s.run_id == run_id);
+        Ok(current.then_some(child))
+    }
+
     /// Fire a hook for an event (fire-and-forget).
     /// Looks up the task's workflow config.on by the event's serde tag name.
     fn spawn_event_hook(&self, task_name: &str, event: &Event) {
@@ -386,16 +994,19 @@ impl Project {
             .flatten()
             .map(|s| s.run_id)
             .unwrap_or_default();
-        let mut ctx = self.context_for(task_name, step_idx, &run_id);
+        let mut ctx = match self.context_for(task_name, step_idx, &run_id) {
+            Ok(ctx) => ctx,
+            Err(e) => {
+                eprintln!("Warning: hook '{}' skipped: {}", event_type, e);
+                return;
+            }
+        };
 
         // Inject retry context variables
         if let Some(si) = step_idx {
             let events = self.read_events(task_name).unwrap_or_default();
             let (retry_count, last_feedback) = extract_step_context(&events, si);
-            ctx = ctx.var("retry_count", retry_count.to_string());
-            if let Some(fb) = &last_feedback {
-                ctx = ctx.var("last_verify_output", fb);
-            }
+            ctx = ctx.retry(retry_count, last_feedback.as_deref());
         }
 
         // Extend with event-specific variables (${exit_code}, ${duration}, etc.)
@@ -408,9 +1019,84 @@ impl Project {
         }
     }
 
+    /// A step of this task succeeded: resume every task blocked on `wait_for` that has
+    /// nothing left to wait for (they re-check, and claim the resume, in their own `pawl start`)
+    pub fn release_blocked(&self, task_name: &str) {
+        let waited_on = self.workflows.values()
+            .flat_map(|config| &config.workflow)
+            .any(|step| !step.wait_for.is_empty());
+        if !waited_on {
+            return;
+        }
+        let Ok(mut tasks) = self.discover_tasks() else { return };
+        // Child runs of sub-workflow steps can be blocked too
+        let mut i = 0;
+        while i < tasks.len() {
+            if let Ok(Some(child)) = self.active_child(&tasks[i]) {
+                tasks.push(child);
+            }
+            i += 1;
+        }
+        for other in tasks.iter().filter(|t| *t != task_name) {
+            if matches!(self.blocked_on(other), Ok(Some(unmet)) if unmet.is_empty())
+                && let Err(e) = self.spawn_start(other)
+            {
+                eprintln!("Warning: could not resume '{}': {}", other, e);
+            }
+        }
+    }
+
+    /// Run `pawl start <task>` in the background (fire-and-forget)
+    pub fn spawn_start(&self, task_name: &str) -> Result<()> {
+        std::process::Command::new(std::env::current_exe()?)
+            .args(["start", task_name])
+            .current_dir(&self.project_root)
+            .stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .spawn()?;
+        Ok(())
+    }
+
+    /// `wait_for` entries of a step (`"task:step"`) whose step hasn't succeeded
+    /// in that task's current run
+    pub fn unmet_wait_for(&self, step: &Step) -> Result<Vec<String>> {
+        let mut unmet = Vec::new();
+        for target in &step.wait_for {
+            let met = match target.split_once(':') {
+                Some((task, name)) => match (self.replay_task(task)?, self.workflow_for(task)) {
+                    (Some(state), Ok((_, config))) => config.step_index(name)
+                        .is_some_and(|i| state.step_status.get(&i) == Some(&StepStatus::Success)),
+                    _ => false,
+                },
+                None => false,
+            };
+            if !met {
+                unmet.push(target.clone());
+            }
+        }
+        Ok(unmet)
+    }
+
+    /// For a task blocked at a `wait_for` step, what it still waits for (None if not blocked)
+    pub fn blocked_on(&self, task_name: &str) -> Result<Option<Vec<String>>> {
+        let Some(state) = self.replay_task(task_name)? else {
+            return Ok(None);
+        };
+        if state.status != TaskStatus::Waiting || state.message.as_deref() != Some(BLOCKED) {
+            return Ok(None);
+        }
+        let (_, config) = self.workflow_for(task_name)?;
+        match config.workflow.get(state.current_step) {
+            Some(step) => self.unmet_wait_for(step).map(Some),
+            None => Ok(None),
+        }
+    }
+
     /// Output task state as JSON to stdout — unified output point for all write commands.
     pub fn output_task_state(&self, task_name: &str) -> Result<()> {
         self.detect_viewport_loss(task_name)?;
+        self.detect_owner_loss(task_name)?;
         let state = self.replay_task(task_name)?;
         let events = self.read_events(task_name)?;
         let (wf_name, config) = self.workflow_for(task_name)?;
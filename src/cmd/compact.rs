@@ -0,0 +1,109 @@
+use anyhow::Result;
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+
+use crate::error::PawlError;
+use crate::model::event::{pinned_hash, replay};
+
+use super::common::Project;
+
+#[derive(Serialize)]
+struct Compacted {
+    task: String,
+    /// Events moved to `.pawl/archive/<task>.jsonl`
+    archived: usize,
+    /// Events left in the log (the last reset onwards)
+    kept: usize,
+}
+
+#[derive(Serialize)]
+struct Verified {
+    task: String,
+    /// Events in the full history (archive and log)
+    events: usize,
+    consistent: bool,
+}
+
+/// Archive the runs before each task's last reset and checkpoint what is left.
+/// With `verify`, check instead that replaying each task's full history gives the
+/// state (and pinned definition) the checkpointed replay gives.
+pub fn run(tasks: &[String], verify: bool) -> Result<()> {
+    let project = Project::load()?;
+    let tasks = if tasks.is_empty() {
+        logged_tasks(&project)?
+    } else {
+        tasks.iter().map(|t| project.resolve_task_name(t)).collect::<Result<_>>()?
+    };
+
+    if verify {
+        return verify_tasks(&project, &tasks);
+    }
+
+    let mut results = Vec::new();
+    for task in tasks {
+        let (archived, kept) = project.compact_log(&task)?;
+        project.checkpoint(&task)?;
+        if archived > 0 {
+            eprintln!("  {}: archived {} events, kept {}", task, archived, kept);
+        }
+        results.push(Compacted { task, archived, kept });
+    }
+    println!("{}", serde_json::to_string(&results)?);
+    Ok(())
+}
+
+fn verify_tasks(project: &Project, tasks: &[String]) -> Result<()> {
+    let mut results = Vec::new();
+    let mut inconsistent = Vec::new();
+    for task in tasks {
+        let history = project.read_full_history(task)?;
+        let workflow_len = project.workflow_for(task)
+            .map(|(_, c)| c.workflow.len())
+            .unwrap_or(0);
+        let consistent = replay(&history, workflow_len) == project.replay_task(task)?
+            && pinned_hash(&history).flatten() == project.pin_of(task);
+        if !consistent {
+            inconsistent.push(task.clone());
+        }
+        results.push(Verified { task: task.clone(), events: history.len(), consistent });
+    }
+    println!("{}", serde_json::to_string(&results)?);
+
+    if !inconsistent.is_empty() {
+        return Err(PawlError::Validation {
+            message: format!(
+                "checkpointed state differs from a full replay for: {} (delete .pawl/checkpoints/ to rebuild)",
+                inconsistent.join(", ")
+            ),
+        }.into());
+    }
+    eprintln!("pawl compact: {} task(s) consistent with a full replay", tasks.len());
+    Ok(())
+}
+
+/// Every task with a log, sub-workflow child runs (`<task>/<step>`) included
+fn logged_tasks(project: &Project) -> Result<Vec<String>> {
+    fn walk(dir: &Path, prefix: &str, tasks: &mut Vec<String>) -> Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                let Some(name) = path.file_name().and_then(|s| s.to_str()) else { continue };
+                walk(&path, &format!("{}{}/", prefix, name), tasks)?;
+            } else if path.extension().is_some_and(|e| e == "jsonl")
+                && let Some(name) = path.file_stem().and_then(|s| s.to_str())
+            {
+                tasks.push(format!("{}{}", prefix, name));
+            }
+        }
+        Ok(())
+    }
+
+    let mut tasks = Vec::new();
+    let logs_dir = project.pawl_dir.join("logs");
+    if logs_dir.exists() {
+        walk(&logs_dir, "", &mut tasks)?;
+    }
+    tasks.sort();
+    Ok(tasks)
+}
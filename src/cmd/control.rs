@@ -1,11 +1,13 @@
 use anyhow::Result;
 
 use crate::error::PawlError;
-use crate::model::event::event_timestamp;
-use crate::model::{Event, TaskStatus};
+use crate::model::event::{event_timestamp, open_sub_step_groups, step_owner, ROLLBACK_FAILED, ROLLED_BACK, ROLLING_BACK};
+use crate::model::{Event, TaskState, TaskStatus};
+use crate::util::process::ProcessOwner;
+use crate::util::shell::{terminate_group, Termination};
 
 use super::common::Project;
-use super::start::resume_workflow;
+use super::start::{finish_run, resume_workflow, run_rollback};
 
 /// Stop the current task
 pub fn stop(task_name: &str) -> Result<()> {
@@ -32,34 +34,283 @@ pub fn stop(task_name: &str) -> Result<()> {
         }
     }
 
+    stop_run(&project, &task_name, &state)?;
+    finish_run(&project, &task_name)?;
+
+    // Output final state as JSON
+    project.output_task_state(&task_name)?;
+
+    Ok(())
+}
+
+/// Stop a running or waiting run: interrupt its viewport and step, record TaskStopped,
+/// then stop (and wrap up) the sub-workflow run its current step started, if any.
+fn stop_run(project: &Project, task_name: &str, state: &TaskState) -> Result<()> {
     // Send Ctrl+C to the viewport (if running)
-    let session = project.session_name_for(&task_name)?;
+    let session = project.session_name_for(task_name)?;
 
-    if let Ok(vp) = project.viewport_for(&task_name) {
-        if vp.exists(&task_name) {
-            eprintln!("Sending interrupt to {}:{}...", session, task_name);
-            vp.execute(&task_name, "\x03")?;
-        }
+    if let Ok(vp) = project.viewport_for(task_name)
+        && vp.exists(task_name) {
+        eprintln!("Sending interrupt to {}:{}...", session, task_name);
+        vp.execute(task_name, "\x03")?;
     }
 
-    project.append_event(&task_name, &Event::TaskStopped {
+    // Terminate a synchronous step's process group (if any)
+    let kill = if state.status == TaskStatus::Running {
+        terminate_step(project, task_name)?
+    } else {
+        None
+    };
+
+    project.append_event(task_name, &Event::TaskStopped {
         ts: event_timestamp(),
         step: state.current_step,
+        kill: kill.map(|k| k.as_str().to_string()),
     })?;
 
     eprintln!("Task '{}' stopped.", task_name);
 
-    // Output final state as JSON
+    if let Some(child) = project.active_child(task_name)?
+        && let Some(child_state) = project.replay_task(&child)?
+        && matches!(child_state.status, TaskStatus::Running | TaskStatus::Waiting)
+    {
+        stop_run(project, &child, &child_state)?;
+        finish_run(project, &child)?;
+    }
+    Ok(())
+}
+
+/// Undo the run's successful steps with their `compensate` commands (or continue
+/// an interrupted/failed rollback)
+pub fn rollback(task_name: &str) -> Result<()> {
+    let project = Project::load()?;
+    let task_name = project.resolve_task_name(task_name)?;
+
+    project.detect_viewport_loss(&task_name)?;
+    project.detect_owner_loss(&task_name)?;
+
+    let Some(state) = project.replay_task(&task_name)? else {
+        return Err(PawlError::StateConflict {
+            task: task_name.clone(),
+            status: "pending".into(),
+            message: "not started".into(),
+        }.into());
+    };
+    if matches!(state.status, TaskStatus::Running | TaskStatus::Waiting) {
+        return Err(PawlError::StateConflict {
+            task: task_name.clone(),
+            status: state.status.to_string(),
+            message: format!("use 'pawl stop {}' first", task_name),
+        }.into());
+    }
+
+    if !run_rollback(&project, &task_name, "manual")? {
+        eprintln!("Nothing to compensate for '{}'.", task_name);
+    }
+
     project.output_task_state(&task_name)?;
 
     Ok(())
 }
 
-/// Reset task — full reset or step-only reset
-pub fn reset(task_name: &str, step_only: bool) -> Result<()> {
+/// Move a paused run onto the live workflow definition. Steps are matched by name
+/// (`renames` maps old names to new ones); the current step must still exist.
+pub fn migrate(task_name: &str, renames: &[String]) -> Result<()> {
     let project = Project::load()?;
     let task_name = project.resolve_task_name(task_name)?;
 
+    project.detect_viewport_loss(&task_name)?;
+    project.detect_owner_loss(&task_name)?;
+
+    let Some(state) = project.replay_task(&task_name)? else {
+        return Err(PawlError::StateConflict {
+            task: task_name.clone(),
+            status: "pending".into(),
+            message: "not started".into(),
+        }.into());
+    };
+    if matches!(state.status, TaskStatus::Running | TaskStatus::Completed) {
+        return Err(PawlError::StateConflict {
+            task: task_name.clone(),
+            status: state.status.to_string(),
+            message: "only a waiting, failed or stopped run can be migrated".into(),
+        }.into());
+    }
+
+    let (wf_name, live) = project.live_workflow_for(&task_name)?;
+    let wf_name = wf_name.to_string();
+    if !project.workflow_drifted(&task_name) {
+        eprintln!("Task '{}' already runs the current '{}' definition.", task_name, wf_name);
+        project.output_task_state(&task_name)?;
+        return Ok(());
+    }
+    let pin = project.pin_of(&task_name).unwrap_or_default();
+    let Some(pinned) = project.snapshot(&pin) else {
+        return Err(PawlError::NotFound {
+            message: format!("snapshot .pawl/snapshots/{}.json of the run's definition is missing", pin),
+        }.into());
+    };
+
+    let mut renamed = std::collections::HashMap::new();
+    for rename in renames {
+        let (old, new) = rename.split_once('=').unwrap_or((rename, ""));
+        if pinned.step_index(old).is_none() || live.step_index(new).is_none() {
+            return Err(PawlError::Validation {
+                message: format!("--map expects OLD=NEW step names of the old and new definition, got '{}'", rename),
+            }.into());
+        }
+        renamed.insert(old, new);
+    }
+    let steps: Vec<Option<usize>> = pinned.workflow.iter()
+        .map(|s| live.step_index(renamed.get(s.name.as_str()).copied().unwrap_or(&s.name)))
+        .collect();
+
+    let step = match steps.get(state.current_step) {
+        Some(Some(new)) => *new,
+        Some(None) => {
+            let name = &pinned.workflow[state.current_step].name;
+            return Err(PawlError::Precondition {
+                message: format!(
+                    "current step '{}' is not in the new definition; map it with --map {}=<step>",
+                    name, name
+                ),
+            }.into());
+        }
+        None => live.workflow.len(),
+    };
+
+    // New steps before the current one never ran in this run
+    let added: Vec<&str> = live.workflow[..step].iter().enumerate()
+        .filter(|(i, _)| !steps.contains(&Some(*i)))
+        .map(|(_, s)| s.name.as_str())
+        .collect();
+    if !added.is_empty() {
+        eprintln!("Warning: steps before the current one are new and will not run: {}", added.join(", "));
+    }
+
+    project.append_event(&task_name, &Event::WorkflowMigrated {
+        ts: event_timestamp(),
+        workflow_hash: project.pin_workflow(&wf_name)?,
+        step,
+        steps,
+    })?;
+    eprintln!(
+        "Migrated '{}' to the current '{}' definition (step {}: {}).",
+        task_name, wf_name, step + 1, project.step_name(&task_name, step)
+    );
+
+    project.output_task_state(&task_name)?;
+
+    Ok(())
+}
+
+/// Terminate the running synchronous step, if any: SIGTERM its owner (which takes its
+/// step's process group down with it), then the group itself, escalating to SIGKILL
+/// after the workflow's grace period. None = no process group recorded for this run.
+fn terminate_step(project: &Project, task_name: &str) -> Result<Option<Termination>> {
+    let events = project.read_events(task_name)?;
+    let Some(owner) = step_owner(&events) else {
+        return Ok(None);
+    };
+    let process = ProcessOwner {
+        pid: owner.pid,
+        hostname: owner.hostname,
+        boot_id: owner.boot_id,
+    };
+    // Processes on another host or from a previous boot can't be signalled
+    if !process.same_boot() {
+        return Ok(None);
+    }
+
+    if process.is_dead() {
+        // Nothing is left to take the sub-steps down with it
+        for pgid in open_sub_step_groups(&events) {
+            eprintln!("Terminating sub-step process group {}...", pgid);
+            terminate_group(pgid, project.grace_period_for(task_name));
+        }
+    } else if process.pid != std::process::id() {
+        unsafe {
+            libc::kill(process.pid as libc::pid_t, libc::SIGTERM);
+        }
+    }
+
+    let Some(pgid) = owner.pgid else {
+        return Ok(None);
+    };
+    eprintln!("Terminating step process group {}...", pgid);
+    let outcome = terminate_group(pgid, project.grace_period_for(task_name));
+    if outcome == Termination::Killed {
+        eprintln!("  Process group {} ignored SIGTERM — sent SIGKILL.", pgid);
+    }
+    Ok(Some(outcome))
+}
+
+/// Full reset: take down a running step, reset the run's active sub-workflow run, then record TaskReset
+fn reset_run(project: &Project, task_name: &str, state: Option<&TaskState>) -> Result<()> {
+    let is_running = state
+        .map(|s| s.status == TaskStatus::Running)
+        .unwrap_or(false);
+
+    if is_running
+        && let Ok(vp) = project.viewport_for(task_name)
+        && vp.exists(task_name) {
+        eprintln!("Stopping task viewport...");
+        vp.execute(task_name, "\x03")?;
+    }
+    if is_running {
+        terminate_step(project, task_name)?;
+    }
+
+    if let Some(child) = project.active_child(task_name)? {
+        let child_state = project.replay_task(&child)?;
+        reset_run(project, &child, child_state.as_ref())?;
+    }
+
+    project.append_event(task_name, &Event::TaskReset { ts: event_timestamp() })
+}
+
+/// Full reset of a task and of every started task that (transitively) depends on it.
+/// Downstream tasks go first, so none of them is left completed against a reset input.
+fn reset_cascade(project: &Project, task_name: &str, dry_run: bool) -> Result<()> {
+    let mut targets = vec![task_name.to_string()];
+    for dependent in project.graph().descendants(task_name) {
+        if project.replay_task(dependent)?.is_some() {
+            targets.push(dependent.to_string());
+        }
+    }
+    if dry_run {
+        println!("{}", serde_json::json!({ "dry_run": true, "reset": targets }));
+        return Ok(());
+    }
+
+    for name in targets.iter().rev() {
+        let state = project.replay_task(name)?;
+        reset_run(project, name, state.as_ref())?;
+        eprintln!("Task '{}' reset to initial state.", name);
+    }
+    project.output_task_state(task_name)
+}
+
+/// Reset task — full reset or step-only reset, or (--cascade) full reset of it and
+/// everything downstream
+pub fn reset(task_name: &str, step_only: bool, cascade: bool, dry_run: bool) -> Result<()> {
+    let project = Project::load()?;
+    let task_name = project.resolve_task_name(task_name)?;
+
+    if cascade {
+        if step_only {
+            return Err(PawlError::Validation {
+                message: "--cascade resets whole tasks; it can't be combined with --step".into(),
+            }.into());
+        }
+        return reset_cascade(&project, &task_name, dry_run);
+    }
+    if dry_run {
+        return Err(PawlError::Validation {
+            message: "--dry-run only applies to --cascade".into(),
+        }.into());
+    }
+
     let state = project.replay_task(&task_name)?;
 
     if step_only {
@@ -82,6 +333,14 @@ pub fn reset(task_name: &str, step_only: bool) -> Result<()> {
             }.into());
         }
 
+        if matches!(state.message.as_deref(), Some(ROLLING_BACK | ROLLBACK_FAILED | ROLLED_BACK)) {
+            return Err(PawlError::StateConflict {
+                task: task_name.clone(),
+                status: state.status.to_string(),
+                message: format!("rolled back; use 'pawl reset {}' for full reset", task_name),
+            }.into());
+        }
+
         match state.status {
             TaskStatus::Failed | TaskStatus::Stopped | TaskStatus::Waiting => {}
             TaskStatus::Running => {
@@ -111,28 +370,14 @@ pub fn reset(task_name: &str, step_only: bool) -> Result<()> {
             ts: event_timestamp(),
             step: step_idx,
             auto: false,
+            retry_at: None,
+            resumed: false,
         })?;
 
         eprintln!("Reset step {}: {}", step_idx + 1, project.step_name(&task_name, step_idx));
         resume_workflow(&project, &task_name)?;
     } else {
-        // Full task reset
-        let is_running = state
-            .as_ref()
-            .map(|s| s.status == TaskStatus::Running)
-            .unwrap_or(false);
-
-        if is_running {
-            if let Ok(vp) = project.viewport_for(&task_name) {
-                if vp.exists(&task_name) {
-                    eprintln!("Stopping task viewport...");
-                    vp.execute(&task_name, "\x03")?;
-                }
-            }
-        }
-
-        project.append_event(&task_name, &Event::TaskReset { ts: event_timestamp() })?;
-
+        reset_run(&project, &task_name, state.as_ref())?;
         eprintln!("Task '{}' reset to initial state.", task_name);
     }
 
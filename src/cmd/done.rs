@@ -1,8 +1,9 @@
 use anyhow::Result;
 
 use crate::error::PawlError;
-use crate::model::event::event_timestamp;
+use crate::model::event::{event_timestamp, step_owner, BLOCKED};
 use crate::model::{Event, TaskStatus};
+use crate::util::process::ProcessOwner;
 
 use super::common::Project;
 use super::start;
@@ -14,6 +15,7 @@ pub fn done(task_name: &str, message: Option<&str>) -> Result<()> {
     let task_name = project.resolve_task_name(task_name)?;
 
     project.detect_viewport_loss(&task_name)?;
+    project.detect_owner_loss(&task_name)?;
 
     let state = project.replay_task(&task_name)?;
     let Some(state) = state else {
@@ -29,38 +31,19 @@ pub fn done(task_name: &str, message: Option<&str>) -> Result<()> {
     match state.status {
         TaskStatus::Running => {
             // Agent in viewport reporting done — go through unified pipeline
-            let (_, config) = project.workflow_for(&task_name)?;
-            let step = &config.workflow[step_idx];
-
             let record = StepRecord {
                 exit_code: 0,
                 duration: None,
                 stdout: message.map(|s| s.to_string()),
                 stderr: None,
+                timed_out: false,
             };
 
             eprintln!("Step {} marked as done.", step_idx + 1);
-
-            // Unified pipeline: combine → decide → split
-            let should_continue = start::settle_step(
-                &project, &task_name, step_idx, step, record
-            )?;
-
-            // Cleanup viewport — but not if retrying (must happen before resume_workflow
-            // which may open a new viewport for the next step with the same name)
-            let new_state = project.replay_task(&task_name)?;
-            let retrying = matches!(&new_state,
-                Some(s) if s.status == TaskStatus::Running && s.current_step == step_idx
-            );
-            if !retrying {
-                if let Ok(vp) = project.viewport_for(&task_name) {
-                    let _ = vp.close(&task_name);
-                }
-            }
-
-            if should_continue {
-                resume_workflow(&project, &task_name)?;
-            }
+            settle_reported(&project, &task_name, step_idx, record)?;
+        }
+        TaskStatus::Waiting if state.message.as_deref() == Some(BLOCKED) => {
+            return Err(blocked_conflict(&project, &task_name)?.into());
         }
         TaskStatus::Waiting => {
             // Manual approval: emit StepResumed and continue
@@ -69,6 +52,7 @@ pub fn done(task_name: &str, message: Option<&str>) -> Result<()> {
                 step: step_idx,
                 message: message.map(|s| s.to_string()),
             })?;
+            project.release_blocked(&task_name);
 
             eprintln!("Step {} approved.", step_idx + 1);
             resume_workflow(&project, &task_name)?;
@@ -87,3 +71,156 @@ pub fn done(task_name: &str, message: Option<&str>) -> Result<()> {
 
     Ok(())
 }
+
+/// Reject a waiting step (gate, verify_manual or on_fail_manual)
+pub fn reject(task_name: &str, message: &str) -> Result<()> {
+    let project = Project::load()?;
+    let task_name = project.resolve_task_name(task_name)?;
+
+    let state = project.replay_task(&task_name)?;
+    let Some(state) = state else {
+        return Err(PawlError::StateConflict {
+            task: task_name.clone(),
+            status: "pending".into(),
+            message: format!("not started. Use 'pawl start {}' to begin", task_name),
+        }.into());
+    };
+
+    if state.status != TaskStatus::Waiting {
+        return Err(PawlError::StateConflict {
+            task: task_name.clone(),
+            status: state.status.to_string(),
+            message: "only a waiting step can be rejected".into(),
+        }.into());
+    }
+
+    // A dependency wait is not a review: nothing to reject
+    if state.message.as_deref() == Some(BLOCKED) {
+        return Err(blocked_conflict(&project, &task_name)?.into());
+    }
+
+    let step_idx = state.current_step;
+    let (_, config) = project.workflow_for(&task_name)?;
+    let step = &config.workflow[step_idx];
+
+    eprintln!("Step {} rejected: {}", step_idx + 1, message);
+    if start::settle_rejection(&project, &task_name, step_idx, step, Some(message.to_string()))? {
+        resume_workflow(&project, &task_name)?;
+    } else {
+        start::finish_run(&project, &task_name)?;
+    }
+
+    project.output_task_state(&task_name)?;
+
+    Ok(())
+}
+
+/// Refusal for a step blocked on `wait_for`: it resumes by itself, not by decision
+fn blocked_conflict(project: &Project, task_name: &str) -> Result<PawlError> {
+    let unmet = project.blocked_on(task_name)?.unwrap_or_default();
+    let message = if unmet.is_empty() {
+        format!("no longer blocked; use 'pawl start {}' to resume", task_name)
+    } else {
+        format!("blocked on {}; it resumes once they succeed", unmet.join(", "))
+    };
+    Ok(PawlError::StateConflict {
+        task: task_name.to_string(),
+        status: "waiting".into(),
+        message,
+    })
+}
+
+/// Report the current in_viewport step as failed (agent-side counterpart of `done`)
+pub fn fail(task_name: &str, message: Option<&str>, exit_code: i32) -> Result<()> {
+    if exit_code == 0 {
+        return Err(PawlError::Validation {
+            message: "--exit-code must be non-zero (use 'pawl done' to report success)".into(),
+        }.into());
+    }
+
+    let project = Project::load()?;
+    let task_name = project.resolve_task_name(task_name)?;
+
+    project.detect_viewport_loss(&task_name)?;
+    project.detect_owner_loss(&task_name)?;
+
+    let state = project.replay_task(&task_name)?;
+    let Some(state) = state else {
+        return Err(PawlError::StateConflict {
+            task: task_name.clone(),
+            status: "pending".into(),
+            message: format!("not started. Use 'pawl start {}' to begin", task_name),
+        }.into());
+    };
+
+    if state.status != TaskStatus::Running {
+        return Err(PawlError::StateConflict {
+            task: task_name.clone(),
+            status: state.status.to_string(),
+            message: "cannot mark as failed".into(),
+        }.into());
+    }
+
+    let step_idx = state.current_step;
+    // Only a step run in the viewport waits for a report; any other is settled by
+    // its own process, unless that process is gone
+    let (_, config) = project.workflow_for(&task_name)?;
+    if !config.workflow[step_idx].in_viewport {
+        let events = project.read_events(&task_name)?;
+        let owner_dead = step_owner(&events).is_some_and(|owner| ProcessOwner {
+            pid: owner.pid,
+            hostname: owner.hostname,
+            boot_id: owner.boot_id,
+        }.is_dead());
+        if !owner_dead {
+            return Err(PawlError::StateConflict {
+                task: task_name.clone(),
+                status: state.status.to_string(),
+                message: format!("step {} is not an in_viewport step; its own process reports the result", step_idx + 1),
+            }.into());
+        }
+    }
+    // The reason becomes ${last_verify_output} for the next attempt
+    let record = StepRecord {
+        exit_code,
+        duration: None,
+        stdout: None,
+        stderr: message.map(|s| s.to_string()),
+        timed_out: false,
+    };
+
+    eprintln!("Step {} marked as failed (exit code {}).", step_idx + 1, exit_code);
+    settle_reported(&project, &task_name, step_idx, record)?;
+
+    project.output_task_state(&task_name)?;
+
+    Ok(())
+}
+
+/// Settle an agent-reported result for a Running step, then tidy up its viewport.
+fn settle_reported(project: &Project, task_name: &str, step_idx: usize, record: StepRecord) -> Result<()> {
+    let (_, config) = project.workflow_for(task_name)?;
+    let step = &config.workflow[step_idx];
+
+    // Unified pipeline: combine → decide → split
+    let should_continue = start::settle_step(project, task_name, step_idx, step, record)?;
+
+    // Cleanup viewport — but not if retrying (must happen before resume_workflow
+    // which may open a new viewport for the next step with the same name)
+    let new_state = project.replay_task(task_name)?;
+    let retrying = matches!(&new_state,
+        Some(s) if s.status == TaskStatus::Running && s.current_step == step_idx
+    );
+    if !retrying
+        && let Ok(vp) = project.viewport_for(task_name) {
+        let _ = vp.close(task_name);
+    }
+
+    if should_continue {
+        resume_workflow(project, task_name)?;
+    } else {
+        start::finish_run(project, task_name)?;
+    }
+
+    Ok(())
+}
@@ -143,7 +143,8 @@ fn print_events_from_file(task_name: &str, path: &std::path::Path, offset: u64,
     let file_len = metadata.len();
 
     if file_len <= offset {
-        return Ok(offset);
+        // Shorter than what was read: `pawl compact` rewrote it, keeping lines already printed
+        return Ok(offset.min(file_len));
     }
 
     let mut reader = BufReader::new(file);
@@ -1,4 +1,6 @@
+pub mod check;
 pub mod common;
+pub mod compact;
 pub mod control;
 pub mod serve;
 pub mod done;
@@ -6,6 +8,7 @@ pub mod events;
 pub mod init;
 pub mod log;
 pub mod run;
+pub mod run_all;
 pub mod start;
 pub mod status;
 pub mod wait;
@@ -17,10 +20,13 @@ pub fn dispatch(cmd: Command) -> Result<()> {
     match cmd {
         Command::Init => init::run(),
         Command::List { ready } => status::list(ready),
-        Command::Start { task, reset } => start::run(&task, reset),
+        Command::Start { task, reset, when_ready, vars, vars_file } => {
+            start::run(&task, reset, when_ready, &vars, vars_file.as_deref())
+        }
+        Command::RunAll { jobs, interval } => run_all::run(jobs, interval),
         Command::Status { task } => status::run(task.as_deref()),
         Command::Stop { task } => control::stop(&task),
-        Command::Reset { task, step } => control::reset(&task, step),
+        Command::Reset { task, step, cascade, dry_run } => control::reset(&task, step, cascade, dry_run),
         Command::Wait { tasks, until, timeout, interval, any } => {
             wait::run(&tasks, &until, timeout, interval, any)
         }
@@ -29,6 +35,12 @@ pub fn dispatch(cmd: Command) -> Result<()> {
             events::run(task.as_deref(), follow, event_type.as_deref())
         }
         Command::Done { task, message } => done::done(&task, message.as_deref()),
+        Command::Reject { task, message } => done::reject(&task, &message),
+        Command::Fail { task, message, exit_code } => done::fail(&task, message.as_deref(), exit_code),
+        Command::Rollback { task } => control::rollback(&task),
+        Command::Migrate { task, map } => control::migrate(&task, &map),
+        Command::Check { strict } => check::run(strict),
+        Command::Compact { tasks, verify } => compact::run(&tasks, verify),
         Command::Serve { port, ui } => serve::run(port, ui.as_deref()),
         Command::Run { task, step } => run::run_in_viewport(&task, step),
     }
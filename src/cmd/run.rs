@@ -5,8 +5,9 @@ use std::time::Instant;
 use anyhow::{bail, Result};
 
 use crate::model::TaskStatus;
+use crate::util::shell::{wait_with_timeout, TIMEOUT_EXIT_CODE};
 use super::common::Project;
-use super::start::{resume_workflow, settle_step, StepRecord};
+use super::start::{finish_run, resume_workflow, settle_step, StepRecord};
 
 /// Internal: run a command in viewport as the parent process.
 /// Replaces the old runner-script + EXIT-trap + `pawl _on-exit` chain.
@@ -44,13 +45,10 @@ pub fn run_in_viewport(task_name: &str, step_idx: usize) -> Result<()> {
     };
 
     // 4. Build context, expand command, prepare env vars
-    let mut ctx = project.context_for(task_name, Some(step_idx), &state.run_id);
+    let mut ctx = project.context_for(task_name, Some(step_idx), &state.run_id)?;
     let events = project.read_events(task_name)?;
     let (retry_count, last_feedback) = super::common::extract_step_context(&events, step_idx);
-    ctx = ctx.var("retry_count", retry_count.to_string());
-    if let Some(fb) = &last_feedback {
-        ctx = ctx.var("last_verify_output", fb);
-    }
+    ctx = ctx.retry(retry_count, last_feedback.as_deref());
 
     let expanded = ctx.expand(&command);
     let env = ctx.to_env_vars();
@@ -78,9 +76,13 @@ pub fn run_in_viewport(task_name: &str, step_idx: usize) -> Result<()> {
             .spawn()?
     };
 
-    // 6. Wait for child (OS-guaranteed delivery)
-    let status = child.wait()?;
-    let exit_code = status.code().unwrap_or(128);
+    // 6. Wait for child (OS-guaranteed delivery), killing it if the step times out
+    let status = wait_with_timeout(&mut child, step.effective_timeout(), project.grace_period_for(task_name))?;
+    let timed_out = status.is_none();
+    let exit_code = match status {
+        Some(status) => status.code().unwrap_or(128),
+        None => TIMEOUT_EXIT_CODE,
+    };
     let elapsed = start_time.elapsed().as_secs_f64();
 
     // 7. Redirect stdout/stderr to /dev/null (pty may be closed after viewport close)
@@ -105,12 +107,15 @@ pub fn run_in_viewport(task_name: &str, step_idx: usize) -> Result<()> {
         duration: Some(elapsed),
         stdout: None,
         stderr: None,
+        timed_out,
     };
 
     if settle_step(&project, task_name, step_idx, &step, record)? {
         // Pipeline says continue — check if next step is also in_viewport
         // If so, execute() will detect PAWL_IN_VIEWPORT and exec into next pawl _run
         resume_workflow(&project, task_name)?;
+    } else {
+        finish_run(&project, task_name)?;
     }
 
     Ok(())
@@ -0,0 +1,169 @@
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::Duration;
+
+use crate::error::PawlError;
+use crate::model::config::DependsOn;
+use crate::model::event::INTERRUPTED;
+use crate::model::{TaskState, TaskStatus};
+
+use super::common::Project;
+
+/// Final state of every declared task after `pawl run-all`
+#[derive(Serialize, Default)]
+struct RunAllSummary {
+    /// Every declared task completed
+    success: bool,
+    /// Tasks this invocation started (or resumed)
+    started: Vec<String>,
+    completed: Vec<String>,
+    failed: Vec<String>,
+    waiting: Vec<String>,
+    stopped: Vec<String>,
+    /// Still running: started elsewhere after the last check
+    running: Vec<String>,
+    /// Never started: a dependency did not complete
+    blocked: Vec<String>,
+    /// Never started and never will: a `failed`/`finished` condition was ruled out
+    /// (or a dependency was itself skipped). Not counted against `success`
+    skipped: Vec<String>,
+}
+
+/// Drive the task DAG: start every ready task (at most `jobs` at a time), wait for
+/// them, and repeat until nothing else can run. Interrupted tasks and tasks blocked
+/// on a `wait_for` that is now met are resumed the same way. All state comes from the logs, so
+/// re-running after a crash picks up where the last run stopped.
+pub fn run(jobs: usize, interval_ms: u64) -> Result<()> {
+    if jobs == 0 {
+        return Err(PawlError::Validation {
+            message: "--jobs must be at least 1".into(),
+        }.into());
+    }
+    let project = Project::load()?;
+    let tasks = project.discover_tasks()?;
+    let interval = Duration::from_millis(interval_ms);
+    let pawl = std::env::current_exe()?;
+
+    let mut children: HashMap<String, Child> = HashMap::new();
+    // Log length when each task was last started: a start that didn't move the log
+    // isn't retried, so a task that can't get going is reported instead of looping
+    let mut attempted: HashMap<String, usize> = HashMap::new();
+    let mut started = Vec::new();
+    let mut last_seen: HashMap<String, TaskStatus> = HashMap::new();
+
+    loop {
+        let mut exited = Vec::new();
+        for (name, child) in children.iter_mut() {
+            if child.try_wait()?.is_some() {
+                exited.push(name.clone());
+            }
+        }
+        for name in exited {
+            children.remove(&name);
+        }
+
+        let mut states: HashMap<&str, Option<TaskState>> = HashMap::new();
+        for name in &tasks {
+            if !children.contains_key(name) {
+                project.detect_viewport_loss(name)?;
+                project.detect_owner_loss(name)?;
+            }
+            let state = project.replay_task(name)?;
+            let status = state.as_ref().map(|s| s.status).unwrap_or(TaskStatus::Pending);
+            if last_seen.insert(name.clone(), status).is_some_and(|prev| prev != status) {
+                eprintln!("  {}: {}", name, status);
+            }
+            states.insert(name, state);
+        }
+
+        // Running tasks occupy a slot whether or not this process started them
+        let mut active = tasks.iter()
+            .filter(|name| children.contains_key(*name) || is_running(&states[name.as_str()]))
+            .count();
+
+        for name in &tasks {
+            if active >= jobs {
+                break;
+            }
+            let startable = match &states[name.as_str()] {
+                None => true,
+                Some(s) if s.status == TaskStatus::Stopped => s.message.as_deref() == Some(INTERRUPTED),
+                // Blocked on `wait_for`, and everything it waits for has succeeded
+                Some(_) => project.blocked_on(name)?.is_some_and(|unmet| unmet.is_empty()),
+            };
+            let log_len = project.read_events(name)?.len();
+            if !startable || attempted.get(name) == Some(&log_len) {
+                continue;
+            }
+            if !project.check_dependencies(name)?.is_empty() {
+                continue;
+            }
+
+            let child = Command::new(&pawl)
+                .args(["start", name])
+                .current_dir(&project.project_root)
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .spawn()?;
+            children.insert(name.clone(), child);
+            attempted.insert(name.clone(), log_len);
+            if !started.contains(name) {
+                started.push(name.clone());
+            }
+            active += 1;
+        }
+
+        if active == 0 {
+            break;
+        }
+        thread::sleep(interval);
+    }
+
+    let mut summary = RunAllSummary { started, ..Default::default() };
+    for name in &tasks {
+        match project.replay_task(name)?.map(|s| s.status) {
+            Some(TaskStatus::Completed) => summary.completed.push(name.clone()),
+            Some(TaskStatus::Failed) => summary.failed.push(name.clone()),
+            Some(TaskStatus::Waiting) => summary.waiting.push(name.clone()),
+            Some(TaskStatus::Stopped) => summary.stopped.push(name.clone()),
+            Some(TaskStatus::Running) => summary.running.push(name.clone()),
+            Some(TaskStatus::Pending) | None => {
+                if is_skipped(&project, name, &summary.skipped)? {
+                    summary.skipped.push(name.clone());
+                } else {
+                    summary.blocked.push(name.clone());
+                }
+            }
+        }
+    }
+    summary.success = summary.completed.len() + summary.skipped.len() == tasks.len();
+    eprintln!(
+        "run-all: {} completed, {} failed, {} waiting, {} stopped, {} running, {} blocked, {} skipped",
+        summary.completed.len(), summary.failed.len(), summary.waiting.len(),
+        summary.stopped.len(), summary.running.len(), summary.blocked.len(), summary.skipped.len()
+    );
+    println!("{}", serde_json::to_string(&summary)?);
+    Ok(())
+}
+
+/// A task that can't apply to this run: one of its conditional dependencies settled
+/// the other way, or depends on a task that was skipped (`skipped` comes first, in
+/// dependency order)
+fn is_skipped(project: &Project, name: &str, skipped: &[String]) -> Result<bool> {
+    for dep in project.check_dependencies(name)? {
+        if skipped.iter().any(|s| s == dep.task()) {
+            return Ok(true);
+        }
+        if dep.on() != DependsOn::Completed && project.dependency_settled(&dep)?.is_some() {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+fn is_running(state: &Option<TaskState>) -> bool {
+    state.as_ref().is_some_and(|s| s.status == TaskStatus::Running)
+}
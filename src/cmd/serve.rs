@@ -6,6 +6,7 @@ use tiny_http::{Header, Method, Response, Server};
 
 use super::common::Project;
 use super::status::{build_task_detail, TaskDetail};
+use crate::model::config::Dependency;
 use crate::model::event::Event;
 
 #[derive(Serialize)]
@@ -26,7 +27,7 @@ struct TaskEntry {
     #[serde(flatten)]
     detail: TaskDetail,
     workflow_name: String,
-    blocked_by: Vec<String>,
+    blocked_by: Vec<Dependency>,
     max_retries: usize,
 }
 
@@ -300,10 +301,9 @@ fn build_events(url: &str) -> Result<String> {
         for event in &events {
             let ts = event.ts();
 
-            if let Some(since_ts) = since {
-                if ts <= since_ts {
-                    continue;
-                }
+            if let Some(since_ts) = since
+                && ts <= since_ts {
+                continue;
             }
 
             let step_name = event.step_index().map(|i| {
@@ -323,7 +323,7 @@ fn build_events(url: &str) -> Result<String> {
         }
     }
 
-    all_events.sort_by(|a, b| b.ts_ms.cmp(&a.ts_ms));
+    all_events.sort_by_key(|e| std::cmp::Reverse(e.ts_ms));
     all_events.truncate(200);
 
     let resp = EventsResponse {
@@ -341,10 +341,31 @@ fn build_event_detail(event: &Event) -> String {
                 "fail".to_string()
             }
         }
+        Event::SubStepStarted { sub, .. } => format!("sub {}", sub),
+        Event::SubStepFinished { sub, success, cancelled, .. } => {
+            let result = if *success { "ok" } else if *cancelled { "cancelled" } else { "fail" };
+            format!("sub {}: {}", sub, result)
+        }
+        Event::SubWorkflowStarted { child, .. } => child.clone(),
+        Event::StepExpanded { items, .. } => format!("{} items", items.len()),
+        Event::WorkflowMigrated { workflow_hash, .. } => workflow_hash.clone(),
         Event::StepYielded { reason, .. } => reason.clone(),
-        Event::StepResumed { .. } => String::new(),
-        Event::StepReset { auto, .. } => {
-            if *auto {
+        Event::StepResumed { .. } | Event::StepUnblocked { .. } => String::new(),
+        Event::StepRejected { message, .. } => message.clone().unwrap_or_default(),
+        Event::StepSkipped { reason, .. } => reason.clone().unwrap_or_default(),
+        Event::StepJumped { to, reason, .. } => format!("{} → {}", reason, to),
+        Event::RollbackStarted { reason, .. } => reason.clone(),
+        Event::StepCompensated { success, .. } => {
+            if *success { "ok".to_string() } else { "fail".to_string() }
+        }
+        Event::FinallyStarted { status, .. } => status.clone(),
+        Event::FinallyFinished { success, .. } => {
+            if *success { "ok".to_string() } else { "fail".to_string() }
+        }
+        Event::StepReset { auto, resumed, .. } => {
+            if *resumed {
+                "resumed".to_string()
+            } else if *auto {
                 "auto".to_string()
             } else {
                 "manual".to_string()
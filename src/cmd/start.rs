@@ -1,33 +1,85 @@
 use anyhow::{bail, Result};
+use indexmap::IndexMap;
+use std::collections::HashMap;
 use std::fs;
 use std::os::unix::process::CommandExt as _;
-use std::time::Instant;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
 use uuid::Uuid;
 
 use crate::error::PawlError;
-use crate::model::config::Step;
-use crate::model::event::event_timestamp;
-use crate::model::{Event, TaskStatus};
-use crate::util::shell::{run_command, run_command_to_file};
+use crate::model::config::{Dependency, Foreach, ForeachSource, OnFail, Step};
+use crate::model::event::{
+    count_auto_retries, count_jumps, event_timestamp, finally_due, foreach_items, item_failures,
+    item_retry_at, pending_retry_at, run_parent, sub_step_results, sub_workflow_run, ParentRun, BLOCKED,
+    INTERRUPTED, ROLLBACK_FAILED, ROLLING_BACK,
+};
+use crate::model::state::StepStatus;
+use crate::model::{Event, TaskState, TaskStatus};
+use crate::util::{condition, extract};
+use crate::util::process::ProcessOwner;
+use crate::util::shell::{run_batch_to_files, run_command_to_file, BatchCommand};
 use crate::util::variable::Context;
-use super::common::Project;
+use super::common::{child_task_name, Project};
 
-pub fn run(task_name: &str, reset: bool) -> Result<()> {
+pub fn run(task_name: &str, reset: bool, when_ready: bool, vars: &[String], vars_file: Option<&str>) -> Result<()> {
     let project = Project::load()?;
     let task_name = project.resolve_task_name(task_name)?;
+    let run_vars = parse_run_vars(vars, vars_file)?;
+
+    // A Running step whose owner died (crash/reboot) becomes Stopped/interrupted
+    project.detect_owner_loss(&task_name)?;
 
     // Check if task is already running
     if let Some(state) = project.replay_task(&task_name)? {
         if reset {
             // Auto-reset before starting
             project.append_event(&task_name, &Event::TaskReset { ts: event_timestamp() })?;
+        } else if state.status == TaskStatus::Stopped && state.message.as_deref() == Some(INTERRUPTED) {
+            // Interrupted run: re-run the interrupted step instead of starting over
+            if !run_vars.is_empty() {
+                return Err(PawlError::StateConflict {
+                    task: task_name.clone(),
+                    status: "stopped".into(),
+                    message: format!("resuming keeps the run's vars; use 'pawl start --reset {}' to start over with new ones", task_name),
+                }.into());
+            }
+            let step_idx = state.current_step;
+            // Carry over a pending backoff so a crash mid-wait doesn't retry early
+            let events = project.read_events(&task_name)?;
+            project.append_event(&task_name, &Event::StepReset {
+                ts: event_timestamp(),
+                step: step_idx,
+                auto: false,
+                retry_at: pending_retry_at(&events, step_idx),
+                resumed: true,
+            })?;
+            eprintln!(
+                "Resuming task: {} (step {} '{}' was interrupted)",
+                task_name, step_idx + 1, project.step_name(&task_name, step_idx)
+            );
+            execute(&project, &task_name)?;
+            project.output_task_state(&task_name)?;
+            return Ok(());
         } else {
             match state.status {
                 TaskStatus::Running => {
+                    let message = match project.active_child(&task_name)? {
+                        Some(child) => format!("running sub-workflow '{}' at step {}", child, state.current_step),
+                        None => format!("already running at step {}", state.current_step),
+                    };
                     return Err(PawlError::StateConflict {
                         task: task_name.clone(),
                         status: "running".into(),
-                        message: format!("already running at step {}", state.current_step),
+                        message,
+                    }.into());
+                }
+                TaskStatus::Failed if matches!(state.message.as_deref(), Some(ROLLING_BACK | ROLLBACK_FAILED)) => {
+                    return Err(PawlError::StateConflict {
+                        task: task_name.clone(),
+                        status: "failed".into(),
+                        message: format!("rollback unfinished; use 'pawl rollback {}' to continue", task_name),
                     }.into());
                 }
                 TaskStatus::Completed => {
@@ -37,6 +89,38 @@ pub fn run(task_name: &str, reset: bool) -> Result<()> {
                         message: format!("use 'pawl reset {}' to restart or 'pawl start --reset {}'", task_name, task_name),
                     }.into());
                 }
+                TaskStatus::Waiting if state.message.as_deref() == Some(BLOCKED) => {
+                    if !run_vars.is_empty() {
+                        return Err(PawlError::StateConflict {
+                            task: task_name.clone(),
+                            status: "waiting".into(),
+                            message: format!("resuming keeps the run's vars; use 'pawl start --reset {}' to start over with new ones", task_name),
+                        }.into());
+                    }
+                    let step_idx = state.current_step;
+                    let unmet = project.blocked_on(&task_name)?.unwrap_or_default();
+                    if !unmet.is_empty() {
+                        return Err(PawlError::StateConflict {
+                            task: task_name.clone(),
+                            status: "waiting".into(),
+                            message: format!("blocked on {}; it resumes once they succeed", unmet.join(", ")),
+                        }.into());
+                    }
+                    if !claim_unblock(&project, &task_name, step_idx)? {
+                        return Err(PawlError::StateConflict {
+                            task: task_name.clone(),
+                            status: "running".into(),
+                            message: "resumed by another process".into(),
+                        }.into());
+                    }
+                    eprintln!(
+                        "Resuming task: {} (step {} '{}' is no longer blocked)",
+                        task_name, step_idx + 1, project.step_name(&task_name, step_idx)
+                    );
+                    execute(&project, &task_name)?;
+                    project.output_task_state(&task_name)?;
+                    return Ok(());
+                }
                 TaskStatus::Waiting => {
                     let step_name = project.step_name(&task_name, state.current_step);
                     let reason = state.message.as_deref().unwrap_or("approval");
@@ -51,23 +135,50 @@ pub fn run(task_name: &str, reset: bool) -> Result<()> {
         }
     }
 
+    // Log length the checks above saw, to detect another start while we wait for deps
+    let checked_len = project.read_events(&task_name)?.len();
+
+    // A child run only starts from its parent's sub-workflow step
+    if let Some((parent, _)) = task_name.rsplit_once('/')
+        && project.task_config(&task_name).is_none()
+    {
+        return Err(PawlError::Precondition {
+            message: format!("'{}' is a sub-workflow run; it starts with its parent '{}'", task_name, parent),
+        }.into());
+    }
+
     // Check dependencies
     let blocking = project.check_dependencies(&task_name)?;
     if !blocking.is_empty() {
-        return Err(PawlError::Precondition {
-            message: format!("Task '{}' is blocked by incomplete dependencies: {}", task_name, blocking.join(", ")),
-        }.into());
+        if !when_ready {
+            return Err(PawlError::Precondition {
+                message: format!("Task '{}' is blocked by unmet dependencies: {}", task_name, join(&blocking)),
+            }.into());
+        }
+        wait_for_dependencies(&project, &task_name, blocking)?;
     }
 
-    // Emit TaskStarted event with run_id and workflow name
+    // Emit TaskStarted event with run_id and workflow name, unless another process
+    // (e.g. an auto-start) wrote to the log meanwhile
     let run_id = Uuid::new_v4().to_string();
-    let (wf_name, _) = project.workflow_for(&task_name)?;
+    let (wf_name, _) = project.live_workflow_for(&task_name)?;
     let wf_name = wf_name.to_string();
-    project.append_event(&task_name, &Event::TaskStarted {
+    let claimed = project.append_event_if(&task_name, &Event::TaskStarted {
         ts: event_timestamp(),
         run_id,
+        workflow_hash: Some(project.pin_workflow(&wf_name)?),
         workflow: wf_name,
-    })?;
+        parent: None,
+        vars: run_vars,
+    }, |events| events.len() == checked_len)?;
+    if !claimed {
+        let status = project.replay_task(&task_name)?.map(|s| s.status).unwrap_or_default();
+        return Err(PawlError::StateConflict {
+            task: task_name.clone(),
+            status: status.to_string(),
+            message: "changed by another process while starting; check 'pawl status'".into(),
+        }.into());
+    }
 
     eprintln!("Starting task: {}", task_name);
 
@@ -80,13 +191,282 @@ pub fn run(task_name: &str, reset: bool) -> Result<()> {
     Ok(())
 }
 
+/// `--when-ready`: block until every dependency's condition is met. A dependency
+/// that settled the other way (failed or stopped while it must complete, completed
+/// or stopped while it must fail) won't change by itself, so give up on it.
+fn wait_for_dependencies(project: &Project, task_name: &str, mut blocking: Vec<Dependency>) -> Result<()> {
+    eprintln!("Waiting for dependencies of '{}': {}", task_name, join(&blocking));
+    while !blocking.is_empty() {
+        for dep in &blocking {
+            project.detect_viewport_loss(dep.task())?;
+            project.detect_owner_loss(dep.task())?;
+            if let Some(status) = project.dependency_settled(dep)? {
+                return Err(PawlError::Precondition {
+                    message: format!("Dependency '{}' of '{}' is {}; not starting", dep.task(), task_name, status),
+                }.into());
+            }
+        }
+        thread::sleep(Duration::from_millis(500));
+        blocking = project.check_dependencies(task_name)?;
+    }
+    Ok(())
+}
+
+fn join(deps: &[Dependency]) -> String {
+    deps.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ")
+}
+
+/// Take over a step blocked on `wait_for`: record `step_unblocked`, unless another
+/// process already did (checked under the log lock, so only one resumer runs the step)
+fn claim_unblock(project: &Project, task_name: &str, step_idx: usize) -> Result<bool> {
+    project.append_event_if(task_name, &Event::StepUnblocked {
+        ts: event_timestamp(),
+        step: step_idx,
+    }, |events| matches!(
+        events.last(),
+        Some(Event::StepYielded { step, reason, .. }) if *step == step_idx && reason == BLOCKED
+    ))
+}
+
+/// Collect `--vars-file` (a JSON object of strings) and `--var KEY=VALUE` flags; flags win.
+fn parse_run_vars(vars: &[String], vars_file: Option<&str>) -> Result<IndexMap<String, String>> {
+    let invalid = |message: String| PawlError::Validation { message };
+    let mut run_vars = IndexMap::new();
+    if let Some(path) = vars_file {
+        let content = fs::read_to_string(path)
+            .map_err(|e| invalid(format!("cannot read vars file '{}': {}", path, e)))?;
+        let file_vars: IndexMap<String, String> = serde_json::from_str(&content)
+            .map_err(|e| invalid(format!("vars file '{}' must be a JSON object of strings: {}", path, e)))?;
+        run_vars.extend(file_vars);
+    }
+    for var in vars {
+        match var.split_once('=') {
+            Some((key, value)) if !key.is_empty() => {
+                run_vars.insert(key.to_string(), value.to_string());
+            }
+            _ => return Err(invalid(format!("--var expects KEY=VALUE, got '{}'", var)).into()),
+        }
+    }
+    Ok(run_vars)
+}
+
 /// Continue execution from current step (called by pawl done, pawl reset --step, etc.)
 pub fn resume_workflow(project: &Project, task_name: &str) -> Result<()> {
     execute(project, task_name)
 }
 
-/// Execute workflow steps starting from current_step
+/// Execute workflow steps starting from current_step, then wrap up if the workflow ended
 fn execute(project: &Project, task_name: &str) -> Result<()> {
+    execute_steps(project, task_name)?;
+    finish_run(project, task_name)
+}
+
+/// Wrap up after a run hands control back: if it ended, run its `finally` steps,
+/// then let the parent run (if this is a sub-workflow's child) settle its step.
+pub fn finish_run(project: &Project, task_name: &str) -> Result<()> {
+    run_finally(project, task_name)?;
+    resume_parent(project, task_name)?;
+    start_dependents(project, task_name)
+}
+
+/// A task whose run ended starts its pending `auto_start` dependents whose
+/// dependencies are now all met, each as a background `pawl start`.
+fn start_dependents(project: &Project, task_name: &str) -> Result<()> {
+    if !project.replay_task(task_name)?.is_some_and(|s| run_ended(&s)) {
+        return Ok(());
+    }
+    for dependent in project.graph().dependents(task_name) {
+        let auto_start = project.task_config(dependent).is_some_and(|tc| tc.auto_start);
+        if !auto_start
+            || project.replay_task(dependent)?.is_some()
+            || !project.check_dependencies(dependent)?.is_empty()
+        {
+            continue;
+        }
+        eprintln!("  → auto-starting '{}'", dependent);
+        project.spawn_start(dependent)?;
+    }
+    Ok(())
+}
+
+/// Whether a run has ended: completed, failed or stopped (an interrupted run is resumable)
+fn run_ended(state: &TaskState) -> bool {
+    match state.status {
+        TaskStatus::Completed | TaskStatus::Failed => true,
+        TaskStatus::Stopped => state.message.as_deref() != Some(INTERRUPTED),
+        _ => false,
+    }
+}
+
+/// Run the workflow's `finally` steps once it has ended (completed, failed or stopped),
+/// unless they already ran since. Failures are reported and recorded as
+/// `finally_finished` but never change the task's outcome. An interrupted run
+/// is resumable, so its cleanup waits until the run really ends.
+fn run_finally(project: &Project, task_name: &str) -> Result<()> {
+    let (_, config) = project.workflow_for(task_name)?;
+    if config.finally.is_empty() {
+        return Ok(());
+    }
+    let Some(state) = project.replay_task(task_name)? else {
+        return Ok(());
+    };
+    if !run_ended(&state) {
+        return Ok(());
+    }
+
+    // Claim the cleanup under the log lock so a concurrent pawl process can't run it too
+    let status = state.status.to_string();
+    let claimed = project.append_event_if(task_name, &Event::FinallyStarted {
+        ts: event_timestamp(),
+        status: status.clone(),
+    }, finally_due)?;
+    if !claimed {
+        return Ok(());
+    }
+
+    let ctx = project.context_for(task_name, None, &state.run_id)?.var("status", status.as_str());
+    let stream_file = project.stream_file(task_name);
+    fs::create_dir_all(stream_file.parent().unwrap())?;
+    let grace = project.grace_period_for(task_name);
+    let total = config.finally.len();
+    let mut failed = 0;
+
+    for (i, step) in config.finally.iter().enumerate() {
+        eprintln!("[finally {}/{}] {}", i + 1, total, step.name);
+        let step_ctx = ctx.clone().var("step", step.name.as_str());
+        let command = step_ctx.expand(step.run.as_deref().unwrap_or_default());
+
+        let start_time = Instant::now();
+        let result = run_command_to_file(
+            &command, &step_ctx.to_env_vars(), &stream_file, grace, step.effective_timeout(), |_| Ok(()),
+        )?;
+        let _ = fs::remove_file(&stream_file);
+
+        if result.success {
+            eprintln!("  ✓ Done");
+        } else if result.timed_out {
+            eprintln!("  ✗ Timed out after {}s", step.timeout.unwrap_or_default());
+        } else {
+            eprintln!("  ✗ Failed (exit code {})", result.exit_code);
+        }
+        if !result.success {
+            failed += 1;
+        }
+
+        project.append_event(task_name, &Event::FinallyFinished {
+            ts: event_timestamp(),
+            step: i,
+            success: result.success,
+            exit_code: result.exit_code,
+            duration: Some(start_time.elapsed().as_secs_f64()),
+            stderr: (!result.stderr.is_empty()).then_some(result.stderr),
+        })?;
+    }
+
+    if failed > 0 {
+        eprintln!("  ! {} finally step(s) failed; task stays {}.", failed, state.status);
+    }
+    Ok(())
+}
+
+/// A child run that ended after handing control back (e.g. it waited at a gate):
+/// continue its parent, whose sub-workflow step now settles with the child's outcome.
+fn resume_parent(project: &Project, task_name: &str) -> Result<()> {
+    let events = project.read_events(task_name)?;
+    let Some(ParentRun { task: parent, run_id, step }) = run_parent(&events) else {
+        return Ok(());
+    };
+    let Some(state) = project.replay_task(task_name)? else {
+        return Ok(());
+    };
+    if !run_ended(&state) {
+        return Ok(());
+    }
+    let waiting = project.replay_task(&parent)?.is_some_and(|p| {
+        p.run_id == run_id && p.status == TaskStatus::Running && p.current_step == step
+    });
+    let parent_events = project.read_events(&parent)?;
+    let linked = sub_workflow_run(&parent_events, step).is_some_and(|(_, id)| id == state.run_id);
+    if !waiting || !linked {
+        return Ok(());
+    }
+
+    eprintln!("Sub-workflow '{}' {}; resuming '{}'", task_name, state.status, parent);
+    execute(project, &parent)
+}
+
+/// Compensate the run's successful steps, newest first, with their `compensate`
+/// commands. Each result is recorded as `step_compensated`, so a crashed or
+/// failed rollback picks up where it stopped. Stops at the first failing
+/// compensation. Returns false if nothing was left to compensate.
+pub fn run_rollback(project: &Project, task_name: &str, reason: &str) -> Result<bool> {
+    let (_, config) = project.workflow_for(task_name)?;
+    let Some(state) = project.replay_task(task_name)? else {
+        return Ok(false);
+    };
+    let pending: Vec<usize> = (0..config.workflow.len())
+        .rev()
+        .filter(|i| {
+            state.step_status.get(i) == Some(&StepStatus::Success)
+                && config.workflow[*i].compensate.is_some()
+        })
+        .collect();
+    let resuming = matches!(state.message.as_deref(), Some(ROLLING_BACK | ROLLBACK_FAILED));
+    if pending.is_empty() && !resuming {
+        return Ok(false);
+    }
+
+    if resuming {
+        eprintln!("Resuming rollback of '{}'", task_name);
+    } else {
+        eprintln!("Rolling back '{}'", task_name);
+        project.append_event(task_name, &Event::RollbackStarted {
+            ts: event_timestamp(),
+            reason: reason.to_string(),
+        })?;
+    }
+
+    let stream_file = project.stream_file(task_name);
+    fs::create_dir_all(stream_file.parent().unwrap())?;
+    let grace = project.grace_period_for(task_name);
+
+    for step_idx in pending {
+        let step = &config.workflow[step_idx];
+        eprintln!("[rollback] {}", step.name);
+        // Step context, so compensations see the step's name and captured outputs
+        let ctx = project.context_for(task_name, Some(step_idx), &state.run_id)?;
+        let command = ctx.expand(step.compensate.as_deref().unwrap_or_default());
+
+        let start_time = Instant::now();
+        let result = run_command_to_file(
+            &command, &ctx.to_env_vars(), &stream_file, grace, step.effective_timeout(), |_| Ok(()),
+        )?;
+        let _ = fs::remove_file(&stream_file);
+
+        project.append_event(task_name, &Event::StepCompensated {
+            ts: event_timestamp(),
+            step: step_idx,
+            success: result.success,
+            exit_code: result.exit_code,
+            duration: Some(start_time.elapsed().as_secs_f64()),
+            stderr: (!result.stderr.is_empty()).then(|| result.stderr.clone()),
+        })?;
+
+        if !result.success {
+            eprintln!("  ✗ Compensation failed (exit code {})", result.exit_code);
+            eprintln!("  Fix the cause, then 'pawl rollback {}' to continue.", task_name);
+            return Ok(true);
+        }
+        eprintln!("  ✓ Compensated");
+    }
+
+    project.append_event(task_name, &Event::RollbackFinished { ts: event_timestamp() })?;
+    eprintln!("  Rolled back.");
+    Ok(true)
+}
+
+/// Run workflow steps starting from current_step until the workflow ends or hands off
+fn execute_steps(project: &Project, task_name: &str) -> Result<()> {
     let skip_list: Vec<String> = project
         .task_config(task_name)
         .map(|tc| tc.skip.clone())
@@ -115,6 +495,7 @@ fn execute(project: &Project, task_name: &str) -> Result<()> {
             project.append_event(task_name, &Event::StepSkipped {
                 ts: event_timestamp(),
                 step: step_idx,
+                reason: Some("skip list".to_string()),
             })?;
             eprintln!(
                 "[{}/{}] {} (skipped)",
@@ -125,12 +506,29 @@ fn execute(project: &Project, task_name: &str) -> Result<()> {
             continue;
         }
 
-        let mut ctx = project.context_for(task_name, Some(step_idx), run_id);
+        let mut ctx = project.context_for(task_name, Some(step_idx), run_id)?;
         let events = project.read_events(task_name)?;
         let (retry_count, last_feedback) = super::common::extract_step_context(&events, step_idx);
-        ctx = ctx.var("retry_count", retry_count.to_string());
-        if let Some(fb) = &last_feedback {
-            ctx = ctx.var("last_verify_output", fb);
+        ctx = ctx.retry(retry_count, last_feedback.as_deref());
+
+        // Runtime condition: a false `when` skips the step, recording why
+        if let Some(when) = &step.when
+            && !condition::evaluate(when, &ctx)?
+        {
+            let reason = format!("when '{}' is false", when);
+            eprintln!(
+                "[{}/{}] {} (skipped: {})",
+                step_idx + 1,
+                workflow_len,
+                step.name,
+                reason
+            );
+            project.append_event(task_name, &Event::StepSkipped {
+                ts: event_timestamp(),
+                step: step_idx,
+                reason: Some(reason),
+            })?;
+            continue;
         }
 
         eprintln!(
@@ -140,6 +538,22 @@ fn execute(project: &Project, task_name: &str) -> Result<()> {
             step.name
         );
 
+        // Cross-task dependency: yield until the steps it waits for have succeeded
+        let unmet = project.unmet_wait_for(step)?;
+        if !unmet.is_empty() {
+            project.append_event(task_name, &Event::StepYielded {
+                ts: event_timestamp(),
+                step: step_idx,
+                reason: BLOCKED.to_string(),
+            })?;
+            // One may have succeeded since the check, before its watcher could see us blocked
+            if project.unmet_wait_for(step)?.is_empty() && claim_unblock(project, task_name, step_idx)? {
+                continue;
+            }
+            eprintln!("  → Blocked on {}. Resumes once they succeed.", unmet.join(", "));
+            return Ok(());
+        }
+
         // Handle different step types
         if step.is_gate() {
             // Gate step: wait for approval
@@ -152,6 +566,35 @@ fn execute(project: &Project, task_name: &str) -> Result<()> {
             return Ok(());
         }
 
+        // Honour a scheduled retry backoff (recorded, so it survives a crash)
+        if let Some(retry_at) = pending_retry_at(&events, step_idx)
+            && let Ok(wait) = (retry_at - event_timestamp()).to_std()
+        {
+            eprintln!("  → Retrying in {:.1}s", wait.as_secs_f64());
+            std::thread::sleep(wait);
+        }
+
+        if step.is_group() {
+            if !execute_group(project, task_name, step, step_idx, &ctx)? {
+                return Ok(());
+            }
+            continue;
+        }
+
+        if step.is_sub_workflow() {
+            if !execute_sub_workflow(project, task_name, step, step_idx, &ctx)? {
+                return Ok(());
+            }
+            continue;
+        }
+
+        if step.is_foreach() {
+            if !execute_foreach(project, task_name, step, step_idx, &ctx)? {
+                return Ok(());
+            }
+            continue;
+        }
+
         let command = step.run.as_ref().unwrap();
         let expanded = ctx.expand(command);
 
@@ -181,6 +624,7 @@ pub struct StepRecord {
     pub duration: Option<f64>,
     pub stdout: Option<String>,
     pub stderr: Option<String>,
+    pub timed_out: bool,
 }
 
 /// Execute a normal (synchronous) step with streaming stdout
@@ -203,16 +647,37 @@ fn execute_step(
 
     let start_time = Instant::now();
     let env = ctx.to_env_vars();
-
-    let result = run_command_to_file(command, &env, &stream_file)?;
+    let grace = project.grace_period_for(task_name);
+
+    let result = run_command_to_file(command, &env, &stream_file, grace, step.effective_timeout(), |pgid| {
+        // Record ownership so a crash mid-step is detectable after replay,
+        // and the process group so stop/reset can take the whole step down
+        let owner = ProcessOwner::current();
+        project.append_event(task_name, &Event::StepStarted {
+            ts: event_timestamp(),
+            step: step_idx,
+            pid: owner.pid,
+            hostname: owner.hostname,
+            boot_id: owner.boot_id,
+            pgid: Some(pgid),
+        })
+    })?;
 
     let duration = start_time.elapsed().as_secs_f64();
 
+    // Re-check state (pawl stop/reset may have taken the step away meanwhile)
+    let state = project.replay_task(task_name)?;
+    if !matches!(&state, Some(s) if s.status == TaskStatus::Running && s.current_step == step_idx) {
+        let _ = fs::remove_file(&stream_file);
+        return Ok(false);
+    }
+
     let record = StepRecord {
         exit_code: result.exit_code,
         duration: Some(duration),
         stdout: Some(result.stdout.clone()),
         stderr: Some(result.stderr.clone()),
+        timed_out: result.timed_out,
     };
 
     // Clean up stream file before settle_step
@@ -220,6 +685,8 @@ fn execute_step(
 
     if result.success {
         eprintln!("  ✓ Done");
+    } else if result.timed_out {
+        eprintln!("  ✗ Timed out after {}s", step.timeout.unwrap_or_default());
     } else {
         eprintln!("  ✗ Failed (exit code {})", result.exit_code);
         if !result.stderr.is_empty() {
@@ -232,6 +699,469 @@ fn execute_step(
     settle_step(project, task_name, step_idx, step, record)
 }
 
+/// Execute a parallel group: run its unfinished sub-steps concurrently, join their
+/// results ("all" or "any"), then settle the group like a single step. Sub-steps that
+/// finished before a crash keep their recorded result; only the others run.
+fn execute_group(
+    project: &Project,
+    task_name: &str,
+    step: &Step,
+    step_idx: usize,
+    ctx: &Context,
+) -> Result<bool> {
+    let group_start = Instant::now();
+    // This process owns the group (crash detection; stop signals it to take the sub-steps down)
+    let owner = ProcessOwner::current();
+    project.append_event(task_name, &Event::StepStarted {
+        ts: event_timestamp(),
+        step: step_idx,
+        pid: owner.pid,
+        hostname: owner.hostname,
+        boot_id: owner.boot_id,
+        pgid: None,
+    })?;
+
+    let any = step.joins_any();
+    let decided = |results: &HashMap<usize, bool>| {
+        if any {
+            results.values().any(|ok| *ok)
+        } else {
+            step.fail_fast && results.values().any(|ok| !ok)
+        }
+    };
+
+    let events = project.read_events(task_name)?;
+    let mut results = sub_step_results(&events, step_idx);
+    for (i, sub) in step.parallel.iter().enumerate() {
+        if let Some(ok) = results.get(&i) {
+            eprintln!("  {} {} (finished earlier)", if *ok { "✓" } else { "✗" }, sub.name);
+        }
+    }
+
+    let pending: Vec<usize> = (0..step.parallel.len()).filter(|i| !results.contains_key(i)).collect();
+    let stream_file = project.stream_file(task_name);
+    let sub_stream = |i: usize| stream_file.with_extension(format!("{}.stream", i));
+    let mut feedback: HashMap<usize, String> = HashMap::new();
+
+    if !pending.is_empty() && !decided(&results) {
+        fs::create_dir_all(stream_file.parent().unwrap())?;
+        let commands: Vec<BatchCommand> = pending
+            .iter()
+            .map(|&i| {
+                let sub = &step.parallel[i];
+                let sub_ctx = ctx.clone().var("sub_step", sub.name.as_str());
+                BatchCommand {
+                    cmd: sub_ctx.expand(sub.run.as_deref().unwrap_or_default()),
+                    env: sub_ctx.to_env_vars(),
+                    stdout_path: sub_stream(i),
+                    timeout: sub.effective_timeout(),
+                }
+            })
+            .collect();
+
+        let start_time = Instant::now();
+        let grace = project.grace_period_for(task_name);
+        let finished = run_batch_to_files(
+            &commands,
+            grace,
+            |j, pgid| {
+                project.append_event(task_name, &Event::SubStepStarted {
+                    ts: event_timestamp(),
+                    step: step_idx,
+                    sub: pending[j],
+                    pgid: Some(pgid),
+                })
+            },
+            |j, result| {
+                let i = pending[j];
+                let name = &step.parallel[i].name;
+                if result.success {
+                    eprintln!("  ✓ {}", name);
+                } else if result.timed_out {
+                    eprintln!("  ✗ {} (timed out)", name);
+                } else {
+                    eprintln!("  ✗ {} (exit code {})", name, result.exit_code);
+                }
+                if !result.success {
+                    let detail = if result.timed_out { "timed out".to_string() } else { format!("exit code {}", result.exit_code) };
+                    feedback.insert(i, format!("{}: {}\n{}", name, detail, result.stderr.trim_end()));
+                }
+                project.append_event(task_name, &Event::SubStepFinished {
+                    ts: event_timestamp(),
+                    step: step_idx,
+                    sub: i,
+                    success: result.success,
+                    exit_code: result.exit_code,
+                    duration: Some(start_time.elapsed().as_secs_f64()),
+                    stdout: Some(result.stdout.clone()),
+                    stderr: Some(result.stderr.clone()),
+                    timed_out: result.timed_out,
+                    cancelled: false,
+                    retry_at: None,
+                })?;
+                results.insert(i, result.success);
+                Ok(decided(&results))
+            },
+        )?;
+
+        for (j, result) in finished.iter().enumerate() {
+            let _ = fs::remove_file(sub_stream(pending[j]));
+            if result.is_some() {
+                continue;
+            }
+            eprintln!("  - {} (cancelled)", step.parallel[pending[j]].name);
+            project.append_event(task_name, &Event::SubStepFinished {
+                ts: event_timestamp(),
+                step: step_idx,
+                sub: pending[j],
+                success: false,
+                exit_code: -1,
+                duration: Some(start_time.elapsed().as_secs_f64()),
+                stdout: None,
+                stderr: None,
+                timed_out: false,
+                cancelled: true,
+                retry_at: None,
+            })?;
+        }
+    }
+
+    // Re-check state (pawl stop/reset may have taken the step away meanwhile)
+    let state = project.replay_task(task_name)?;
+    if !matches!(&state, Some(s) if s.status == TaskStatus::Running && s.current_step == step_idx) {
+        return Ok(false);
+    }
+
+    let success = if any {
+        results.values().any(|ok| *ok)
+    } else {
+        (0..step.parallel.len()).all(|i| results.get(&i) == Some(&true))
+    };
+    let failed: Vec<String> = (0..step.parallel.len())
+        .filter(|i| results.get(i) == Some(&false))
+        .map(|i| feedback.remove(&i).unwrap_or_else(|| format!("{}: failed", step.parallel[i].name)))
+        .collect();
+
+    if success {
+        eprintln!("  ✓ Done");
+    } else {
+        eprintln!("  ✗ Failed ({} of {} sub-steps failed)", failed.len(), step.parallel.len());
+    }
+
+    let record = StepRecord {
+        exit_code: if success { 0 } else { 1 },
+        duration: Some(group_start.elapsed().as_secs_f64()),
+        stdout: None,
+        stderr: (!failed.is_empty()).then(|| failed.join("\n")),
+        timed_out: false,
+    };
+    settle_step(project, task_name, step_idx, step, record)
+}
+
+/// Execute a foreach step: resolve its items (once per attempt, recorded so a resumed
+/// attempt keeps them), then run the command for each item in turn with ${item} set.
+/// With on_fail "retry" a failing item is retried on its own budget, and the step fails
+/// once an item runs out of retries. Items that succeeded before a crash don't run again.
+fn execute_foreach(
+    project: &Project,
+    task_name: &str,
+    step: &Step,
+    step_idx: usize,
+    ctx: &Context,
+) -> Result<bool> {
+    let step_start = Instant::now();
+    // This process owns the step (crash detection; stop signals it to take the item down)
+    let owner = ProcessOwner::current();
+    project.append_event(task_name, &Event::StepStarted {
+        ts: event_timestamp(),
+        step: step_idx,
+        pid: owner.pid,
+        hostname: owner.hostname,
+        boot_id: owner.boot_id,
+        pgid: None,
+    })?;
+
+    let stream_file = project.stream_file(task_name);
+    fs::create_dir_all(stream_file.parent().unwrap())?;
+    let grace = project.grace_period_for(task_name);
+
+    let events = project.read_events(task_name)?;
+    let items = match foreach_items(&events, step_idx) {
+        Some(items) => items,
+        None => {
+            let items: Vec<String> = match &step.foreach {
+                Some(Foreach::List(items)) => items.iter().map(|item| ctx.expand(item)).collect(),
+                Some(Foreach::Source(ForeachSource { var: Some(var), .. })) => {
+                    ctx.get(var).unwrap_or_default().split_whitespace().map(String::from).collect()
+                }
+                Some(Foreach::Source(ForeachSource { command: Some(command), .. })) => {
+                    let result = run_command_to_file(
+                        &ctx.expand(command), &ctx.to_env_vars(), &stream_file, grace, step.effective_timeout(), |_| Ok(()),
+                    )?;
+                    let _ = fs::remove_file(&stream_file);
+                    if !result.success {
+                        eprintln!("  ✗ foreach command failed (exit code {})", result.exit_code);
+                        let record = StepRecord {
+                            exit_code: result.exit_code,
+                            duration: Some(step_start.elapsed().as_secs_f64()),
+                            stdout: Some(result.stdout),
+                            stderr: Some(format!("foreach command failed\n{}", result.stderr)),
+                            timed_out: result.timed_out,
+                        };
+                        return settle_step(project, task_name, step_idx, step, record);
+                    }
+                    result.stdout.lines().map(str::trim).filter(|l| !l.is_empty()).map(String::from).collect()
+                }
+                _ => Vec::new(),
+            };
+            project.append_event(task_name, &Event::StepExpanded {
+                ts: event_timestamp(),
+                step: step_idx,
+                items: items.clone(),
+            })?;
+            items
+        }
+    };
+
+    let finished = sub_step_results(&events, step_idx);
+    let retries = step.on_fail_strategy() == Some("retry");
+    let total = items.len();
+    let mut failure: Option<(i32, String)> = None;
+
+    'items: for (i, item) in items.iter().enumerate() {
+        if finished.get(&i) == Some(&true) {
+            eprintln!("  ✓ {} (finished earlier)", item);
+            continue;
+        }
+        let item_ctx = ctx.clone().var("item", item.as_str()).var("item_index", i.to_string());
+        let mut feedback: Option<String> = None;
+
+        loop {
+            // Each failed run of this item so far is one retry used from its own budget
+            let failures = item_failures(&project.read_events(task_name)?, step_idx, i);
+            if let Some(&exit_code) = failures.last() {
+                let can_retry = retries
+                    && failures.len() <= step.effective_max_retries()
+                    && step.retries_exit_code(exit_code);
+                if !can_retry {
+                    let mut summary = format!("{}: exit code {}", item, exit_code);
+                    if let Some(fb) = &feedback {
+                        summary.push('\n');
+                        summary.push_str(fb);
+                    }
+                    failure = Some((exit_code, summary));
+                    break 'items;
+                }
+                // Honour the backoff recorded with the failure (it survives a crash)
+                let delay = item_retry_at(&project.read_events(task_name)?, step_idx, i)
+                    .and_then(|at| (at - event_timestamp()).to_std().ok());
+                let wait = delay.map(|d| format!(" in {:.1}s", d.as_secs_f64())).unwrap_or_default();
+                eprintln!("  {} failed (attempt {}/{}). Retrying{}...",
+                         item, failures.len(), step.effective_max_retries(), wait);
+                if let Some(delay) = delay {
+                    std::thread::sleep(delay);
+                }
+            }
+
+            let run_ctx = item_ctx.clone().retry(failures.len(), feedback.as_deref());
+            eprintln!("  [{}/{}] {}", i + 1, total, item);
+
+            let start_time = Instant::now();
+            let result = run_command_to_file(
+                &run_ctx.expand(step.run.as_deref().unwrap_or_default()),
+                &run_ctx.to_env_vars(),
+                &stream_file,
+                grace,
+                step.effective_timeout(),
+                |pgid| {
+                    project.append_event(task_name, &Event::SubStepStarted {
+                        ts: event_timestamp(),
+                        step: step_idx,
+                        sub: i,
+                        pgid: Some(pgid),
+                    })
+                },
+            )?;
+            let _ = fs::remove_file(&stream_file);
+
+            // Re-check state (pawl stop/reset may have taken the step away meanwhile)
+            let state = project.replay_task(task_name)?;
+            if !matches!(&state, Some(s) if s.status == TaskStatus::Running && s.current_step == step_idx) {
+                return Ok(false);
+            }
+
+            // A failure the item will retry schedules its backoff
+            let will_retry = !result.success
+                && retries
+                && failures.len() < step.effective_max_retries()
+                && step.retries_exit_code(result.exit_code);
+            let retry_at = if will_retry {
+                chrono::Duration::from_std(step.retry_delay_for(failures.len(), jitter_unit()))
+                    .ok()
+                    .filter(|d| !d.is_zero())
+                    .map(|d| event_timestamp() + d)
+            } else {
+                None
+            };
+            project.append_event(task_name, &Event::SubStepFinished {
+                ts: event_timestamp(),
+                step: step_idx,
+                sub: i,
+                success: result.success,
+                exit_code: result.exit_code,
+                duration: Some(start_time.elapsed().as_secs_f64()),
+                stdout: Some(result.stdout.clone()),
+                stderr: Some(result.stderr.clone()),
+                timed_out: result.timed_out,
+                cancelled: false,
+                retry_at,
+            })?;
+
+            if result.success {
+                eprintln!("    ✓ Done");
+                break;
+            }
+            if result.timed_out {
+                eprintln!("    ✗ Timed out after {}s", step.timeout.unwrap_or_default());
+                feedback = Some("Timed out".to_string());
+            } else {
+                eprintln!("    ✗ Failed (exit code {})", result.exit_code);
+                feedback = Some(result.stderr.trim_end().to_string()).filter(|s| !s.is_empty());
+            }
+        }
+    }
+
+    // Re-check state (pawl stop/reset may have taken the step away meanwhile)
+    let state = project.replay_task(task_name)?;
+    if !matches!(&state, Some(s) if s.status == TaskStatus::Running && s.current_step == step_idx) {
+        return Ok(false);
+    }
+
+    let (exit_code, stderr) = match failure {
+        Some((exit_code, summary)) => {
+            eprintln!("  ✗ Failed ({})", summary.lines().next().unwrap_or_default());
+            (exit_code, Some(summary))
+        }
+        None => {
+            eprintln!("  ✓ Done ({} items)", total);
+            (0, None)
+        }
+    };
+    let record = StepRecord {
+        exit_code,
+        duration: Some(step_start.elapsed().as_secs_f64()),
+        stdout: None,
+        stderr,
+        timed_out: false,
+    };
+    settle_step(project, task_name, step_idx, step, record)
+}
+
+/// Execute a sub-workflow step: start a child run of the named workflow (or pick up
+/// the one this attempt already started) and run it inline. Once the child run has
+/// ended, the step settles with its outcome; if it hands control back earlier (a gate,
+/// a viewport), the parent resumes when the child ends.
+fn execute_sub_workflow(
+    project: &Project,
+    task_name: &str,
+    step: &Step,
+    step_idx: usize,
+    ctx: &Context,
+) -> Result<bool> {
+    let child = child_task_name(task_name, &step.name);
+    let events = project.read_events(task_name)?;
+    let current = sub_workflow_run(&events, step_idx)
+        .and_then(|(_, run_id)| project.replay_task(&child).ok().flatten().filter(|s| s.run_id == run_id));
+
+    match current {
+        // Already ended (pawl died before the step settled): its outcome stands
+        Some(child_state) if run_ended(&child_state) => {}
+        Some(child_state) if child_state.status == TaskStatus::Stopped => {
+            // Interrupted together with this step: re-run the child's interrupted step
+            let child_step = child_state.current_step;
+            let child_events = project.read_events(&child)?;
+            project.append_event(&child, &Event::StepReset {
+                ts: event_timestamp(),
+                step: child_step,
+                auto: false,
+                retry_at: pending_retry_at(&child_events, child_step),
+                resumed: true,
+            })?;
+            eprintln!("  → Resuming sub-workflow '{}'", child);
+            execute_steps(project, &child)?;
+            run_finally(project, &child)?;
+        }
+        // Running elsewhere or waiting: the child resumes this task when it ends
+        Some(_) => return Ok(false),
+        None => {
+            let parent_run = project.replay_task(task_name)?.expect("Task state missing").run_id;
+            let run_id = Uuid::new_v4().to_string();
+            let vars = step.vars.iter().map(|(k, v)| (k.clone(), ctx.expand(v))).collect();
+            let wf_name = step.workflow.clone().unwrap_or_default();
+            project.append_event(task_name, &Event::SubWorkflowStarted {
+                ts: event_timestamp(),
+                step: step_idx,
+                child: child.clone(),
+                run_id: run_id.clone(),
+            })?;
+            project.append_event(&child, &Event::TaskStarted {
+                ts: event_timestamp(),
+                run_id,
+                workflow: wf_name.clone(),
+                parent: Some(ParentRun { task: task_name.to_string(), run_id: parent_run, step: step_idx }),
+                vars,
+                workflow_hash: Some(project.pin_workflow(&wf_name)?),
+            })?;
+            eprintln!("  → Sub-workflow '{}' as '{}'", wf_name, child);
+            execute_steps(project, &child)?;
+            run_finally(project, &child)?;
+        }
+    }
+
+    // Re-check state (pawl stop/reset may have taken the step away meanwhile)
+    let state = project.replay_task(task_name)?;
+    if !matches!(&state, Some(s) if s.status == TaskStatus::Running && s.current_step == step_idx) {
+        return Ok(false);
+    }
+    let Some(child_state) = project.replay_task(&child)? else {
+        return Ok(false);
+    };
+    if !run_ended(&child_state) {
+        eprintln!("  → Sub-workflow '{}' is {}; '{}' continues when it ends.", child, child_state.status, task_name);
+        return Ok(false);
+    }
+
+    let success = child_state.status == TaskStatus::Completed;
+    let stderr = (!success).then(|| {
+        let child_events = project.read_events(&child).unwrap_or_default();
+        let (_, feedback) = super::common::extract_step_context(&child_events, child_state.current_step);
+        let mut summary = format!("sub-workflow '{}' {}", child, child_state.status);
+        if let Some(message) = &child_state.message {
+            summary.push_str(&format!(" ({})", message));
+        }
+        match feedback {
+            Some(feedback) => format!("{}\n{}", summary, feedback),
+            None => summary,
+        }
+    });
+    if success {
+        eprintln!("  ✓ Done");
+    } else {
+        eprintln!("  ✗ Sub-workflow {}", child_state.status);
+    }
+
+    let record = StepRecord {
+        exit_code: if success { 0 } else { 1 },
+        duration: child_state.started_at
+            .zip(child_state.updated_at)
+            .map(|(start, end)| (end - start).num_milliseconds() as f64 / 1000.0),
+        stdout: None,
+        stderr,
+        timed_out: false,
+    };
+    settle_step(project, task_name, step_idx, step, record)
+}
+
 // --- combine | decide | split pipeline ---
 
 #[derive(Debug, PartialEq)]
@@ -246,6 +1176,7 @@ pub(crate) enum FailPolicy {
     Terminal,
     Retry { can_retry: bool },
     Manual,
+    Goto { target: usize, can_jump: bool },
 }
 
 #[derive(Debug, PartialEq)]
@@ -253,10 +1184,11 @@ pub(crate) enum Verdict {
     Advance,
     Yield { reason: &'static str },
     Retry,
+    Jump { target: usize, reason: &'static str },
     Fail,
 }
 
-/// Pure decision function: 2 parameters, 6 rules.
+/// Pure decision function: 2 parameters, 7 rules.
 pub(crate) fn decide(outcome: Outcome, policy: FailPolicy) -> Verdict {
     match outcome {
         Outcome::Success => Verdict::Advance,
@@ -264,23 +1196,58 @@ pub(crate) fn decide(outcome: Outcome, policy: FailPolicy) -> Verdict {
         Outcome::Failure { .. } => match policy {
             FailPolicy::Retry { can_retry: true } => Verdict::Retry,
             FailPolicy::Manual => Verdict::Yield { reason: "on_fail_manual" },
+            FailPolicy::Goto { target, can_jump: true } => Verdict::Jump { target, reason: "on_fail" },
             _ => Verdict::Fail,
         },
     }
 }
 
-fn derive_fail_policy(project: &Project, task_name: &str, step: &Step, step_idx: usize) -> Result<FailPolicy> {
-    match step.on_fail.as_deref() {
+fn derive_fail_policy(
+    project: &Project,
+    task_name: &str,
+    step: &Step,
+    step_idx: usize,
+    exit_code: i32,
+) -> Result<FailPolicy> {
+    if let Some(OnFail::Goto(goto)) = &step.on_fail {
+        return match jump_target(project, task_name, &goto.goto)? {
+            Some(target) => Ok(FailPolicy::Goto { target, can_jump: true }),
+            None => Ok(FailPolicy::Goto { target: step_idx, can_jump: false }),
+        };
+    }
+    match step.on_fail_strategy() {
         Some("retry") => {
             let events = project.read_events(task_name)?;
-            let count = crate::model::event::count_auto_retries(&events, step_idx);
-            Ok(FailPolicy::Retry { can_retry: count < step.effective_max_retries() })
+            let count = count_auto_retries(&events, step_idx);
+            // A foreach item that failed has already used its own retries
+            let item_failed = exit_code != 0 && foreach_items(&events, step_idx).is_some();
+            let can_retry = count < step.effective_max_retries() && step.retries_exit_code(exit_code) && !item_failed;
+            Ok(FailPolicy::Retry { can_retry })
         }
         Some("manual") => Ok(FailPolicy::Manual),
         _ => Ok(FailPolicy::Terminal),
     }
 }
 
+/// Resolve a goto target, or None once the run has used up its max_jumps.
+fn jump_target(project: &Project, task_name: &str, name: &str) -> Result<Option<usize>> {
+    let (_, config) = project.workflow_for(task_name)?;
+    let events = project.read_events(task_name)?;
+    let max_jumps = config.effective_max_jumps();
+    if count_jumps(&events) >= max_jumps {
+        eprintln!("  ! Jump limit ({}) reached, not jumping to '{}'", max_jumps, name);
+        return Ok(None);
+    }
+    // Targets are validated when the config loads
+    Ok(config.step_index(name))
+}
+
+/// Uniform sample in [0, 1) for retry jitter
+fn jitter_unit() -> f64 {
+    // The top 48 bits of a v4 UUID are fully random
+    (Uuid::new_v4().as_u128() >> 80) as f64 / (1u64 << 48) as f64
+}
+
 /// Recording + Routing: first unconditionally record, then route.
 fn apply_verdict(
     project: &Project,
@@ -291,7 +1258,11 @@ fn apply_verdict(
     verdict: &Verdict,
     verify_output: Option<String>,
 ) -> Result<bool> {
-    let success = matches!(verdict, Verdict::Advance | Verdict::Yield { reason: "verify_manual" });
+    let success = matches!(
+        verdict,
+        Verdict::Advance | Verdict::Yield { reason: "verify_manual" } | Verdict::Jump { reason: "on_success", .. }
+    );
+    let outputs = capture_outputs(project, task_name, step_idx, step, &record)?;
 
     // Phase 1: Recording — always faithfully record the run result
     project.append_event(task_name, &Event::StepFinished {
@@ -303,9 +1274,68 @@ fn apply_verdict(
         stdout: record.stdout,
         stderr: record.stderr,
         verify_output,
+        timed_out: record.timed_out,
+        outputs,
     })?;
+    if success {
+        project.release_blocked(task_name);
+    }
 
     // Phase 2: Routing — control flow decision
+    route_verdict(project, task_name, step_idx, step, verdict)
+}
+
+/// Evaluate the step's `outputs` against its result. Values that can't be
+/// captured are left out (with a warning) rather than failing the step.
+fn capture_outputs(
+    project: &Project,
+    task_name: &str,
+    step_idx: usize,
+    step: &Step,
+    record: &StepRecord,
+) -> Result<IndexMap<String, String>> {
+    let mut captured = IndexMap::new();
+    if step.outputs.is_empty() {
+        return Ok(captured);
+    }
+
+    let stdout = record.stdout.as_deref().unwrap_or("");
+    let run_id = project.replay_task(task_name)
+        .ok()
+        .flatten()
+        .map(|s| s.run_id)
+        .unwrap_or_default();
+    let ctx = project.context_for(task_name, Some(step_idx), &run_id)?;
+
+    for (name, spec) in &step.outputs {
+        let value = if let Some(pattern) = &spec.regex {
+            extract::regex_capture(pattern, stdout).ok().flatten()
+        } else if let Some(path) = &spec.json {
+            extract::json_path(path, stdout)
+        } else if let Some(file) = &spec.file {
+            let path = Path::new(&project.project_root).join(ctx.expand(file));
+            extract::file_last_line(&path)
+        } else {
+            None
+        };
+        match value {
+            Some(value) => {
+                captured.insert(name.clone(), value);
+            }
+            None => eprintln!("  ! Output '{}' not captured", name),
+        }
+    }
+    Ok(captured)
+}
+
+/// Routing half of apply_verdict, shared with rejections (whose record is StepRejected).
+fn route_verdict(
+    project: &Project,
+    task_name: &str,
+    step_idx: usize,
+    step: &Step,
+    verdict: &Verdict,
+) -> Result<bool> {
     match verdict {
         Verdict::Advance => {
             let new_state = project.replay_task(task_name)?.expect("Task state missing");
@@ -335,19 +1365,39 @@ fn apply_verdict(
         }
         Verdict::Retry => {
             let events = project.read_events(task_name)?;
-            let retry_count = crate::model::event::count_auto_retries(&events, step_idx);
-            eprintln!("  Verify failed (attempt {}/{}). Auto-retrying...",
-                     retry_count + 1, step.effective_max_retries());
+            let retry_count = count_auto_retries(&events, step_idx);
+            let delay = step.retry_delay_for(retry_count, jitter_unit());
+            let wait = if delay.is_zero() { String::new() } else { format!(" in {:.1}s", delay.as_secs_f64()) };
+            eprintln!("  Verify failed (attempt {}/{}). Auto-retrying{}...",
+                     retry_count + 1, step.effective_max_retries(), wait);
+            let retry_at = chrono::Duration::from_std(delay)
+                .ok()
+                .filter(|d| !d.is_zero())
+                .map(|d| event_timestamp() + d);
             project.append_event(task_name, &Event::StepReset {
                 ts: event_timestamp(),
                 step: step_idx,
                 auto: true,
+                retry_at,
+                resumed: false,
             })?;
             resume_workflow(project, task_name)?;
             Ok(false)
         }
+        Verdict::Jump { target, reason } => {
+            let (_, config) = project.workflow_for(task_name)?;
+            eprintln!("  → Jumping to step {} '{}'", target + 1, config.workflow[*target].name);
+            project.append_event(task_name, &Event::StepJumped {
+                ts: event_timestamp(),
+                step: step_idx,
+                to: *target,
+                reason: reason.to_string(),
+            })?;
+            Ok(true)
+        }
         Verdict::Fail => {
             eprintln!("  ✗ Failed.");
+            run_rollback(project, task_name, "failed")?;
             Ok(false)
         }
     }
@@ -360,10 +1410,12 @@ pub fn settle_step(
     task_name: &str,
     step_idx: usize,
     step: &Step,
-    record: StepRecord,
+    mut record: StepRecord,
 ) -> Result<bool> {
-    // combine: (exit_code, verify) → Outcome
-    let (outcome, verify_output) = if record.exit_code == 0 {
+    // combine: (exit_code, timed_out, verify) → Outcome
+    let (outcome, verify_output) = if record.timed_out {
+        (Outcome::Failure { feedback: format!("Timed out after {}s", step.timeout.unwrap_or_default()) }, None)
+    } else if record.exit_code == 0 {
         match run_verify(project, task_name, step, step_idx)? {
             VerifyResult::Passed => (Outcome::Success, None),
             VerifyResult::ManualNeeded => (Outcome::ManualNeeded, None),
@@ -371,21 +1423,62 @@ pub fn settle_step(
                 Outcome::Failure { feedback: feedback.clone() },
                 Some(feedback),
             ),
+            VerifyResult::TimedOut { feedback } => {
+                record.timed_out = true;
+                (Outcome::Failure { feedback: feedback.clone() }, Some(feedback))
+            }
         }
     } else {
         (Outcome::Failure { feedback: format!("Exit code: {}", record.exit_code) }, None)
     };
 
     // derive FailPolicy from Step config + retry state
-    let policy = derive_fail_policy(project, task_name, step, step_idx)?;
+    let policy = derive_fail_policy(project, task_name, step, step_idx, record.exit_code)?;
 
     // decide
-    let verdict = decide(outcome, policy);
+    let mut verdict = decide(outcome, policy);
+    if verdict == Verdict::Advance
+        && let Some(goto) = &step.on_success
+        && let Some(target) = jump_target(project, task_name, &goto.goto)?
+    {
+        verdict = Verdict::Jump { target, reason: "on_success" };
+    }
 
     // split: apply verdict
     apply_verdict(project, task_name, step_idx, step, record, &verdict, verify_output)
 }
 
+/// Rejection pipeline for a waiting step: record StepRejected, then route the
+/// failure through on_fail like a verify failure. The message becomes the feedback.
+/// Rejecting an on_fail_manual decision fails the step outright.
+pub fn settle_rejection(
+    project: &Project,
+    task_name: &str,
+    step_idx: usize,
+    step: &Step,
+    message: Option<String>,
+) -> Result<bool> {
+    let yielded_on_fail = project
+        .replay_task(task_name)?
+        .and_then(|s| s.message)
+        .is_some_and(|reason| reason == "on_fail_manual");
+
+    project.append_event(task_name, &Event::StepRejected {
+        ts: event_timestamp(),
+        step: step_idx,
+        message: message.clone(),
+    })?;
+
+    let policy = if yielded_on_fail {
+        FailPolicy::Terminal
+    } else {
+        derive_fail_policy(project, task_name, step, step_idx, 0)?
+    };
+    let verdict = decide(Outcome::Failure { feedback: message.unwrap_or_default() }, policy);
+
+    route_verdict(project, task_name, step_idx, step, &verdict)
+}
+
 /// Execute an in_viewport step (send to viewport)
 fn launch_in_viewport(
     project: &Project,
@@ -426,6 +1519,7 @@ enum VerifyResult {
     Passed,
     ManualNeeded,
     Failed { feedback: String },
+    TimedOut { feedback: String },
 }
 
 /// Run the verify command for a step, if any.
@@ -439,16 +1533,20 @@ fn run_verify(project: &Project, task_name: &str, step: &Step, step_idx: usize)
                 .flatten()
                 .map(|s| s.run_id)
                 .unwrap_or_default();
-            let mut ctx = project.context_for(task_name, Some(step_idx), &run_id);
+            let mut ctx = project.context_for(task_name, Some(step_idx), &run_id)?;
             let events = project.read_events(task_name)?;
             let (retry_count, last_feedback) = super::common::extract_step_context(&events, step_idx);
-            ctx = ctx.var("retry_count", retry_count.to_string());
-            if let Some(fb) = &last_feedback {
-                ctx = ctx.var("last_verify_output", fb);
-            }
+            ctx = ctx.retry(retry_count, last_feedback.as_deref());
             let expanded = ctx.expand(cmd);
             let env = ctx.to_env_vars();
-            let result = run_command(&expanded, &env, |_| {})?;
+
+            // Verify streams like a run command, in its own process group
+            let stream_file = project.stream_file(task_name);
+            fs::create_dir_all(stream_file.parent().unwrap())?;
+            let grace = project.grace_period_for(task_name);
+            let timeout = step.effective_verify_timeout();
+            let result = run_command_to_file(&expanded, &env, &stream_file, grace, timeout, |_| Ok(()))?;
+            let _ = fs::remove_file(&stream_file);
 
             if result.success {
                 Ok(VerifyResult::Passed)
@@ -463,6 +1561,15 @@ fn run_verify(project: &Project, task_name: &str, step: &Step, step_idx: usize)
                     }
                     feedback.push_str(&result.stderr);
                 }
+                if result.timed_out {
+                    let message = format!("Verify timed out after {}s", timeout.unwrap_or_default().as_secs());
+                    eprintln!("  ✗ {}", message);
+                    if !feedback.is_empty() {
+                        feedback.push('\n');
+                    }
+                    feedback.push_str(&message);
+                    return Ok(VerifyResult::TimedOut { feedback });
+                }
                 Ok(VerifyResult::Failed { feedback })
             }
         }
@@ -473,6 +1580,15 @@ fn run_verify(project: &Project, task_name: &str, step: &Step, step_idx: usize)
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_parse_run_vars() {
+        let vars = parse_run_vars(&["branch=fix/a=b".into(), "empty=".into(), "branch=final".into()], None).unwrap();
+        assert_eq!(vars.get("branch").map(String::as_str), Some("final"));
+        assert_eq!(vars.get("empty").map(String::as_str), Some(""));
+        assert!(parse_run_vars(&["novalue".into()], None).is_err());
+        assert!(parse_run_vars(&["=x".into()], None).is_err());
+    }
+
     #[test]
     fn test_decide_advance() {
         assert_eq!(decide(Outcome::Success, FailPolicy::Terminal), Verdict::Advance);
@@ -514,4 +1630,17 @@ mod tests {
             Verdict::Yield { reason: "on_fail_manual" }
         );
     }
+
+    #[test]
+    fn test_decide_failure_goto() {
+        assert_eq!(
+            decide(Outcome::Failure { feedback: "bad".into() }, FailPolicy::Goto { target: 1, can_jump: true }),
+            Verdict::Jump { target: 1, reason: "on_fail" }
+        );
+        assert_eq!(
+            decide(Outcome::Failure { feedback: "bad".into() }, FailPolicy::Goto { target: 1, can_jump: false }),
+            Verdict::Fail
+        );
+        assert_eq!(decide(Outcome::Success, FailPolicy::Goto { target: 1, can_jump: true }), Verdict::Advance);
+    }
 }
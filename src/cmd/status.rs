@@ -1,7 +1,13 @@
 use anyhow::Result;
+use indexmap::IndexMap;
 use serde::Serialize;
+use std::collections::HashMap;
 
-use super::common::{extract_step_context, Project};
+use crate::model::config::Dependency;
+use crate::model::event::{finally_results, run_parent, run_vars};
+use crate::model::{Event, TaskStatus};
+
+use super::common::{child_task_name, extract_step_context, Project};
 
 /// JSON output structure for task summary
 #[derive(Serialize)]
@@ -11,6 +17,9 @@ struct TaskSummary {
     #[serde(skip_serializing_if = "Option::is_none")]
     description: Option<String>,
     status: String,
+    /// Completed, but a dependency was reset or re-run since (`invalidate_on_upstream_reset`)
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    stale: bool,
     run_id: String,
     current_step: usize,
     total_steps: usize,
@@ -21,8 +30,15 @@ struct TaskSummary {
     started_at: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     updated_at: Option<String>,
+    /// Direct dependencies whose condition is unmet, as declared
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    blocked_by: Vec<Dependency>,
+    /// Incomplete tasks upstream, direct or transitive
     #[serde(skip_serializing_if = "Vec::is_empty")]
-    blocked_by: Vec<String>,
+    blockers: Vec<String>,
+    /// Pending tasks that become ready when this one completes
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    unlocks: Vec<String>,
     retry_count: usize,
     #[serde(skip_serializing_if = "Option::is_none")]
     last_feedback: Option<String>,
@@ -36,9 +52,31 @@ struct TaskSummary {
 #[derive(Serialize)]
 pub struct TaskDetail {
     pub name: String,
+    /// Parent task, for the child run of a sub-workflow step
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent: Option<String>,
     pub description: Option<String>,
-    pub depends: Vec<String>,
+    pub depends: Vec<Dependency>,
+    /// Incomplete tasks upstream, direct or transitive
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub blockers: Vec<String>,
+    /// Pending tasks that become ready when this one completes
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unlocks: Vec<String>,
+    /// Vars the current run was started with (`--var`/`--vars-file`, or a sub-workflow step)
+    #[serde(skip_serializing_if = "IndexMap::is_empty")]
+    pub vars: IndexMap<String, String>,
+    /// The workflow file changed since the run started; the run keeps its pinned
+    /// definition until `pawl migrate`
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub workflow_changed: bool,
     pub status: String,
+    /// What a step blocked on `wait_for` still waits for (`task:step`)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub blocked_on: Vec<String>,
+    /// Completed, but a dependency was reset or re-run since (`invalidate_on_upstream_reset`)
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub stale: bool,
     pub run_id: String,
     pub current_step: usize,
     pub total_steps: usize,
@@ -56,6 +94,9 @@ pub struct TaskDetail {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub prompt: Option<String>,
     pub workflow: Vec<StepInfo>,
+    /// Cleanup steps and their results from the last time the workflow ended
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub finally: Vec<StepInfo>,
 }
 
 #[derive(Serialize)]
@@ -65,6 +106,15 @@ pub struct StepInfo {
     #[serde(skip_serializing_if = "Option::is_none")]
     step_type: Option<String>,
     status: String,
+    /// Why a skipped step was skipped
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<String>,
+    /// Sub-steps of a parallel group
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    sub_steps: Vec<StepInfo>,
+    /// The child run of a sub-workflow step, started by this run
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sub_workflow: Option<Box<TaskDetail>>,
 }
 
 /// Derive routing hints from task status.
@@ -75,11 +125,11 @@ pub fn derive_routing(status: &str, message: Option<&str>, task: &str) -> (Vec<S
         "waiting" => match message {
             Some("gate") => (
                 vec![],
-                Some(format!("confirm preconditions, then: pawl done {task}")),
+                Some(format!("confirm preconditions, then: pawl done {task} (or pawl reject {task} -m <reason>)")),
             ),
             Some("verify_manual") => (
                 vec![],
-                Some(format!("verify work quality, then: pawl done {task}")),
+                Some(format!("verify work quality, then: pawl done {task} (or pawl reject {task} -m <reason>)")),
             ),
             Some("on_fail_manual") => (
                 vec![format!("pawl reset --step {task}")],
@@ -87,7 +137,11 @@ pub fn derive_routing(status: &str, message: Option<&str>, task: &str) -> (Vec<S
             ),
             _ => (vec![], None),
         },
-        "failed" => (vec![format!("pawl reset --step {task}")], None),
+        "failed" => match message {
+            Some("rolling_back") | Some("rollback_failed") => (vec![format!("pawl rollback {task}")], None),
+            Some("rolled_back") => (vec![format!("pawl reset {task}")], None),
+            _ => (vec![format!("pawl reset --step {task}")], None),
+        },
         "stopped" => (
             vec![format!("pawl start {task}"), format!("pawl reset {task}")],
             None,
@@ -162,12 +216,15 @@ fn show_all_tasks(project: &Project) -> Result<()> {
     for name in &tasks {
         let tc = project.task_config(name);
         let blocking = project.check_dependencies(name)?;
+        let blockers = project.blockers(name)?;
+        let unlocks = project.unlocks(name)?;
         let description = tc.and_then(|t| t.description.clone());
         let (wf_name, config) = project.workflow_for(name)?;
         let wf_name = wf_name.to_string();
         let workflow_len = config.workflow.len();
 
         project.detect_viewport_loss(name)?;
+        project.detect_owner_loss(name)?;
         let summary = if let Some(state) = project.replay_task(name)? {
             let step_name = project.step_name(name, state.current_step).to_string();
             let events = project.read_events(name)?;
@@ -180,6 +237,7 @@ fn show_all_tasks(project: &Project) -> Result<()> {
                 workflow: wf_name,
                 description,
                 status: status_str,
+                stale: project.is_stale(name)?,
                 run_id: state.run_id,
                 current_step: state.current_step,
                 total_steps: workflow_len,
@@ -188,6 +246,8 @@ fn show_all_tasks(project: &Project) -> Result<()> {
                 started_at: state.started_at.map(|t| t.to_rfc3339()),
                 updated_at: state.updated_at.map(|t| t.to_rfc3339()),
                 blocked_by: blocking,
+                blockers,
+                unlocks,
                 retry_count,
                 last_feedback,
                 suggest,
@@ -200,6 +260,7 @@ fn show_all_tasks(project: &Project) -> Result<()> {
                 workflow: wf_name,
                 description,
                 status: "pending".to_string(),
+                stale: false,
                 run_id: String::new(),
                 current_step: 0,
                 total_steps: workflow_len,
@@ -208,6 +269,8 @@ fn show_all_tasks(project: &Project) -> Result<()> {
                 started_at: None,
                 updated_at: None,
                 blocked_by: blocking,
+                blockers,
+                unlocks,
                 retry_count: 0,
                 last_feedback: None,
                 suggest,
@@ -232,10 +295,35 @@ pub fn build_task_detail(project: &Project, task_name: &str) -> Result<TaskDetai
     let state = project.replay_task(task_name)?;
     let current_step = state.as_ref().map(|s| s.current_step).unwrap_or(0);
 
+    let events = project.read_events(task_name)?;
+    let mut skip_reasons: HashMap<usize, String> = HashMap::new();
+    let mut foreach_items: HashMap<usize, Vec<String>> = HashMap::new();
+    for event in &events {
+        match event {
+            Event::TaskStarted { .. } | Event::TaskReset { .. } => {
+                skip_reasons.clear();
+                foreach_items.clear();
+            }
+            Event::StepSkipped { step, reason: Some(reason), .. } => {
+                skip_reasons.insert(*step, reason.clone());
+            }
+            Event::StepExpanded { step, items, .. } => {
+                foreach_items.insert(*step, items.clone());
+            }
+            _ => {}
+        }
+    }
+
     let mut steps: Vec<StepInfo> = Vec::new();
     for (i, step) in workflow.iter().enumerate() {
         let step_type = if step.is_gate() {
             Some("gate".to_string())
+        } else if step.is_group() {
+            Some("parallel".to_string())
+        } else if step.is_sub_workflow() {
+            Some("workflow".to_string())
+        } else if step.is_foreach() {
+            Some("foreach".to_string())
         } else if step.in_viewport {
             Some("in_viewport".to_string())
         } else {
@@ -258,15 +346,89 @@ pub fn build_task_detail(project: &Project, task_name: &str) -> Result<TaskDetai
             "pending".to_string()
         };
 
+        let reason = (step_status == "skipped")
+            .then(|| skip_reasons.get(&i).cloned())
+            .flatten();
+
+        // Partial progress of a parallel group: finished sub-steps, the rest running or pending
+        let group_running = step_status == "current"
+            && state.as_ref().is_some_and(|s| s.status == TaskStatus::Running);
+        let sub_status = state.as_ref().and_then(|s| s.sub_step_status.get(&i));
+        let sub_steps = step.parallel.iter().enumerate().map(|(j, sub)| StepInfo {
+            index: j,
+            name: sub.name.clone(),
+            step_type: None,
+            status: match sub_status.and_then(|subs| subs.get(&j)) {
+                Some(status) => status.to_string(),
+                None if group_running => "running".to_string(),
+                None => "pending".to_string(),
+            },
+            reason: None,
+            sub_steps: Vec::new(),
+            sub_workflow: None,
+        });
+        // Items of a foreach step run one at a time, in order
+        let items = foreach_items.get(&i).map(Vec::as_slice).unwrap_or_default();
+        let mut item_running = group_running;
+        let items = items.iter().enumerate().map(|(j, item)| StepInfo {
+            index: j,
+            name: item.clone(),
+            step_type: None,
+            status: match sub_status.and_then(|subs| subs.get(&j)) {
+                Some(status) => status.to_string(),
+                None if item_running => {
+                    item_running = false;
+                    "running".to_string()
+                }
+                None => "pending".to_string(),
+            },
+            reason: None,
+            sub_steps: Vec::new(),
+            sub_workflow: None,
+        });
+        let sub_steps = sub_steps.chain(items).collect();
+
+        // Nested detail of the child run this run started for a sub-workflow step
+        let sub_workflow = match (&state, step.is_sub_workflow()) {
+            (Some(s), true) => {
+                let child = child_task_name(task_name, &step.name);
+                let linked = run_parent(&project.read_events(&child)?)
+                    .is_some_and(|p| p.run_id == s.run_id && p.step == i);
+                if linked {
+                    Some(Box::new(build_task_detail(project, &child)?))
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        };
+
         steps.push(StepInfo {
             index: i,
             name: step.name.clone(),
             step_type,
             status: step_status,
+            reason,
+            sub_steps,
+            sub_workflow,
         });
     }
 
-    let events = project.read_events(task_name)?;
+    let results = finally_results(&events);
+    let finally = config.finally.iter().enumerate().map(|(i, step)| StepInfo {
+        index: i,
+        name: step.name.clone(),
+        step_type: None,
+        status: match results.get(&i) {
+            Some(true) => "success",
+            Some(false) => "failed",
+            None => "pending",
+        }.to_string(),
+        reason: None,
+        sub_steps: Vec::new(),
+        sub_workflow: None,
+    }).collect();
+
     let (retry_count, last_feedback) = extract_step_context(&events, current_step);
 
     let status_str = state
@@ -278,9 +440,16 @@ pub fn build_task_detail(project: &Project, task_name: &str) -> Result<TaskDetai
 
     Ok(TaskDetail {
         name: task_name.to_string(),
+        parent: run_parent(&events).map(|p| p.task),
         description: tc.and_then(|t| t.description.clone()),
         depends: tc.map(|t| t.depends.clone()).unwrap_or_default(),
+        blockers: project.blockers(task_name)?,
+        unlocks: project.unlocks(task_name)?,
+        vars: run_vars(&events),
+        workflow_changed: project.workflow_drifted(task_name),
         status: status_str,
+        blocked_on: project.blocked_on(task_name)?.unwrap_or_default(),
+        stale: project.is_stale(task_name)?,
         run_id: state.as_ref().map(|s| s.run_id.clone()).unwrap_or_default(),
         current_step,
         total_steps: workflow_len,
@@ -292,11 +461,13 @@ pub fn build_task_detail(project: &Project, task_name: &str) -> Result<TaskDetai
         suggest,
         prompt,
         workflow: steps,
+        finally,
     })
 }
 
 fn show_task_detail(project: &Project, task_name: &str) -> Result<()> {
     project.detect_viewport_loss(task_name)?;
+    project.detect_owner_loss(task_name)?;
     let detail = build_task_detail(project, task_name)?;
     println!("{}", serde_json::to_string(&detail)?);
     Ok(())
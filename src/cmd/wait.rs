@@ -91,6 +91,7 @@ pub fn run(task_names: &[String], until: &str, timeout_secs: u64, interval_ms: u
             if reached[i] { continue; }
 
             project.detect_viewport_loss(name)?;
+            project.detect_owner_loss(name)?;
             let status = current_status(&project, name)?;
 
             if targets.contains(&status) {
@@ -142,6 +143,7 @@ fn output_all(project: &Project, tasks: &[String]) -> Result<()> {
         let mut results = Vec::new();
         for name in tasks {
             project.detect_viewport_loss(name)?;
+            project.detect_owner_loss(name)?;
             let state = project.replay_task(name)?;
             let events = project.read_events(name)?;
             let (wf_name, config) = project.workflow_for(name)?;
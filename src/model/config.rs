@@ -3,20 +3,31 @@ use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
-
 use std::path::Path;
+use std::time::Duration;
+
+use super::event::EVENT_TYPES;
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct TaskConfig {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub description: Option<String>,
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
-    pub depends: Vec<String>,
+    pub depends: Vec<Dependency>,
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub skip: Vec<String>,
     /// Task-local variables (shadow workflow-level vars)
     #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
     pub vars: IndexMap<String, String>,
+    /// Template axes: one task per combination, named by replacing `*` in the key
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub matrix: IndexMap<String, Vec<String>>,
+    /// Start in the background as soon as the last dependency completes
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub auto_start: bool,
+    /// Once completed, count as stale (not completed) if a dependency is reset or re-run afterwards
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub invalidate_on_upstream_reset: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -37,13 +48,29 @@ pub struct Config {
     #[serde(default)]
     pub tasks: IndexMap<String, TaskConfig>,
 
+    /// Command printing more tasks as JSON (a list of names or a `tasks` object)
+    #[serde(default)]
+    pub tasks_from: Option<String>,
+
     /// Workflow steps
     pub workflow: Vec<Step>,
 
+    /// Cleanup steps, run once after the workflow ends (completed, failed or stopped)
+    #[serde(default)]
+    pub finally: Vec<Step>,
+
     /// Event hooks: event type (snake_case) -> shell command
     /// Keys match Event enum serde tags: task_started, step_finished, etc.
     #[serde(default)]
     pub on: HashMap<String, String>,
+
+    /// Seconds between SIGTERM and SIGKILL when stopping a step's process group (default: 5)
+    #[serde(default)]
+    pub grace_period: Option<u64>,
+
+    /// Max on_fail/on_success jumps per run before the loop guard stops routing (default: 10)
+    #[serde(default)]
+    pub max_jumps: Option<usize>,
 }
 
 fn default_viewport() -> String {
@@ -59,6 +86,15 @@ pub struct Step {
     #[serde(default)]
     pub run: Option<String>,
 
+    /// Command that undoes a successful run, executed during a rollback
+    #[serde(default)]
+    pub compensate: Option<String>,
+
+    /// Condition checked before the step runs; false → skipped.
+    /// `lhs OP rhs` over context vars (== != < > <= >=) or a shell predicate
+    #[serde(default)]
+    pub when: Option<String>,
+
     /// Whether to run in a viewport
     #[serde(default)]
     pub in_viewport: bool,
@@ -67,25 +103,407 @@ pub struct Step {
     #[serde(default)]
     pub verify: Option<String>,
 
-    /// Failure strategy: "retry" (auto-retry) or "manual" (wait for decision)
+    /// Failure strategy: "retry" (auto-retry), "manual" (wait for decision),
+    /// or { "goto": "<step>" } (continue at another step)
     #[serde(default)]
-    pub on_fail: Option<String>,
+    pub on_fail: Option<OnFail>,
+
+    /// Success routing: { "goto": "<step>" } instead of advancing to the next step
+    #[serde(default)]
+    pub on_success: Option<Goto>,
 
     /// Max auto-retries when on_fail="retry" (default: 3)
     #[serde(default)]
     pub max_retries: Option<usize>,
+
+    /// Seconds the run command may take before its process group is killed (default: none)
+    #[serde(default)]
+    pub timeout: Option<u64>,
+
+    /// Seconds the verify command may take (default: same as timeout)
+    #[serde(default)]
+    pub verify_timeout: Option<u64>,
+
+    /// Seconds to wait before the first auto-retry (default: 0, retry immediately)
+    #[serde(default)]
+    pub retry_delay: Option<f64>,
+
+    /// Multiplier applied to retry_delay after each auto-retry (default: 2)
+    #[serde(default)]
+    pub retry_backoff: Option<f64>,
+
+    /// Upper bound in seconds for the backed-off delay (default: none)
+    #[serde(default)]
+    pub retry_max_delay: Option<f64>,
+
+    /// Randomize each delay by up to ± this fraction, 0.0–1.0 (default: 0)
+    #[serde(default)]
+    pub retry_jitter: Option<f64>,
+
+    /// Only auto-retry when the run command exits with one of these codes (default: any)
+    #[serde(default)]
+    pub retry_on_exit_codes: Option<Vec<i32>>,
+
+    /// Never auto-retry when the run command exits with one of these codes
+    #[serde(default)]
+    pub no_retry_on_exit_codes: Vec<i32>,
+
+    /// Named values captured when the step finishes, exposed to later steps
+    /// as ${steps.<step>.<output>}
+    #[serde(default)]
+    pub outputs: IndexMap<String, OutputSpec>,
+
+    /// Sub-steps run concurrently as this one step (a parallel group; no `run`)
+    #[serde(default)]
+    pub parallel: Vec<Step>,
+
+    /// Parallel group join policy: "all" (every sub-step must pass) or "any" (default: "all")
+    #[serde(default)]
+    pub join: Option<String>,
+
+    /// Parallel group with join "all": cancel the other sub-steps after the first failure
+    #[serde(default)]
+    pub fail_fast: bool,
+
+    /// Name of another workflow whose steps run as a child run of this step (no `run`)
+    #[serde(default)]
+    pub workflow: Option<String>,
+
+    /// Variables passed to the sub-workflow (expanded in this step's context)
+    #[serde(default)]
+    pub vars: IndexMap<String, String>,
+
+    /// Run `run` once per item, with ${item} / ${item_index} set
+    #[serde(default)]
+    pub foreach: Option<Foreach>,
+
+    /// Steps of other tasks (`"task:step"`) that must have succeeded before this one runs
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub wait_for: Vec<String>,
+}
+
+/// foreach value: a static list or where to read the items from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Foreach {
+    List(Vec<String>),
+    Source(ForeachSource),
+}
+
+/// Dynamic foreach items (exactly one source)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ForeachSource {
+    /// Variable whose value holds the items (split on whitespace)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub var: Option<String>,
+
+    /// Command whose non-empty stdout lines are the items
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+}
+
+/// A `depends` entry: a task that must complete, or `{ "task", "on" }` for another outcome
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Dependency {
+    Task(String),
+    On {
+        task: String,
+        #[serde(default)]
+        on: DependsOn,
+    },
+}
+
+/// What a dependency must reach before its dependent may start
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DependsOn {
+    #[default]
+    Completed,
+    Failed,
+    /// Completed, failed, or stopped (an interrupted run is not finished)
+    Finished,
+}
+
+impl Dependency {
+    pub fn task(&self) -> &str {
+        match self {
+            Dependency::Task(task) | Dependency::On { task, .. } => task,
+        }
+    }
+
+    pub fn on(&self) -> DependsOn {
+        match self {
+            Dependency::Task(_) => DependsOn::Completed,
+            Dependency::On { on, .. } => *on,
+        }
+    }
+
+    /// Same condition on another task (keeps the short form when there is one)
+    fn with_task(&self, task: String) -> Self {
+        match self {
+            Dependency::Task(_) => Dependency::Task(task),
+            Dependency::On { on, .. } => Dependency::On { task, on: *on },
+        }
+    }
+}
+
+impl std::fmt::Display for DependsOn {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            DependsOn::Completed => write!(f, "completed"),
+            DependsOn::Failed => write!(f, "failed"),
+            DependsOn::Finished => write!(f, "finished"),
+        }
+    }
+}
+
+/// `api`, or `api (on failed)` for any condition other than completed
+impl std::fmt::Display for Dependency {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.on() {
+            DependsOn::Completed => write!(f, "{}", self.task()),
+            on => write!(f, "{} (on {})", self.task(), on),
+        }
+    }
+}
+
+/// on_fail value: a strategy name or a jump
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum OnFail {
+    /// "retry" or "manual"
+    Strategy(String),
+    Goto(Goto),
+}
+
+/// Jump target for on_fail / on_success
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Goto {
+    /// Name of the step to continue at
+    pub goto: String,
+}
+
+/// Where a step output comes from (exactly one source)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutputSpec {
+    /// Regex over stdout: first capture group, or the whole match
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub regex: Option<String>,
+
+    /// JSON path into stdout, e.g. ".build.id" or "$.items[0]"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub json: Option<String>,
+
+    /// File whose last non-empty line is the value (variables expanded, relative to project root)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+}
+
+impl OutputSpec {
+    fn source_count(&self) -> usize {
+        [self.regex.is_some(), self.json.is_some(), self.file.is_some()]
+            .into_iter()
+            .filter(|set| *set)
+            .count()
+    }
 }
 
 impl Step {
     /// Gate step: no run command (waits for approval or passes through)
     pub fn is_gate(&self) -> bool {
-        self.run.is_none()
+        self.run.is_none() && !self.is_group() && !self.is_sub_workflow()
+    }
+
+    /// Sub-workflow step: runs another workflow as a child run
+    pub fn is_sub_workflow(&self) -> bool {
+        self.workflow.is_some()
+    }
+
+    /// Foreach step: runs its command once per item
+    pub fn is_foreach(&self) -> bool {
+        self.foreach.is_some()
+    }
+
+    /// Parallel group: sub-steps instead of a run command
+    pub fn is_group(&self) -> bool {
+        !self.parallel.is_empty()
+    }
+
+    /// Parallel group passes as soon as one sub-step passes (join "any")
+    pub fn joins_any(&self) -> bool {
+        self.join.as_deref() == Some("any")
     }
 
     /// Effective max retries (default: 3)
     pub fn effective_max_retries(&self) -> usize {
         self.max_retries.unwrap_or(3)
     }
+
+    /// on_fail strategy name ("retry" / "manual"), if not a jump
+    pub fn on_fail_strategy(&self) -> Option<&str> {
+        match &self.on_fail {
+            Some(OnFail::Strategy(s)) => Some(s.as_str()),
+            _ => None,
+        }
+    }
+
+    /// Whether a failure with this run exit code may be auto-retried.
+    /// Verify failures (exit code 0) are always retryable.
+    pub fn retries_exit_code(&self, exit_code: i32) -> bool {
+        if exit_code == 0 {
+            return true;
+        }
+        if self.no_retry_on_exit_codes.contains(&exit_code) {
+            return false;
+        }
+        self.retry_on_exit_codes
+            .as_ref()
+            .is_none_or(|codes| codes.contains(&exit_code))
+    }
+
+    /// Delay before auto-retry number `attempt` (0-based): retry_delay * backoff^attempt,
+    /// jittered by `unit` (uniform in [0, 1)) and capped at retry_max_delay.
+    pub fn retry_delay_for(&self, attempt: usize, unit: f64) -> Duration {
+        let base = self.retry_delay.unwrap_or(0.0).max(0.0);
+        let backoff = self.retry_backoff.unwrap_or(2.0).max(1.0);
+        let mut delay = base * backoff.powi(attempt.min(i32::MAX as usize) as i32);
+        let jitter = self.retry_jitter.unwrap_or(0.0).clamp(0.0, 1.0);
+        delay *= 1.0 - jitter + 2.0 * jitter * unit;
+        if let Some(max) = self.retry_max_delay {
+            delay = delay.min(max.max(0.0));
+        }
+        Duration::try_from_secs_f64(delay).unwrap_or(Duration::MAX)
+    }
+
+    /// Time limit for the run command, if any
+    pub fn effective_timeout(&self) -> Option<Duration> {
+        self.timeout.map(Duration::from_secs)
+    }
+
+    /// Time limit for the verify command (falls back to the run timeout)
+    pub fn effective_verify_timeout(&self) -> Option<Duration> {
+        self.verify_timeout.or(self.timeout).map(Duration::from_secs)
+    }
+}
+
+/// How bad a config problem is: errors make `pawl check` fail, warnings don't
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A config problem found by `Config::lint`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Lint {
+    pub severity: Severity,
+    /// JSON path into the workflow file, e.g. `$.workflow[2].on_fail`
+    pub path: String,
+    pub message: String,
+}
+
+/// A parallel group holds plain run sub-steps and joins them with "all" or "any".
+fn validate_group(step: &Step) -> Result<()> {
+    if step.run.is_some() || step.in_viewport {
+        anyhow::bail!("parallel step '{}' cannot have run or in_viewport", step.name);
+    }
+    if !matches!(step.join.as_deref(), None | Some("all") | Some("any")) {
+        anyhow::bail!("parallel step '{}' join must be \"all\" or \"any\"", step.name);
+    }
+    for (i, sub) in step.parallel.iter().enumerate() {
+        if sub.run.is_none() || sub.in_viewport || sub.is_group() {
+            anyhow::bail!(
+                "sub-step '{}' of '{}' needs a run command (no in_viewport or nested parallel)",
+                sub.name, step.name
+            );
+        }
+        if step.parallel[..i].iter().any(|other| other.name == sub.name) {
+            anyhow::bail!("parallel step '{}' has two sub-steps named '{}'", step.name, sub.name);
+        }
+    }
+    Ok(())
+}
+
+/// A sub-workflow step only names the workflow to run (and the vars to pass it).
+fn validate_sub_workflow(step: &Step) -> Result<()> {
+    if step.run.is_some() || step.in_viewport || step.is_group() {
+        anyhow::bail!("sub-workflow step '{}' cannot have run, in_viewport or parallel", step.name);
+    }
+    Ok(())
+}
+
+/// A foreach step runs a plain command per item; a dynamic source names one place to read items.
+fn validate_foreach(step: &Step) -> Result<()> {
+    if step.run.is_none() || step.in_viewport || step.is_group() || step.is_sub_workflow() {
+        anyhow::bail!(
+            "foreach step '{}' needs a run command (no in_viewport, parallel or workflow)",
+            step.name
+        );
+    }
+    if let Some(Foreach::Source(source)) = &step.foreach
+        && source.var.is_some() == source.command.is_some()
+    {
+        anyhow::bail!("foreach step '{}' must set exactly one of var, command", step.name);
+    }
+    Ok(())
+}
+
+/// Replace task templates (keys with `*` and a `matrix`) by one task per combination.
+/// `*` becomes the combination's values joined with `-`; each value is also a task var,
+/// and `${axis}` in description/depends/skip is substituted.
+pub fn expand_task_templates(tasks: IndexMap<String, TaskConfig>) -> Result<IndexMap<String, TaskConfig>> {
+    let mut expanded = IndexMap::new();
+    for (key, tc) in tasks {
+        if key.contains('*') == tc.matrix.is_empty() {
+            anyhow::bail!("task '{}' must have both a '*' in its name and a matrix, or neither", key);
+        }
+        if tc.matrix.is_empty() {
+            if expanded.contains_key(&key) {
+                anyhow::bail!("task '{}' is declared twice", key);
+            }
+            expanded.insert(key, tc);
+            continue;
+        }
+
+        let mut combos: Vec<Vec<(&str, &str)>> = vec![Vec::new()];
+        for (axis, values) in &tc.matrix {
+            combos = combos
+                .iter()
+                .flat_map(|combo| values.iter().map(move |v| {
+                    let mut next = combo.clone();
+                    next.push((axis.as_str(), v.as_str()));
+                    next
+                }))
+                .collect();
+        }
+
+        for combo in combos {
+            let subst = |text: &str| {
+                combo.iter().fold(text.to_string(), |acc, (axis, value)| {
+                    acc.replace(&format!("${{{}}}", axis), value)
+                })
+            };
+            let name = key.replace('*', &combo.iter().map(|(_, v)| *v).collect::<Vec<_>>().join("-"));
+            if expanded.contains_key(&name) {
+                anyhow::bail!("task '{}' (from template '{}') is declared twice", name, key);
+            }
+            let mut vars: IndexMap<String, String> =
+                combo.iter().map(|(axis, value)| (axis.to_string(), value.to_string())).collect();
+            vars.extend(tc.vars.clone());
+            expanded.insert(name, TaskConfig {
+                description: tc.description.as_deref().map(subst),
+                depends: tc.depends.iter().map(|d| d.with_task(subst(d.task()))).collect(),
+                skip: tc.skip.iter().map(|s| subst(s)).collect(),
+                vars,
+                matrix: IndexMap::new(),
+                auto_start: tc.auto_start,
+                invalidate_on_upstream_reset: tc.invalidate_on_upstream_reset,
+            });
+        }
+    }
+    Ok(expanded)
 }
 
 impl Config {
@@ -99,32 +517,195 @@ impl Config {
 
     /// Parse config from JSON string
     pub fn from_str(content: &str) -> Result<Self> {
-        let config: Self = serde_json::from_str(content).context("Failed to parse config JSON")?;
+        let mut config: Self = serde_json::from_str(content).context("Failed to parse config JSON")?;
+        config.tasks = expand_task_templates(std::mem::take(&mut config.tasks))?;
 
         for step in &config.workflow {
-            if step.run.is_none() && (step.verify.is_some() || step.on_fail.is_some()) {
-                eprintln!(
-                    "Warning: step '{}' has verify/on_fail but no run command — it will be treated as a gate step.",
-                    step.name
+            let gotos = [
+                match &step.on_fail {
+                    Some(OnFail::Goto(g)) => Some(("on_fail", g)),
+                    _ => None,
+                },
+                step.on_success.as_ref().map(|g| ("on_success", g)),
+            ];
+            for (field, goto) in gotos.into_iter().flatten() {
+                if config.step_index(&goto.goto).is_none() {
+                    anyhow::bail!(
+                        "step '{}' {} goto '{}' does not name a step in this workflow",
+                        step.name, field, goto.goto
+                    );
+                }
+            }
+            for (name, spec) in &step.outputs {
+                if spec.source_count() != 1 {
+                    anyhow::bail!(
+                        "step '{}' output '{}' must set exactly one of regex, json, file",
+                        step.name, name
+                    );
+                }
+                if let Some(pattern) = &spec.regex {
+                    regex::Regex::new(pattern).with_context(|| {
+                        format!("step '{}' output '{}' has an invalid regex", step.name, name)
+                    })?;
+                }
+            }
+            if step.is_group() {
+                validate_group(step)?;
+            }
+            if step.is_foreach() {
+                validate_foreach(step)?;
+            }
+            if step.is_sub_workflow() {
+                validate_sub_workflow(step)?;
+            }
+        }
+
+        for step in &config.finally {
+            if step.run.is_none() {
+                anyhow::bail!("finally step '{}' needs a run command", step.name);
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// Problems that don't stop the config from loading (reported by `pawl check`,
+    /// printed as warnings by `Project::load`). Paths are JSON paths into the workflow file.
+    pub fn lint(&self) -> Vec<Lint> {
+        let mut lints = Vec::new();
+        let mut error = |path: String, message: String| lints.push(Lint { severity: Severity::Error, path, message });
+
+        for (i, step) in self.workflow.iter().enumerate() {
+            if self.workflow[..i].iter().any(|other| other.name == step.name) {
+                error(format!("$.workflow[{}].name", i), format!("step name '{}' is used twice", step.name));
+            }
+            for (k, target) in step.wait_for.iter().enumerate() {
+                if !target.split_once(':').is_some_and(|(task, name)| !task.is_empty() && !name.is_empty()) {
+                    error(
+                        format!("$.workflow[{}].wait_for[{}]", i, k),
+                        format!("step '{}' wait_for '{}' is not \"task:step\"", step.name, target),
+                    );
+                }
+            }
+            if let Some(strategy) = step.on_fail_strategy()
+                && !matches!(strategy, "retry" | "manual")
+            {
+                error(
+                    format!("$.workflow[{}].on_fail", i),
+                    format!("step '{}' on_fail '{}' is not \"retry\", \"manual\" or a goto", step.name, strategy),
                 );
             }
+        }
+        for (name, tc) in &self.tasks {
+            for (k, skip) in tc.skip.iter().enumerate() {
+                if self.step_index(skip).is_none() {
+                    error(
+                        format!("$.tasks.{}.skip[{}]", name, k),
+                        format!("task '{}' skips '{}', which is not a step of this workflow", name, skip),
+                    );
+                }
+            }
+        }
+        let mut hooks: Vec<&String> = self.on.keys().collect();
+        hooks.sort();
+        for event in hooks {
+            if !EVENT_TYPES.contains(&event.as_str()) {
+                error(format!("$.on.{}", event), format!("hook '{}' does not name an event type", event));
+            }
+        }
+
+        let mut warn = |path: String, message: String| lints.push(Lint { severity: Severity::Warning, path, message });
+        for (i, step) in self.workflow.iter().enumerate() {
+            let path = format!("$.workflow[{}]", i);
+            if !step.is_sub_workflow() && !step.vars.is_empty() {
+                warn(format!("{}.vars", path), format!("step '{}' has vars but no workflow — they are ignored.", step.name));
+            }
+            if step.is_gate() && (step.verify.is_some() || step.on_fail.is_some() || step.timeout.is_some()) {
+                warn(path.clone(), format!(
+                    "step '{}' has verify/on_fail/timeout but no run command — it will be treated as a gate step.",
+                    step.name
+                ));
+            }
             if step.in_viewport {
                 if step.verify.is_none() {
-                    eprintln!(
-                        "Warning: step '{}' (in_viewport) has no verify — `pawl done` will assume success unconditionally.",
+                    warn(path.clone(), format!(
+                        "step '{}' (in_viewport) has no verify — `pawl done` will assume success unconditionally.",
                         step.name
-                    );
+                    ));
                 }
                 if step.verify.is_some() && step.on_fail.is_none() {
-                    eprintln!(
-                        "Warning: step '{}' (in_viewport) has verify but no on_fail — verify failure is terminal.",
+                    warn(path.clone(), format!(
+                        "step '{}' (in_viewport) has verify but no on_fail — verify failure is terminal.",
                         step.name
-                    );
+                    ));
                 }
             }
+            for (j, sub) in step.parallel.iter().enumerate() {
+                if sub.verify.is_some() || sub.on_fail.is_some() || sub.when.is_some() || !sub.outputs.is_empty() {
+                    warn(format!("{}.parallel[{}]", path, j), format!(
+                        "sub-step '{}' of '{}' only uses run/timeout — set verify/on_fail on the group instead.",
+                        sub.name, step.name
+                    ));
+                }
+            }
+            if step.is_group() && step.fail_fast && step.joins_any() {
+                warn(format!("{}.fail_fast", path), format!(
+                    "parallel step '{}' has fail_fast with join \"any\" — it is ignored.",
+                    step.name
+                ));
+            }
+        }
+        for (i, step) in self.finally.iter().enumerate() {
+            if step.in_viewport || step.verify.is_some() || step.on_fail.is_some() || step.when.is_some() {
+                warn(format!("$.finally[{}]", i), format!(
+                    "finally step '{}' only uses run/timeout — in_viewport/verify/on_fail/when are ignored.",
+                    step.name
+                ));
+            }
         }
 
-        Ok(config)
+        lints
+    }
+
+    /// The part of the config a run is pinned to: everything but the task list
+    pub fn definition(&self) -> Config {
+        Config {
+            tasks: IndexMap::new(),
+            tasks_from: None,
+            ..self.clone()
+        }
+    }
+
+    /// Content hash of `definition()` (FNV-1a over its canonical JSON), naming its snapshot
+    pub fn definition_hash(&self) -> String {
+        // to_value sorts object keys, so `on` (a HashMap) hashes the same every time
+        let canonical = serde_json::to_value(self.definition())
+            .map(|v| v.to_string())
+            .unwrap_or_default();
+        let hash = canonical.bytes().fold(0xcbf29ce484222325u64, |h, b| {
+            (h ^ b as u64).wrapping_mul(0x100000001b3)
+        });
+        format!("{:016x}", hash)
+    }
+
+    /// Workflows this one runs as sub-workflows
+    pub fn sub_workflows(&self) -> impl Iterator<Item = &str> {
+        self.workflow.iter().filter_map(|s| s.workflow.as_deref())
+    }
+
+    /// Index of the step with this name
+    pub fn step_index(&self, name: &str) -> Option<usize> {
+        self.workflow.iter().position(|s| s.name == name)
+    }
+
+    /// Effective jump limit per run (default: 10)
+    pub fn effective_max_jumps(&self) -> usize {
+        self.max_jumps.unwrap_or(10)
+    }
+
+    /// Effective grace period before SIGKILL (default: 5s)
+    pub fn effective_grace_period(&self) -> Duration {
+        Duration::from_secs(self.grace_period.unwrap_or(5))
     }
 
     /// Get session name, defaulting to directory name
@@ -138,3 +719,209 @@ impl Config {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step(extra: &str) -> Step {
+        serde_json::from_str(&format!(r#"{{"name":"s","run":"true"{}}}"#, extra)).unwrap()
+    }
+
+    #[test]
+    fn test_retry_delay_defaults_to_immediate() {
+        assert_eq!(step("").retry_delay_for(3, 0.5), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_retry_delay_backoff_and_cap() {
+        let s = step(r#","retry_delay":1,"retry_max_delay":5"#);
+        assert_eq!(s.retry_delay_for(0, 0.0), Duration::from_secs(1));
+        assert_eq!(s.retry_delay_for(1, 0.0), Duration::from_secs(2));
+        assert_eq!(s.retry_delay_for(2, 0.0), Duration::from_secs(4));
+        assert_eq!(s.retry_delay_for(3, 0.0), Duration::from_secs(5));
+        assert_eq!(s.retry_delay_for(500, 0.0), Duration::from_secs(5));
+
+        let constant = step(r#","retry_delay":3,"retry_backoff":1"#);
+        assert_eq!(constant.retry_delay_for(4, 0.0), Duration::from_secs(3));
+    }
+
+    #[test]
+    fn test_retry_delay_jitter_bounds() {
+        let s = step(r#","retry_delay":10,"retry_backoff":1,"retry_jitter":0.5"#);
+        assert_eq!(s.retry_delay_for(0, 0.0), Duration::from_secs(5));
+        assert_eq!(s.retry_delay_for(0, 0.5), Duration::from_secs(10));
+        assert!(s.retry_delay_for(0, 0.999) < Duration::from_secs(15));
+    }
+
+    #[test]
+    fn test_on_fail_forms() {
+        let retry = step(r#","on_fail":"retry""#);
+        assert_eq!(retry.on_fail_strategy(), Some("retry"));
+
+        let goto = step(r#","on_fail":{"goto":"fix"},"on_success":{"goto":"s"}"#);
+        assert_eq!(goto.on_fail, Some(OnFail::Goto(Goto { goto: "fix".to_string() })));
+        assert_eq!(goto.on_fail_strategy(), None);
+        assert_eq!(goto.on_success.map(|g| g.goto).as_deref(), Some("s"));
+    }
+
+    #[test]
+    fn test_depends_forms() {
+        let config = Config::from_str(r#"{"tasks":{"t":{"depends":[
+            "a", {"task":"b"}, {"task":"c","on":"failed"}, {"task":"d","on":"finished"}
+        ]}},"workflow":[]}"#).unwrap();
+        let deps = &config.tasks["t"].depends;
+        let ons: Vec<_> = deps.iter().map(|d| (d.task(), d.on())).collect();
+        assert_eq!(ons, [
+            ("a", DependsOn::Completed), ("b", DependsOn::Completed),
+            ("c", DependsOn::Failed), ("d", DependsOn::Finished),
+        ]);
+        assert_eq!(deps[2].to_string(), "c (on failed)");
+        assert_eq!(serde_json::to_string(&deps[0]).unwrap(), r#""a""#);
+
+        let bad = r#"{"tasks":{"t":{"depends":[{"task":"a","on":"stopped"}]}},"workflow":[]}"#;
+        assert!(Config::from_str(bad).is_err());
+    }
+
+    #[test]
+    fn test_goto_must_name_a_step() {
+        let ok = r#"{"workflow":[{"name":"test","run":"true","on_fail":{"goto":"fix"}},{"name":"fix","run":"true","on_success":{"goto":"test"}}]}"#;
+        assert!(Config::from_str(ok).is_ok());
+        let bad = r#"{"workflow":[{"name":"test","run":"true","on_fail":{"goto":"nope"}}]}"#;
+        assert!(Config::from_str(bad).is_err());
+    }
+
+    #[test]
+    fn test_finally_needs_run() {
+        let ok = r#"{"workflow":[{"name":"a","run":"true"}],"finally":[{"name":"cleanup","run":"rm -rf tmp"}]}"#;
+        assert_eq!(Config::from_str(ok).unwrap().finally.len(), 1);
+        let bad = r#"{"workflow":[{"name":"a","run":"true"}],"finally":[{"name":"cleanup"}]}"#;
+        assert!(Config::from_str(bad).is_err());
+    }
+
+    #[test]
+    fn test_parallel_group() {
+        let ok = r#"{"workflow":[{"name":"checks","join":"any","parallel":[
+            {"name":"lint","run":"make lint"},{"name":"test","run":"make test","timeout":60}]}]}"#;
+        let config = Config::from_str(ok).unwrap();
+        let group = &config.workflow[0];
+        assert!(group.is_group() && !group.is_gate() && group.joins_any());
+        assert_eq!(group.parallel[1].timeout, Some(60));
+
+        for bad in [
+            r#"{"workflow":[{"name":"g","run":"true","parallel":[{"name":"a","run":"true"}]}]}"#,
+            r#"{"workflow":[{"name":"g","join":"most","parallel":[{"name":"a","run":"true"}]}]}"#,
+            r#"{"workflow":[{"name":"g","parallel":[{"name":"a"}]}]}"#,
+            r#"{"workflow":[{"name":"g","parallel":[{"name":"a","run":"true"},{"name":"a","run":"true"}]}]}"#,
+        ] {
+            assert!(Config::from_str(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_sub_workflow_step() {
+        let ok = r#"{"workflow":[{"name":"build","workflow":"build","vars":{"dir":"${task}"}},{"name":"ship","run":"true"}]}"#;
+        let config = Config::from_str(ok).unwrap();
+        assert!(config.workflow[0].is_sub_workflow());
+        assert!(!config.workflow[0].is_gate());
+        assert_eq!(config.sub_workflows().collect::<Vec<_>>(), vec!["build"]);
+        let bad = r#"{"workflow":[{"name":"build","workflow":"build","run":"make"}]}"#;
+        assert!(Config::from_str(bad).is_err());
+    }
+
+    #[test]
+    fn test_foreach_forms() {
+        let list = step(r#","foreach":["linux","mac"]"#);
+        assert_eq!(list.foreach, Some(Foreach::List(vec!["linux".to_string(), "mac".to_string()])));
+        assert!(list.is_foreach());
+
+        let ok = r#"{"workflow":[{"name":"pkg","run":"make -C ${item}","foreach":{"command":"ls packages"}}]}"#;
+        assert!(Config::from_str(ok).is_ok());
+        let both = r#"{"workflow":[{"name":"pkg","run":"true","foreach":{"var":"a","command":"ls"}}]}"#;
+        assert!(Config::from_str(both).is_err());
+        let no_run = r#"{"workflow":[{"name":"pkg","foreach":["a"]}]}"#;
+        assert!(Config::from_str(no_run).is_err());
+    }
+
+    #[test]
+    fn test_task_matrix() {
+        let config = Config::from_str(r#"{"tasks":{
+            "lib": {},
+            "svc-*": {"matrix":{"svc":["auth","billing"],"env":["dev","prod"]},
+                      "depends":["lib",{"task":"db-${env}","on":"failed"}],"skip":["deploy-${env}"],"vars":{"dir":"services/${svc}"}}
+        },"workflow":[{"name":"build","run":"true"}]}"#).unwrap();
+        let names: Vec<_> = config.tasks.keys().map(String::as_str).collect();
+        assert_eq!(names, ["lib", "svc-auth-dev", "svc-auth-prod", "svc-billing-dev", "svc-billing-prod"]);
+        let tc = &config.tasks["svc-billing-prod"];
+        assert_eq!(tc.depends, [
+            Dependency::Task("lib".to_string()),
+            Dependency::On { task: "db-prod".to_string(), on: DependsOn::Failed },
+        ]);
+        assert_eq!(tc.skip, ["deploy-prod"]);
+        assert_eq!(tc.vars.keys().collect::<Vec<_>>(), ["svc", "env", "dir"]);
+        assert_eq!(tc.vars["svc"], "billing");
+
+        for bad in [
+            r#"{"tasks":{"svc-*":{}},"workflow":[]}"#,
+            r#"{"tasks":{"svc":{"matrix":{"svc":["a"]}}},"workflow":[]}"#,
+            r#"{"tasks":{"svc-a":{},"svc-*":{"matrix":{"svc":["a"]}}},"workflow":[]}"#,
+        ] {
+            assert!(Config::from_str(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_definition_hash() {
+        let base = r#"{"tasks":{"a":{}},"on":{"step_finished":"x","task_started":"y"},"workflow":[{"name":"build","run":"make"}]}"#;
+        let hash = Config::from_str(base).unwrap().definition_hash();
+        assert_eq!(hash.len(), 16);
+        assert_eq!(Config::from_str(base).unwrap().definition_hash(), hash);
+
+        let other_tasks = base.replace(r#""a":{}"#, r#""b":{}"#);
+        assert_eq!(Config::from_str(&other_tasks).unwrap().definition_hash(), hash);
+        let other_steps = base.replace("make", "make all");
+        assert_ne!(Config::from_str(&other_steps).unwrap().definition_hash(), hash);
+    }
+
+    #[test]
+    fn test_retries_exit_code() {
+        let any = step("");
+        assert!(any.retries_exit_code(1));
+        assert!(any.retries_exit_code(124));
+
+        let only = step(r#","retry_on_exit_codes":[75,124]"#);
+        assert!(only.retries_exit_code(75));
+        assert!(!only.retries_exit_code(1));
+        // Verify failures keep retrying regardless of filters
+        assert!(only.retries_exit_code(0));
+
+        let except = step(r#","no_retry_on_exit_codes":[2]"#);
+        assert!(except.retries_exit_code(1));
+        assert!(!except.retries_exit_code(2));
+    }
+
+    #[test]
+    fn test_lint() {
+        let config = Config::from_str(r#"{
+            "tasks": {"a": {"skip": ["build", "ghost"]}},
+            "on": {"step_finished": "true", "step_done": "true"},
+            "workflow": [
+                {"name": "build", "run": "make", "on_fail": "retyr"},
+                {"name": "build", "run": "make test"},
+                {"name": "review", "in_viewport": true, "run": "claude"}
+            ]
+        }"#).unwrap();
+        let lints = config.lint();
+        let paths = |severity: Severity| -> Vec<&str> {
+            lints.iter().filter(|l| l.severity == severity).map(|l| l.path.as_str()).collect()
+        };
+        assert_eq!(
+            paths(Severity::Error),
+            ["$.workflow[0].on_fail", "$.workflow[1].name", "$.tasks.a.skip[1]", "$.on.step_done"]
+        );
+        assert_eq!(paths(Severity::Warning), ["$.workflow[2]"]);
+
+        let clean = Config::from_str(r#"{"workflow": [{"name": "build", "run": "make"}]}"#).unwrap();
+        assert!(clean.lint().is_empty());
+    }
+}
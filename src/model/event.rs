@@ -1,6 +1,7 @@
 use chrono::{DateTime, Utc};
+use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use super::state::{StepStatus, TaskState, TaskStatus};
 
@@ -11,6 +12,27 @@ pub enum Event {
         ts: DateTime<Utc>,
         run_id: String,
         workflow: String,
+        /// The parent run whose sub-workflow step started this (child) run
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        parent: Option<ParentRun>,
+        /// Variables given to this run (shadow workflow and task vars)
+        #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+        vars: IndexMap<String, String>,
+        /// Content hash of the workflow definition the run is pinned to (`.pawl/snapshots/<hash>.json`)
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        workflow_hash: Option<String>,
+    },
+    /// A synchronous step began executing, owned by the recorded process.
+    StepStarted {
+        ts: DateTime<Utc>,
+        step: usize,
+        pid: u32,
+        hostname: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        boot_id: Option<String>,
+        /// Process group of the step command (signalled by stop/reset)
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pgid: Option<u32>,
     },
     StepFinished {
         ts: DateTime<Utc>,
@@ -25,6 +47,54 @@ pub enum Event {
         stderr: Option<String>,
         #[serde(skip_serializing_if = "Option::is_none")]
         verify_output: Option<String>,
+        /// The run or verify command hit its timeout and its process group was killed
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        timed_out: bool,
+        /// Named values captured by the step's `outputs`
+        #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+        outputs: IndexMap<String, String>,
+    },
+    /// A foreach step resolved its items; `sub` in its sub-step events indexes this list.
+    StepExpanded {
+        ts: DateTime<Utc>,
+        step: usize,
+        items: Vec<String>,
+    },
+    /// A sub-step of a parallel group (or a foreach item) began executing (`sub` indexes the group's `parallel` list).
+    SubStepStarted {
+        ts: DateTime<Utc>,
+        step: usize,
+        sub: usize,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pgid: Option<u32>,
+    },
+    /// A sub-step of a parallel group (or a foreach item) exited, or was cancelled once the group was decided.
+    SubStepFinished {
+        ts: DateTime<Utc>,
+        step: usize,
+        sub: usize,
+        success: bool,
+        exit_code: i32,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        duration: Option<f64>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        stdout: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        stderr: Option<String>,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        timed_out: bool,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        cancelled: bool,
+        /// Backoff for a failed foreach item: its next run must not start before this time
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        retry_at: Option<DateTime<Utc>>,
+    },
+    /// A sub-workflow step started its child run (`child` is the child's task name).
+    SubWorkflowStarted {
+        ts: DateTime<Utc>,
+        step: usize,
+        child: String,
+        run_id: String,
     },
     StepYielded {
         ts: DateTime<Utc>,
@@ -37,6 +107,18 @@ pub enum Event {
         #[serde(skip_serializing_if = "Option::is_none")]
         message: Option<String>,
     },
+    /// The steps a blocked step waits for (`wait_for`) succeeded; it runs now.
+    StepUnblocked {
+        ts: DateTime<Utc>,
+        step: usize,
+    },
+    /// A reviewer declined a waiting step (`pawl reject`); replays like a verify failure.
+    StepRejected {
+        ts: DateTime<Utc>,
+        step: usize,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        message: Option<String>,
+    },
     ViewportLaunched {
         ts: DateTime<Utc>,
         step: usize,
@@ -44,15 +126,36 @@ pub enum Event {
     StepSkipped {
         ts: DateTime<Utc>,
         step: usize,
+        /// Why the step was skipped (task skip list or a false `when`)
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reason: Option<String>,
+    },
+    /// Control moved from `step` to step `to` via its on_fail / on_success goto.
+    StepJumped {
+        ts: DateTime<Utc>,
+        step: usize,
+        to: usize,
+        /// "on_fail" or "on_success"
+        reason: String,
     },
     StepReset {
         ts: DateTime<Utc>,
         step: usize,
         auto: bool,
+        /// Backoff: the step must not re-run before this time
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        retry_at: Option<DateTime<Utc>>,
+        /// Re-run after a crash: not an attempt of its own, so retry counting
+        /// and feedback look past it
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        resumed: bool,
     },
     TaskStopped {
         ts: DateTime<Utc>,
         step: usize,
+        /// How the step's process group was stopped: "not_running", "terminated" or "killed"
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        kill: Option<String>,
     },
     TaskReset {
         ts: DateTime<Utc>,
@@ -61,8 +164,101 @@ pub enum Event {
         ts: DateTime<Utc>,
         step: usize,
     },
+    /// The owner of a running synchronous step died before recording its result.
+    StepInterrupted {
+        ts: DateTime<Utc>,
+        step: usize,
+    },
+    /// Compensation of the run's successful steps began ("failed" or "manual").
+    RollbackStarted {
+        ts: DateTime<Utc>,
+        reason: String,
+    },
+    /// A step's `compensate` command ran during a rollback.
+    StepCompensated {
+        ts: DateTime<Utc>,
+        step: usize,
+        success: bool,
+        exit_code: i32,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        duration: Option<f64>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        stderr: Option<String>,
+    },
+    /// Every successful step with a `compensate` command has been compensated.
+    RollbackFinished {
+        ts: DateTime<Utc>,
+    },
+    /// The workflow ended with `status`; its `finally` steps run next.
+    /// Finally events never change the replayed task state.
+    FinallyStarted {
+        ts: DateTime<Utc>,
+        status: String,
+    },
+    /// A `finally` step ran (`step` indexes the finally list, not the workflow).
+    FinallyFinished {
+        ts: DateTime<Utc>,
+        step: usize,
+        success: bool,
+        exit_code: i32,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        duration: Option<f64>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        stderr: Option<String>,
+    },
+    /// The run moved onto another workflow definition (`pawl migrate`). `steps` maps each
+    /// old step index to its new one (None = removed); earlier events are read through it.
+    WorkflowMigrated {
+        ts: DateTime<Utc>,
+        workflow_hash: String,
+        /// The run's current step in the new definition
+        step: usize,
+        steps: Vec<Option<usize>>,
+    },
 }
 
+/// Every event type (serde tag), i.e. the valid keys of a workflow's `on` hooks
+pub const EVENT_TYPES: &[&str] = &[
+    "task_started", "step_started", "step_finished", "step_expanded", "sub_step_started",
+    "sub_step_finished", "sub_workflow_started", "step_yielded", "step_resumed", "step_unblocked",
+    "step_rejected", "step_skipped", "step_jumped", "step_reset", "step_interrupted", "viewport_launched",
+    "viewport_lost", "task_stopped", "task_reset", "rollback_started", "step_compensated",
+    "rollback_finished", "finally_started", "finally_finished", "workflow_migrated",
+];
+
+/// Every variable `Event::extra_vars` can give a hook, on top of the step context
+pub const HOOK_VARS: &[&str] = &[
+    "auto", "cancelled", "child", "child_run_id", "duration", "exit_code", "finally_index",
+    "hostname", "item_count", "kill", "message", "pid", "reason", "resumed", "retry_at", "run_id",
+    "status", "sub", "success", "timed_out", "to", "workflow", "workflow_hash",
+];
+
+/// Link from a child run to the sub-workflow step that started it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ParentRun {
+    pub task: String,
+    pub run_id: String,
+    pub step: usize,
+}
+
+/// TaskState message for a run whose step owner died mid-step.
+pub const INTERRUPTED: &str = "interrupted";
+
+/// TaskState message for a step that failed because it hit its timeout.
+pub const TIMED_OUT: &str = "timed_out";
+
+/// TaskState message for a step a reviewer rejected.
+pub const REJECTED: &str = "rejected";
+
+/// Yield reason (TaskState message) for a step waiting on other tasks' steps (`wait_for`).
+pub const BLOCKED: &str = "blocked";
+
+/// TaskState messages for a rollback in progress (or crashed mid-way), stuck on a
+/// failed compensation, and finished.
+pub const ROLLING_BACK: &str = "rolling_back";
+pub const ROLLBACK_FAILED: &str = "rollback_failed";
+pub const ROLLED_BACK: &str = "rolled_back";
+
 pub fn event_timestamp() -> DateTime<Utc> {
     Utc::now()
 }
@@ -72,15 +268,30 @@ impl Event {
     pub fn ts(&self) -> DateTime<Utc> {
         match self {
             Event::TaskStarted { ts, .. }
+            | Event::StepStarted { ts, .. }
             | Event::StepFinished { ts, .. }
+            | Event::StepExpanded { ts, .. }
+            | Event::SubStepStarted { ts, .. }
+            | Event::SubStepFinished { ts, .. }
+            | Event::SubWorkflowStarted { ts, .. }
             | Event::StepYielded { ts, .. }
             | Event::StepResumed { ts, .. }
+            | Event::StepUnblocked { ts, .. }
+            | Event::StepRejected { ts, .. }
             | Event::ViewportLaunched { ts, .. }
             | Event::StepSkipped { ts, .. }
+            | Event::StepJumped { ts, .. }
             | Event::StepReset { ts, .. }
             | Event::TaskStopped { ts, .. }
             | Event::TaskReset { ts, .. }
-            | Event::ViewportLost { ts, .. } => *ts,
+            | Event::ViewportLost { ts, .. }
+            | Event::StepInterrupted { ts, .. }
+            | Event::RollbackStarted { ts, .. }
+            | Event::StepCompensated { ts, .. }
+            | Event::RollbackFinished { ts, .. }
+            | Event::FinallyStarted { ts, .. }
+            | Event::FinallyFinished { ts, .. }
+            | Event::WorkflowMigrated { ts, .. } => *ts,
         }
     }
 
@@ -88,30 +299,97 @@ impl Event {
     pub fn type_name(&self) -> &'static str {
         match self {
             Event::TaskStarted { .. } => "task_started",
+            Event::StepStarted { .. } => "step_started",
             Event::StepFinished { .. } => "step_finished",
+            Event::StepExpanded { .. } => "step_expanded",
+            Event::SubStepStarted { .. } => "sub_step_started",
+            Event::SubStepFinished { .. } => "sub_step_finished",
+            Event::SubWorkflowStarted { .. } => "sub_workflow_started",
             Event::StepYielded { .. } => "step_yielded",
             Event::StepResumed { .. } => "step_resumed",
+            Event::StepUnblocked { .. } => "step_unblocked",
+            Event::StepRejected { .. } => "step_rejected",
             Event::ViewportLaunched { .. } => "viewport_launched",
             Event::StepSkipped { .. } => "step_skipped",
+            Event::StepJumped { .. } => "step_jumped",
             Event::StepReset { .. } => "step_reset",
             Event::TaskStopped { .. } => "task_stopped",
             Event::TaskReset { .. } => "task_reset",
             Event::ViewportLost { .. } => "viewport_lost",
+            Event::StepInterrupted { .. } => "step_interrupted",
+            Event::RollbackStarted { .. } => "rollback_started",
+            Event::StepCompensated { .. } => "step_compensated",
+            Event::RollbackFinished { .. } => "rollback_finished",
+            Event::FinallyStarted { .. } => "finally_started",
+            Event::FinallyFinished { .. } => "finally_finished",
+            Event::WorkflowMigrated { .. } => "workflow_migrated",
         }
     }
 
     /// Returns the step index associated with this event, if any
     pub fn step_index(&self) -> Option<usize> {
         match self {
-            Event::TaskStarted { .. } | Event::TaskReset { .. } => None,
-            Event::StepFinished { step, .. }
+            Event::TaskStarted { .. }
+            | Event::TaskReset { .. }
+            | Event::RollbackStarted { .. }
+            | Event::RollbackFinished { .. }
+            | Event::FinallyStarted { .. }
+            | Event::FinallyFinished { .. } => None,
+            Event::StepStarted { step, .. }
+            | Event::StepFinished { step, .. }
+            | Event::StepExpanded { step, .. }
+            | Event::SubStepStarted { step, .. }
+            | Event::SubStepFinished { step, .. }
+            | Event::SubWorkflowStarted { step, .. }
             | Event::StepYielded { step, .. }
             | Event::StepResumed { step, .. }
+            | Event::StepUnblocked { step, .. }
+            | Event::StepRejected { step, .. }
             | Event::ViewportLaunched { step, .. }
             | Event::StepSkipped { step, .. }
+            | Event::StepJumped { step, .. }
             | Event::StepReset { step, .. }
             | Event::TaskStopped { step, .. }
-            | Event::ViewportLost { step, .. } => Some(*step),
+            | Event::ViewportLost { step, .. }
+            | Event::StepInterrupted { step, .. }
+            | Event::StepCompensated { step, .. }
+            | Event::WorkflowMigrated { step, .. } => Some(*step),
+        }
+    }
+
+    /// Rewrite this event's workflow step indices through a migration's `steps` map.
+    /// Returns false if the event belongs to a step the new definition no longer has.
+    fn remap_steps(&mut self, steps: &[Option<usize>]) -> bool {
+        let remap = |idx: &mut usize| match steps.get(*idx) {
+            Some(Some(new)) => {
+                *idx = *new;
+                true
+            }
+            Some(None) => false,
+            None => true,
+        };
+        match self {
+            Event::StepJumped { step, to, .. } => remap(step) && remap(to),
+            Event::StepStarted { step, .. }
+            | Event::StepFinished { step, .. }
+            | Event::StepExpanded { step, .. }
+            | Event::SubStepStarted { step, .. }
+            | Event::SubStepFinished { step, .. }
+            | Event::SubWorkflowStarted { step, .. }
+            | Event::StepYielded { step, .. }
+            | Event::StepResumed { step, .. }
+            | Event::StepUnblocked { step, .. }
+            | Event::StepRejected { step, .. }
+            | Event::ViewportLaunched { step, .. }
+            | Event::StepSkipped { step, .. }
+            | Event::StepReset { step, .. }
+            | Event::TaskStopped { step, .. }
+            | Event::ViewportLost { step, .. }
+            | Event::StepInterrupted { step, .. }
+            | Event::StepCompensated { step, .. }
+            | Event::WorkflowMigrated { step, .. } => remap(step),
+            // Finally events index the finally list, which a migration leaves alone
+            _ => true,
         }
     }
 
@@ -119,25 +397,82 @@ impl Event {
     pub fn extra_vars(&self) -> HashMap<String, String> {
         let mut vars = HashMap::new();
         match self {
-            Event::StepFinished { success, exit_code, duration, .. } => {
+            Event::StepFinished { success, exit_code, duration, timed_out, .. } => {
+                vars.insert("success".to_string(), success.to_string());
+                vars.insert("exit_code".to_string(), exit_code.to_string());
+                vars.insert("timed_out".to_string(), timed_out.to_string());
+                if let Some(d) = duration {
+                    vars.insert("duration".to_string(), format!("{:.1}", d));
+                }
+            }
+            Event::StepExpanded { items, .. } => {
+                vars.insert("item_count".to_string(), items.len().to_string());
+            }
+            Event::SubStepStarted { sub, .. } => {
+                vars.insert("sub".to_string(), sub.to_string());
+            }
+            Event::SubStepFinished { sub, success, exit_code, duration, timed_out, cancelled, retry_at, .. } => {
+                vars.insert("sub".to_string(), sub.to_string());
                 vars.insert("success".to_string(), success.to_string());
                 vars.insert("exit_code".to_string(), exit_code.to_string());
+                vars.insert("timed_out".to_string(), timed_out.to_string());
+                vars.insert("cancelled".to_string(), cancelled.to_string());
                 if let Some(d) = duration {
                     vars.insert("duration".to_string(), format!("{:.1}", d));
                 }
+                if let Some(at) = retry_at {
+                    vars.insert("retry_at".to_string(), at.to_rfc3339());
+                }
             }
             Event::TaskStarted { run_id, workflow, .. } => {
                 vars.insert("run_id".to_string(), run_id.clone());
                 vars.insert("workflow".to_string(), workflow.clone());
             }
-            Event::StepYielded { reason, .. } => {
+            Event::StepStarted { pid, hostname, .. } => {
+                vars.insert("pid".to_string(), pid.to_string());
+                vars.insert("hostname".to_string(), hostname.clone());
+            }
+            Event::SubWorkflowStarted { child, run_id, .. } => {
+                vars.insert("child".to_string(), child.clone());
+                vars.insert("child_run_id".to_string(), run_id.clone());
+            }
+            Event::StepYielded { reason, .. }
+            | Event::StepSkipped { reason: Some(reason), .. }
+            | Event::RollbackStarted { reason, .. } => {
                 vars.insert("reason".to_string(), reason.clone());
             }
-            Event::StepResumed { message: Some(msg), .. } => {
+            Event::StepResumed { message: Some(msg), .. }
+            | Event::StepRejected { message: Some(msg), .. } => {
                 vars.insert("message".to_string(), msg.clone());
             }
-            Event::StepReset { auto, .. } => {
+            Event::StepJumped { to, reason, .. } => {
+                vars.insert("to".to_string(), to.to_string());
+                vars.insert("reason".to_string(), reason.clone());
+            }
+            Event::StepReset { auto, retry_at, resumed, .. } => {
                 vars.insert("auto".to_string(), auto.to_string());
+                vars.insert("resumed".to_string(), resumed.to_string());
+                if let Some(at) = retry_at {
+                    vars.insert("retry_at".to_string(), at.to_rfc3339());
+                }
+            }
+            Event::TaskStopped { kill: Some(kill), .. } => {
+                vars.insert("kill".to_string(), kill.clone());
+            }
+            Event::FinallyStarted { status, .. } => {
+                vars.insert("status".to_string(), status.clone());
+            }
+            Event::StepCompensated { success, exit_code, .. } => {
+                vars.insert("success".to_string(), success.to_string());
+                vars.insert("exit_code".to_string(), exit_code.to_string());
+            }
+            Event::FinallyFinished { step, success, exit_code, .. } => {
+                vars.insert("finally_index".to_string(), step.to_string());
+                vars.insert("success".to_string(), success.to_string());
+                vars.insert("exit_code".to_string(), exit_code.to_string());
+            }
+            Event::WorkflowMigrated { workflow_hash, .. } => {
+                vars.insert("workflow_hash".to_string(), workflow_hash.clone());
             }
             _ => {}
         }
@@ -148,8 +483,25 @@ impl Event {
 /// Replay events to reconstruct TaskState.
 /// Returns None if no TaskStarted event found (after last reset).
 pub fn replay(events: &[Event], workflow_len: usize) -> Option<TaskState> {
-    let mut state: Option<TaskState> = None;
+    replay_from(None, events, workflow_len)
+}
+
+/// Replay events on top of the state earlier events left (see `apply_events`)
+pub fn replay_from(state: Option<TaskState>, events: &[Event], workflow_len: usize) -> Option<TaskState> {
+    let mut state = apply_events(state, events);
+
+    // Auto-derive Completed when all steps done
+    if let Some(s) = state.as_mut()
+        && s.current_step >= workflow_len && s.status == TaskStatus::Running {
+            s.status = TaskStatus::Completed;
+        }
+
+    state
+}
 
+/// Fold events into a state, without deriving Completed (that depends on the
+/// workflow length at replay time, so checkpoints store the state before it)
+pub fn apply_events(mut state: Option<TaskState>, events: &[Event]) -> Option<TaskState> {
     for event in events {
         match event {
             Event::TaskStarted { ts, run_id, .. } => {
@@ -159,6 +511,7 @@ pub fn replay(events: &[Event], workflow_len: usize) -> Option<TaskState> {
                     started_at: Some(*ts),
                     updated_at: Some(*ts),
                     step_status: HashMap::new(),
+                    sub_step_status: HashMap::new(),
                     message: None,
                     run_id: run_id.clone(),
                 });
@@ -166,10 +519,16 @@ pub fn replay(events: &[Event], workflow_len: usize) -> Option<TaskState> {
             Event::TaskReset { .. } => {
                 state = None;
             }
+            Event::StepStarted { ts, .. } => {
+                let Some(s) = state.as_mut() else { continue };
+                s.updated_at = Some(*ts);
+                s.status = TaskStatus::Running;
+            }
             Event::StepFinished {
                 ts,
                 step,
                 success,
+                timed_out,
                 ..
             } => {
                 let Some(s) = state.as_mut() else { continue };
@@ -182,9 +541,35 @@ pub fn replay(events: &[Event], workflow_len: usize) -> Option<TaskState> {
                 } else {
                     s.step_status.insert(*step, StepStatus::Failed);
                     s.status = TaskStatus::Failed;
-                    s.message = None;
+                    s.message = timed_out.then(|| TIMED_OUT.to_string());
+                }
+            }
+            Event::StepExpanded { ts, step, .. } => {
+                let Some(s) = state.as_mut() else { continue };
+                s.updated_at = Some(*ts);
+                s.status = TaskStatus::Running;
+                // A new item list: earlier item results no longer apply
+                s.sub_step_status.remove(step);
+            }
+            Event::SubStepStarted { ts, step, sub, .. } => {
+                let Some(s) = state.as_mut() else { continue };
+                s.updated_at = Some(*ts);
+                s.status = TaskStatus::Running;
+                // Re-running: the previous result no longer stands
+                if let Some(subs) = s.sub_step_status.get_mut(step) {
+                    subs.remove(sub);
                 }
             }
+            Event::SubStepFinished { ts, step, sub, success, cancelled, .. } => {
+                let Some(s) = state.as_mut() else { continue };
+                s.updated_at = Some(*ts);
+                let status = match (success, cancelled) {
+                    (true, _) => StepStatus::Success,
+                    (false, true) => StepStatus::Cancelled,
+                    (false, false) => StepStatus::Failed,
+                };
+                s.sub_step_status.entry(*step).or_default().insert(*sub, status);
+            }
             Event::StepYielded { ts, step, reason } => {
                 let Some(s) = state.as_mut() else { continue };
                 s.updated_at = Some(*ts);
@@ -199,18 +584,49 @@ pub fn replay(events: &[Event], workflow_len: usize) -> Option<TaskState> {
                 s.current_step = step + 1;
                 s.status = TaskStatus::Running;
             }
-            Event::ViewportLaunched { ts, .. } => {
+            Event::StepUnblocked { ts, step } => {
+                let Some(s) = state.as_mut() else { continue };
+                s.updated_at = Some(*ts);
+                s.current_step = *step;
+                s.status = TaskStatus::Running;
+                s.message = None;
+            }
+            Event::StepRejected { ts, step, .. } => {
+                let Some(s) = state.as_mut() else { continue };
+                s.updated_at = Some(*ts);
+                s.current_step = *step;
+                s.step_status.insert(*step, StepStatus::Failed);
+                s.status = TaskStatus::Failed;
+                s.message = Some(REJECTED.to_string());
+            }
+            Event::ViewportLaunched { ts, .. } | Event::SubWorkflowStarted { ts, .. } => {
                 let Some(s) = state.as_mut() else { continue };
                 s.updated_at = Some(*ts);
                 s.status = TaskStatus::Running;
             }
-            Event::StepSkipped { ts, step } => {
+            Event::StepSkipped { ts, step, .. } => {
                 let Some(s) = state.as_mut() else { continue };
                 s.updated_at = Some(*ts);
                 s.step_status.insert(*step, StepStatus::Skipped);
                 s.current_step = step + 1;
                 s.status = TaskStatus::Running;
             }
+            Event::StepJumped { ts, step, to, .. } => {
+                let Some(s) = state.as_mut() else { continue };
+                s.updated_at = Some(*ts);
+                if to <= step {
+                    // Backward: the target and everything after it runs again
+                    s.step_status.retain(|i, _| i < to);
+                    s.sub_step_status.retain(|i, _| i < to);
+                } else {
+                    for i in step + 1..*to {
+                        s.step_status.insert(i, StepStatus::Skipped);
+                    }
+                }
+                s.current_step = *to;
+                s.status = TaskStatus::Running;
+                s.message = None;
+            }
             Event::StepReset { ts, step, .. } => {
                 let Some(s) = state.as_mut() else { continue };
                 s.updated_at = Some(*ts);
@@ -231,25 +647,345 @@ pub fn replay(events: &[Event], workflow_len: usize) -> Option<TaskState> {
                 s.status = TaskStatus::Failed;
                 s.message = Some("viewport lost".to_string());
             }
+            Event::StepInterrupted { ts, step } => {
+                let Some(s) = state.as_mut() else { continue };
+                s.updated_at = Some(*ts);
+                s.current_step = *step;
+                s.step_status.remove(step);
+                s.status = TaskStatus::Stopped;
+                s.message = Some(INTERRUPTED.to_string());
+            }
+            Event::RollbackStarted { ts, .. } => {
+                let Some(s) = state.as_mut() else { continue };
+                s.updated_at = Some(*ts);
+                s.status = TaskStatus::Failed;
+                s.message = Some(ROLLING_BACK.to_string());
+            }
+            Event::StepCompensated { ts, step, success, .. } => {
+                let Some(s) = state.as_mut() else { continue };
+                s.updated_at = Some(*ts);
+                if *success {
+                    s.step_status.insert(*step, StepStatus::Compensated);
+                    s.message = Some(ROLLING_BACK.to_string());
+                } else {
+                    s.message = Some(ROLLBACK_FAILED.to_string());
+                }
+            }
+            Event::RollbackFinished { ts } => {
+                let Some(s) = state.as_mut() else { continue };
+                s.updated_at = Some(*ts);
+                s.message = Some(ROLLED_BACK.to_string());
+            }
+            Event::FinallyStarted { .. } | Event::FinallyFinished { .. } => {}
+            Event::WorkflowMigrated { ts, step, .. } => {
+                let Some(s) = state.as_mut() else { continue };
+                s.updated_at = Some(*ts);
+                s.current_step = *step;
+            }
         }
     }
+    state
+}
 
-    // Auto-derive Completed when all steps done
-    if let Some(s) = state.as_mut()
-        && s.current_step >= workflow_len && s.status == TaskStatus::Running {
-            s.status = TaskStatus::Completed;
+/// Process recorded as driving a run (from StepStarted)
+#[derive(Debug, Clone, PartialEq)]
+pub struct StepOwner {
+    pub step: usize,
+    pub pid: u32,
+    pub hostname: String,
+    pub boot_id: Option<String>,
+    pub pgid: Option<u32>,
+}
+
+/// Find the process driving the run, if any: the most recent StepStarted,
+/// unless control was handed elsewhere afterwards (yield, viewport, child run, stop, resume).
+pub fn step_owner(events: &[Event]) -> Option<StepOwner> {
+    for event in events.iter().rev() {
+        match event {
+            Event::StepStarted { step, pid, hostname, boot_id, pgid, .. } => {
+                return Some(StepOwner {
+                    step: *step,
+                    pid: *pid,
+                    hostname: hostname.clone(),
+                    boot_id: boot_id.clone(),
+                    pgid: *pgid,
+                });
+            }
+            Event::TaskStarted { .. }
+            | Event::TaskReset { .. }
+            | Event::StepYielded { .. }
+            | Event::StepResumed { .. }
+            | Event::StepUnblocked { .. }
+            | Event::ViewportLaunched { .. }
+            | Event::SubWorkflowStarted { .. }
+            | Event::TaskStopped { .. }
+            | Event::StepInterrupted { .. } => return None,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Process groups of sub-steps (parallel branches, foreach items) the current owner
+/// started that have no `sub_step_finished` yet: left running if the owner dies.
+pub fn open_sub_step_groups(events: &[Event]) -> Vec<u32> {
+    let mut finished = HashSet::new();
+    let mut groups = Vec::new();
+    for event in events.iter().rev() {
+        match event {
+            Event::StepStarted { .. } | Event::TaskStarted { .. } | Event::TaskReset { .. } => break,
+            Event::SubStepFinished { step, sub, .. } => {
+                finished.insert((*step, *sub));
+            }
+            Event::SubStepStarted { step, sub, pgid: Some(pgid), .. } if !finished.contains(&(*step, *sub)) => {
+                groups.push(*pgid);
+            }
+            _ => {}
         }
+    }
+    groups
+}
 
-    state
+/// Outputs captured in the current run, by step index (latest attempt wins).
+pub fn step_outputs(events: &[Event]) -> IndexMap<usize, IndexMap<String, String>> {
+    let mut outputs = IndexMap::new();
+    for event in events {
+        match event {
+            Event::TaskStarted { .. } | Event::TaskReset { .. } => outputs.clear(),
+            Event::StepFinished { step, outputs: captured, .. } if !captured.is_empty() => {
+                outputs.insert(*step, captured.clone());
+            }
+            _ => {}
+        }
+    }
+    outputs
+}
+
+/// Whether the workflow's `finally` steps are still due: true unless they already
+/// started after the latest workflow event (each time the workflow ends, they run once).
+/// Rollback events don't count as the workflow ending again.
+pub fn finally_due(events: &[Event]) -> bool {
+    for event in events.iter().rev() {
+        match event {
+            Event::FinallyStarted { .. } => return false,
+            Event::FinallyFinished { .. }
+            | Event::WorkflowMigrated { .. }
+            | Event::RollbackStarted { .. }
+            | Event::StepCompensated { .. }
+            | Event::RollbackFinished { .. } => {}
+            _ => return true,
+        }
+    }
+    false
+}
+
+/// Results of the `finally` steps since the workflow last ended: index → success.
+pub fn finally_results(events: &[Event]) -> HashMap<usize, bool> {
+    let mut results = HashMap::new();
+    for event in events {
+        match event {
+            Event::FinallyStarted { .. } | Event::TaskStarted { .. } | Event::TaskReset { .. } => results.clear(),
+            Event::FinallyFinished { step, success, .. } => {
+                results.insert(*step, *success);
+            }
+            _ => {}
+        }
+    }
+    results
+}
+
+/// Count on_fail/on_success jumps in the current run (the max_jumps loop guard).
+pub fn count_jumps(events: &[Event]) -> usize {
+    let mut count = 0;
+    for event in events.iter().rev() {
+        match event {
+            Event::TaskStarted { .. } | Event::TaskReset { .. } => break,
+            Event::StepJumped { .. } => count += 1,
+            _ => {}
+        }
+    }
+    count
+}
+
+/// Sub-steps of a parallel group that already finished in the current attempt
+/// (since the group last settled or was reset by hand), by sub-step index → success.
+/// Cancelled ones don't count: a resumed group runs them again.
+pub fn sub_step_results(events: &[Event], step_idx: usize) -> HashMap<usize, bool> {
+    let mut results = HashMap::new();
+    for event in events.iter().rev() {
+        match event {
+            Event::TaskStarted { .. } | Event::TaskReset { .. } => break,
+            Event::StepFinished { step, .. } | Event::StepSkipped { step, .. } if *step == step_idx => break,
+            Event::StepReset { step, resumed: false, .. } if *step == step_idx => break,
+            Event::StepJumped { to, .. } if *to <= step_idx => break,
+            Event::SubStepFinished { step, sub, success, cancelled: false, .. } if *step == step_idx => {
+                results.entry(*sub).or_insert(*success);
+            }
+            _ => {}
+        }
+    }
+    results
+}
+
+/// Items a foreach step resolved in its current attempt (since it last settled or was
+/// reset by hand). A crash-resumed attempt keeps them, so the item indices recorded so
+/// far stay valid.
+pub fn foreach_items(events: &[Event], step_idx: usize) -> Option<Vec<String>> {
+    for event in events.iter().rev() {
+        match event {
+            Event::TaskStarted { .. } | Event::TaskReset { .. } => return None,
+            Event::StepFinished { step, .. } | Event::StepSkipped { step, .. } if *step == step_idx => return None,
+            Event::StepReset { step, resumed: false, .. } if *step == step_idx => return None,
+            Event::StepJumped { to, .. } if *to <= step_idx => return None,
+            Event::StepExpanded { step, items, .. } if *step == step_idx => return Some(items.clone()),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Exit codes of a foreach item's failed runs in the current attempt of its step, oldest
+/// first. Each failure is one auto-retry used from the item's own budget.
+pub fn item_failures(events: &[Event], step_idx: usize, item: usize) -> Vec<i32> {
+    let mut codes = Vec::new();
+    for event in events.iter().rev() {
+        match event {
+            Event::TaskStarted { .. } | Event::TaskReset { .. } => break,
+            Event::StepFinished { step, .. } | Event::StepSkipped { step, .. } if *step == step_idx => break,
+            Event::StepJumped { to, .. } if *to <= step_idx => break,
+            Event::StepReset { step, resumed: false, .. } if *step == step_idx => break,
+            Event::StepExpanded { step, .. } if *step == step_idx => break,
+            Event::SubStepFinished { step, sub, success: false, cancelled: false, exit_code, .. }
+                if *step == step_idx && *sub == item =>
+            {
+                codes.push(*exit_code);
+            }
+            _ => {}
+        }
+    }
+    codes.reverse();
+    codes
+}
+
+/// Scheduled backoff for a foreach item's next run, if that run hasn't started yet
+/// (the item's latest failure decides).
+pub fn item_retry_at(events: &[Event], step_idx: usize, item: usize) -> Option<DateTime<Utc>> {
+    for event in events.iter().rev() {
+        match event {
+            Event::TaskStarted { .. } | Event::TaskReset { .. } => return None,
+            Event::StepFinished { step, .. } | Event::StepSkipped { step, .. } if *step == step_idx => return None,
+            Event::StepJumped { to, .. } if *to <= step_idx => return None,
+            Event::StepReset { step, resumed: false, .. } if *step == step_idx => return None,
+            Event::StepExpanded { step, .. } if *step == step_idx => return None,
+            Event::SubStepStarted { step, sub, .. } if *step == step_idx && *sub == item => return None,
+            Event::SubStepFinished { step, sub, cancelled: false, retry_at, .. }
+                if *step == step_idx && *sub == item =>
+            {
+                return *retry_at;
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// The child run (task name, run_id) a sub-workflow step started in its current
+/// attempt (since the step last settled), if any.
+pub fn sub_workflow_run(events: &[Event], step_idx: usize) -> Option<(String, String)> {
+    for event in events.iter().rev() {
+        match event {
+            Event::TaskStarted { .. } | Event::TaskReset { .. } => return None,
+            Event::StepFinished { step, .. } | Event::StepSkipped { step, .. } if *step == step_idx => return None,
+            Event::StepJumped { to, .. } if *to <= step_idx => return None,
+            Event::SubWorkflowStarted { step, child, run_id, .. } if *step == step_idx => {
+                return Some((child.clone(), run_id.clone()));
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// The TaskStarted of the current run (None before the first start or after a reset)
+fn current_run(events: &[Event]) -> Option<&Event> {
+    for event in events.iter().rev() {
+        match event {
+            Event::TaskStarted { .. } => return Some(event),
+            Event::TaskReset { .. } => return None,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Hash of the workflow definition the current run is pinned to (inner None for runs
+/// recorded before pinning, which follow the live config, and after a reset).
+/// Outer None: the events hold no run start, reset or migration, so they don't decide it.
+pub fn pinned_hash(events: &[Event]) -> Option<Option<String>> {
+    for event in events.iter().rev() {
+        match event {
+            Event::WorkflowMigrated { workflow_hash, .. } => return Some(Some(workflow_hash.clone())),
+            Event::TaskStarted { workflow_hash, .. } => return Some(workflow_hash.clone()),
+            Event::TaskReset { .. } => return Some(None),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Read a log through its migrations: events before each `workflow_migrated` get their
+/// step indices rewritten to the new definition, and events of removed steps are dropped.
+pub fn apply_migrations(events: Vec<Event>) -> Vec<Event> {
+    let mut migrated: Vec<Event> = Vec::with_capacity(events.len());
+    for event in events {
+        if let Event::WorkflowMigrated { steps, .. } = &event {
+            migrated.retain_mut(|e| e.remap_steps(steps));
+        }
+        migrated.push(event);
+    }
+    migrated
+}
+
+/// The parent run of the current run, if it is a sub-workflow's child run
+pub fn run_parent(events: &[Event]) -> Option<ParentRun> {
+    match current_run(events) {
+        Some(Event::TaskStarted { parent, .. }) => parent.clone(),
+        _ => None,
+    }
+}
+
+/// Variables given to the current run when it started
+pub fn run_vars(events: &[Event]) -> IndexMap<String, String> {
+    match current_run(events) {
+        Some(Event::TaskStarted { vars, .. }) => vars.clone(),
+        _ => IndexMap::new(),
+    }
+}
+
+/// Scheduled backoff for the next attempt of a step, if it hasn't started yet.
+/// The latest StepReset for the step decides; an attempt that already began clears it.
+pub fn pending_retry_at(events: &[Event], step_idx: usize) -> Option<DateTime<Utc>> {
+    for event in events.iter().rev() {
+        match event {
+            Event::TaskStarted { .. } | Event::TaskReset { .. } => return None,
+            Event::StepStarted { step, .. } | Event::ViewportLaunched { step, .. } if *step == step_idx => return None,
+            Event::StepReset { step, retry_at, .. } if *step == step_idx => return *retry_at,
+            _ => {}
+        }
+    }
+    None
 }
 
 /// Count auto-retries for a specific step since last TaskStarted/TaskReset(manual).
+/// A jump back to (or before) the step starts a fresh round of retries.
 pub fn count_auto_retries(events: &[Event], step_idx: usize) -> usize {
     let mut count = 0;
     for event in events.iter().rev() {
         match event {
             Event::TaskStarted { .. } | Event::TaskReset { .. } => break,
+            Event::StepReset { resumed: true, .. } => {}
             Event::StepReset { step, auto: false, .. } if *step == step_idx => break,
+            Event::StepJumped { to, .. } if *to <= step_idx => break,
             Event::StepReset { step, auto: true, .. } if *step == step_idx => {
                 count += 1;
             }
@@ -267,16 +1003,24 @@ mod tests {
         Utc::now()
     }
 
+    fn task_started() -> Event {
+        Event::TaskStarted {
+            ts: ts(), run_id: String::new(), workflow: String::new(),
+            parent: None, vars: IndexMap::new(), workflow_hash: None,
+        }
+    }
+
     fn finished(step: usize, success: bool, exit_code: i32) -> Event {
         Event::StepFinished {
             ts: ts(), step, success, exit_code,
             duration: Some(1.0), stdout: None, stderr: None, verify_output: None,
+            timed_out: false, outputs: IndexMap::new(),
         }
     }
 
     #[test]
     fn test_task_started() {
-        let events = vec![Event::TaskStarted { ts: ts(), run_id: String::new(), workflow: String::new() }];
+        let events = vec![task_started()];
         let state = replay(&events, 3).unwrap();
         assert_eq!(state.status, TaskStatus::Running);
         assert_eq!(state.current_step, 0);
@@ -285,7 +1029,7 @@ mod tests {
     #[test]
     fn test_step_finished_success() {
         let events = vec![
-            Event::TaskStarted { ts: ts(), run_id: String::new(), workflow: String::new() },
+            task_started(),
             finished(0, true, 0),
         ];
         let state = replay(&events, 3).unwrap();
@@ -297,7 +1041,7 @@ mod tests {
     #[test]
     fn test_step_finished_failure() {
         let events = vec![
-            Event::TaskStarted { ts: ts(), run_id: String::new(), workflow: String::new() },
+            task_started(),
             finished(0, false, 1),
         ];
         let state = replay(&events, 3).unwrap();
@@ -308,7 +1052,7 @@ mod tests {
     #[test]
     fn test_step_yielded_resumed() {
         let events = vec![
-            Event::TaskStarted { ts: ts(), run_id: String::new(), workflow: String::new() },
+            task_started(),
             Event::StepYielded { ts: ts(), step: 0, reason: "gate".to_string() },
         ];
         let state = replay(&events, 3).unwrap();
@@ -322,10 +1066,30 @@ mod tests {
         assert_eq!(state.current_step, 1);
     }
 
+    #[test]
+    fn test_step_blocked_unblocked() {
+        let mut events = vec![
+            task_started(),
+            finished(0, true, 0),
+            Event::StepYielded { ts: ts(), step: 1, reason: BLOCKED.to_string() },
+        ];
+        let state = replay(&events, 3).unwrap();
+        assert_eq!(state.status, TaskStatus::Waiting);
+        assert_eq!(state.message.as_deref(), Some(BLOCKED));
+
+        // Unblocking runs the same step; it isn't marked done
+        events.push(Event::StepUnblocked { ts: ts(), step: 1 });
+        let state = replay(&events, 3).unwrap();
+        assert_eq!(state.status, TaskStatus::Running);
+        assert_eq!(state.current_step, 1);
+        assert_eq!(state.message, None);
+        assert_eq!(state.step_status.get(&1), None);
+    }
+
     #[test]
     fn test_step_yielded_after_finished_resets_current_step() {
         let events = vec![
-            Event::TaskStarted { ts: ts(), run_id: String::new(), workflow: String::new() },
+            task_started(),
             finished(0, true, 0),
             Event::StepYielded { ts: ts(), step: 0, reason: "verify_manual".to_string() },
         ];
@@ -337,11 +1101,12 @@ mod tests {
     #[test]
     fn test_verify_failure_as_step_finished() {
         let events = vec![
-            Event::TaskStarted { ts: ts(), run_id: String::new(), workflow: String::new() },
+            task_started(),
             Event::StepFinished {
                 ts: ts(), step: 0, success: false, exit_code: 0,
                 duration: Some(2.0), stdout: None, stderr: None,
                 verify_output: Some("verify: tests failed".to_string()),
+                timed_out: false, outputs: IndexMap::new(),
             },
         ];
         let state = replay(&events, 3).unwrap();
@@ -353,9 +1118,9 @@ mod tests {
     #[test]
     fn test_verify_failure_then_retry() {
         let events = vec![
-            Event::TaskStarted { ts: ts(), run_id: String::new(), workflow: String::new() },
+            task_started(),
             finished(0, false, 1),
-            Event::StepReset { ts: ts(), step: 0, auto: true },
+            Event::StepReset { ts: ts(), step: 0, auto: true, retry_at: None, resumed: false },
         ];
         let state = replay(&events, 3).unwrap();
         assert_eq!(state.status, TaskStatus::Running);
@@ -366,7 +1131,7 @@ mod tests {
     #[test]
     fn test_auto_complete() {
         let events = vec![
-            Event::TaskStarted { ts: ts(), run_id: String::new(), workflow: String::new() },
+            task_started(),
             finished(0, true, 0),
         ];
         let state = replay(&events, 1).unwrap();
@@ -376,7 +1141,7 @@ mod tests {
     #[test]
     fn test_reset_clears_state() {
         let events = vec![
-            Event::TaskStarted { ts: ts(), run_id: String::new(), workflow: String::new() },
+            task_started(),
             finished(0, true, 0),
             Event::TaskReset { ts: ts() },
         ];
@@ -387,10 +1152,10 @@ mod tests {
     #[test]
     fn test_reset_then_restart() {
         let events = vec![
-            Event::TaskStarted { ts: ts(), run_id: String::new(), workflow: String::new() },
+            task_started(),
             finished(0, true, 0),
             Event::TaskReset { ts: ts() },
-            Event::TaskStarted { ts: ts(), run_id: String::new(), workflow: String::new() },
+            task_started(),
         ];
         let state = replay(&events, 3).unwrap();
         assert_eq!(state.current_step, 0);
@@ -401,8 +1166,8 @@ mod tests {
     #[test]
     fn test_skip_step() {
         let events = vec![
-            Event::TaskStarted { ts: ts(), run_id: String::new(), workflow: String::new() },
-            Event::StepSkipped { ts: ts(), step: 0 },
+            task_started(),
+            Event::StepSkipped { ts: ts(), step: 0, reason: None },
         ];
         let state = replay(&events, 3).unwrap();
         assert_eq!(state.current_step, 1);
@@ -412,8 +1177,8 @@ mod tests {
     #[test]
     fn test_task_stopped() {
         let events = vec![
-            Event::TaskStarted { ts: ts(), run_id: String::new(), workflow: String::new() },
-            Event::TaskStopped { ts: ts(), step: 0 },
+            task_started(),
+            Event::TaskStopped { ts: ts(), step: 0, kill: None },
         ];
         let state = replay(&events, 3).unwrap();
         assert_eq!(state.status, TaskStatus::Stopped);
@@ -422,9 +1187,9 @@ mod tests {
     #[test]
     fn test_step_reset_auto() {
         let events = vec![
-            Event::TaskStarted { ts: ts(), run_id: String::new(), workflow: String::new() },
+            task_started(),
             finished(0, false, 1),
-            Event::StepReset { ts: ts(), step: 0, auto: true },
+            Event::StepReset { ts: ts(), step: 0, auto: true, retry_at: None, resumed: false },
         ];
         let state = replay(&events, 3).unwrap();
         assert_eq!(state.current_step, 0);
@@ -435,9 +1200,9 @@ mod tests {
     #[test]
     fn test_step_reset_manual() {
         let events = vec![
-            Event::TaskStarted { ts: ts(), run_id: String::new(), workflow: String::new() },
+            task_started(),
             finished(0, true, 0),
-            Event::StepReset { ts: ts(), step: 0, auto: false },
+            Event::StepReset { ts: ts(), step: 0, auto: false, retry_at: None, resumed: false },
         ];
         let state = replay(&events, 3).unwrap();
         assert_eq!(state.current_step, 0);
@@ -447,7 +1212,7 @@ mod tests {
     #[test]
     fn test_viewport_lost() {
         let events = vec![
-            Event::TaskStarted { ts: ts(), run_id: String::new(), workflow: String::new() },
+            task_started(),
             Event::ViewportLaunched { ts: ts(), step: 0 },
             Event::ViewportLost { ts: ts(), step: 0 },
         ];
@@ -457,34 +1222,533 @@ mod tests {
         assert_eq!(state.message.as_deref(), Some("viewport lost"));
     }
 
+    fn started(step: usize, pid: u32) -> Event {
+        Event::StepStarted {
+            ts: ts(), step, pid, hostname: "host".to_string(), boot_id: None, pgid: Some(pid + 1),
+        }
+    }
+
+    #[test]
+    fn test_step_interrupted() {
+        let events = vec![
+            task_started(),
+            finished(0, true, 0),
+            started(1, 42),
+            Event::StepInterrupted { ts: ts(), step: 1 },
+        ];
+        let state = replay(&events, 3).unwrap();
+        assert_eq!(state.status, TaskStatus::Stopped);
+        assert_eq!(state.current_step, 1);
+        assert_eq!(state.message.as_deref(), Some(INTERRUPTED));
+        assert!(!state.step_status.contains_key(&1));
+    }
+
+    #[test]
+    fn test_step_finished_timed_out() {
+        let events = vec![
+            task_started(),
+            Event::StepFinished {
+                ts: ts(), step: 0, success: false, exit_code: 124,
+                duration: Some(2.0), stdout: None, stderr: None, verify_output: None,
+                timed_out: true, outputs: IndexMap::new(),
+            },
+        ];
+        let state = replay(&events, 3).unwrap();
+        assert_eq!(state.status, TaskStatus::Failed);
+        assert_eq!(state.message.as_deref(), Some(TIMED_OUT));
+
+        // Marker is omitted from the log unless set, and defaults when absent
+        let json = serde_json::to_string(&finished(0, false, 1)).unwrap();
+        assert!(!json.contains("timed_out"));
+        let json = serde_json::to_string(&events[1]).unwrap();
+        assert!(json.contains("\"timed_out\":true"));
+        let parsed: Event = serde_json::from_str(&json).unwrap();
+        assert!(matches!(parsed, Event::StepFinished { timed_out: true, .. }));
+    }
+
+    #[test]
+    fn test_step_rejected() {
+        let events = vec![
+            task_started(),
+            finished(0, true, 0),
+            Event::StepYielded { ts: ts(), step: 1, reason: "verify_manual".to_string() },
+            Event::StepRejected { ts: ts(), step: 1, message: Some("needs tests".to_string()) },
+        ];
+        let state = replay(&events, 3).unwrap();
+        assert_eq!(state.status, TaskStatus::Failed);
+        assert_eq!(state.current_step, 1);
+        assert_eq!(state.step_status.get(&1), Some(&StepStatus::Failed));
+        assert_eq!(state.message.as_deref(), Some(REJECTED));
+
+        // on_fail=retry routing follows with an auto reset
+        let mut events = events;
+        events.push(Event::StepReset { ts: ts(), step: 1, auto: true, retry_at: None, resumed: false });
+        let state = replay(&events, 3).unwrap();
+        assert_eq!(state.status, TaskStatus::Running);
+        assert_eq!(count_auto_retries(&events, 1), 1);
+    }
+
+    #[test]
+    fn test_step_jumped() {
+        let jump = |step: usize, to: usize| Event::StepJumped {
+            ts: ts(), step, to, reason: "on_fail".to_string(),
+        };
+        // test (1) fails and jumps back to fix (0)
+        let mut events = vec![
+            task_started(),
+            finished(0, true, 0),
+            finished(1, false, 1),
+            jump(1, 0),
+        ];
+        let state = replay(&events, 4).unwrap();
+        assert_eq!(state.status, TaskStatus::Running);
+        assert_eq!(state.current_step, 0);
+        assert!(state.step_status.is_empty());
+        assert_eq!(state.message, None);
+
+        // A forward jump marks the steps in between as skipped
+        events.push(finished(0, true, 0));
+        events.push(finished(1, false, 1));
+        events.push(jump(1, 3));
+        let state = replay(&events, 4).unwrap();
+        assert_eq!(state.current_step, 3);
+        assert_eq!(state.step_status.get(&1), Some(&StepStatus::Failed));
+        assert_eq!(state.step_status.get(&2), Some(&StepStatus::Skipped));
+        assert_eq!(count_jumps(&events), 2);
+
+        // Jumping back re-enters a step with a fresh retry budget
+        let events = vec![
+            task_started(),
+            Event::StepReset { ts: ts(), step: 0, auto: true, retry_at: None, resumed: false },
+            finished(0, true, 0),
+            finished(1, false, 1),
+            jump(1, 0),
+        ];
+        assert_eq!(count_auto_retries(&events, 0), 0);
+        assert_eq!(count_jumps(&events), 1);
+    }
+
+    #[test]
+    fn test_sub_steps() {
+        let sub_finished = |sub: usize, success: bool, cancelled: bool| Event::SubStepFinished {
+            ts: ts(), step: 1, sub, success, exit_code: if success { 0 } else { 1 },
+            duration: None, stdout: None, stderr: None, timed_out: false, cancelled, retry_at: None,
+        };
+        let mut events = vec![
+            task_started(),
+            finished(0, true, 0),
+            started(1, 42),
+            Event::SubStepStarted { ts: ts(), step: 1, sub: 0, pgid: Some(100) },
+            Event::SubStepStarted { ts: ts(), step: 1, sub: 1, pgid: Some(101) },
+            Event::SubStepStarted { ts: ts(), step: 1, sub: 2, pgid: Some(102) },
+            sub_finished(0, true, false),
+            sub_finished(1, false, false),
+            sub_finished(2, false, true),
+            Event::StepInterrupted { ts: ts(), step: 1 },
+        ];
+        let state = replay(&events, 3).unwrap();
+        assert_eq!(state.current_step, 1);
+        let subs = &state.sub_step_status[&1];
+        assert_eq!(subs.get(&0), Some(&StepStatus::Success));
+        assert_eq!(subs.get(&1), Some(&StepStatus::Failed));
+        assert_eq!(subs.get(&2), Some(&StepStatus::Cancelled));
+
+        // Crash resume keeps finished branches; the cancelled one runs again
+        events.push(Event::StepReset { ts: ts(), step: 1, auto: false, retry_at: None, resumed: true });
+        let results = sub_step_results(&events, 1);
+        assert_eq!(results, HashMap::from([(0, true), (1, false)]));
+
+        // pawl stop + reset --step: a manual reset runs every branch again
+        let mut reset = events[..events.len() - 2].to_vec();
+        reset.push(Event::TaskStopped { ts: ts(), step: 1, kill: None });
+        reset.push(Event::StepReset { ts: ts(), step: 1, auto: false, retry_at: None, resumed: false });
+        assert!(sub_step_results(&reset, 1).is_empty());
+
+        // Once the group settles, the next attempt starts from scratch
+        events.push(finished(1, false, 1));
+        events.push(Event::StepReset { ts: ts(), step: 1, auto: true, retry_at: None, resumed: false });
+        assert!(sub_step_results(&events, 1).is_empty());
+        events.push(Event::SubStepStarted { ts: ts(), step: 1, sub: 1, pgid: None });
+        let state = replay(&events, 3).unwrap();
+        assert_eq!(state.sub_step_status[&1].get(&1), None);
+    }
+
+    #[test]
+    fn test_open_sub_step_groups() {
+        let sub_finished = |sub: usize| Event::SubStepFinished {
+            ts: ts(), step: 1, sub, success: false, exit_code: 1, duration: None,
+            stdout: None, stderr: None, timed_out: false, cancelled: false, retry_at: None,
+        };
+        let mut events = vec![
+            task_started(),
+            started(1, 42),
+            Event::SubStepStarted { ts: ts(), step: 1, sub: 0, pgid: Some(100) },
+            Event::SubStepStarted { ts: ts(), step: 1, sub: 1, pgid: Some(101) },
+            sub_finished(0),
+        ];
+        assert_eq!(open_sub_step_groups(&events), vec![101]);
+
+        // A retried item: only its latest run is open
+        events.push(Event::SubStepStarted { ts: ts(), step: 1, sub: 0, pgid: Some(102) });
+        assert_eq!(open_sub_step_groups(&events), vec![102, 101]);
+
+        // Groups from an earlier owner were dealt with when it was found dead
+        events.push(Event::StepInterrupted { ts: ts(), step: 1 });
+        events.push(started(1, 43));
+        assert!(open_sub_step_groups(&events).is_empty());
+    }
+
+    #[test]
+    fn test_foreach_items() {
+        let item_finished = |sub: usize, success: bool, exit_code: i32| Event::SubStepFinished {
+            ts: ts(), step: 0, sub, success, exit_code, duration: None,
+            stdout: None, stderr: None, timed_out: false, cancelled: false, retry_at: None,
+        };
+        let items = vec!["linux".to_string(), "mac".to_string()];
+        let mut events = vec![
+            task_started(),
+            Event::StepExpanded { ts: ts(), step: 0, items: items.clone() },
+            item_finished(0, true, 0),
+            item_finished(1, false, 2),
+            item_finished(1, false, 3),
+        ];
+        assert_eq!(foreach_items(&events, 0), Some(items));
+        assert_eq!(item_failures(&events, 0, 1), vec![2, 3]);
+        assert!(item_failures(&events, 0, 0).is_empty());
+        assert_eq!(sub_step_results(&events, 0).get(&1), Some(&false));
+
+        // A crash-resumed attempt keeps its items and per-item budgets
+        events.push(Event::StepInterrupted { ts: ts(), step: 0 });
+        events.push(Event::StepReset { ts: ts(), step: 0, auto: false, retry_at: None, resumed: true });
+        assert_eq!(foreach_items(&events, 0).map(|i| i.len()), Some(2));
+        assert_eq!(item_failures(&events, 0, 1).len(), 2);
+        assert_eq!(sub_step_results(&events, 0).get(&0), Some(&true));
+
+        // A manual reset (pawl stop + reset --step) starts over: items, progress and budgets
+        let mut reset = events.clone();
+        reset.push(Event::TaskStopped { ts: ts(), step: 0, kill: None });
+        reset.push(Event::StepReset { ts: ts(), step: 0, auto: false, retry_at: None, resumed: false });
+        assert_eq!(foreach_items(&reset, 0), None);
+        assert!(item_failures(&reset, 0, 1).is_empty());
+        assert!(sub_step_results(&reset, 0).is_empty());
+
+        // A failed item's backoff stands until its next run starts
+        let at = ts() + chrono::Duration::seconds(30);
+        events.push(Event::SubStepFinished {
+            ts: ts(), step: 0, sub: 1, success: false, exit_code: 1, duration: None,
+            stdout: None, stderr: None, timed_out: false, cancelled: false, retry_at: Some(at),
+        });
+        assert_eq!(item_retry_at(&events, 0, 1), Some(at));
+        assert_eq!(item_retry_at(&events, 0, 0), None);
+        events.push(Event::SubStepStarted { ts: ts(), step: 0, sub: 1, pgid: None });
+        assert_eq!(item_retry_at(&events, 0, 1), None);
+        events.pop();
+        events.pop();
+
+        // Once the step settles, the next attempt resolves its items afresh
+        events.push(finished(0, false, 3));
+        assert_eq!(foreach_items(&events, 0), None);
+        assert!(item_failures(&events, 0, 1).is_empty());
+
+        events.push(Event::StepReset { ts: ts(), step: 0, auto: false, retry_at: None, resumed: false });
+        events.push(Event::StepExpanded { ts: ts(), step: 0, items: vec!["linux".to_string()] });
+        let state = replay(&events, 1).unwrap();
+        assert_eq!(state.status, TaskStatus::Running);
+        assert!(!state.sub_step_status.contains_key(&0));
+    }
+
+    #[test]
+    fn test_sub_workflow_run() {
+        let sub_started = |run_id: &str| Event::SubWorkflowStarted {
+            ts: ts(), step: 1, child: "t/build".to_string(), run_id: run_id.to_string(),
+        };
+        let mut events = vec![task_started(), finished(0, true, 0), sub_started("c1")];
+        let state = replay(&events, 3).unwrap();
+        assert_eq!(state.status, TaskStatus::Running);
+        assert_eq!(state.current_step, 1);
+        // The child run drives the step, not this log's last StepStarted
+        assert_eq!(step_owner(&events), None);
+        assert_eq!(sub_workflow_run(&events, 1), Some(("t/build".to_string(), "c1".to_string())));
+        assert_eq!(sub_workflow_run(&events, 0), None);
+
+        // A crash and resume keeps the attempt (and its child run)
+        events.push(Event::StepInterrupted { ts: ts(), step: 1 });
+        events.push(Event::StepReset { ts: ts(), step: 1, auto: false, retry_at: None, resumed: true });
+        assert_eq!(sub_workflow_run(&events, 1).map(|(_, id)| id).as_deref(), Some("c1"));
+
+        // Once the step settles, a retry starts a new child run
+        events.push(finished(1, false, 1));
+        events.push(Event::StepReset { ts: ts(), step: 1, auto: true, retry_at: None, resumed: false });
+        assert_eq!(sub_workflow_run(&events, 1), None);
+    }
+
+    #[test]
+    fn test_run_parent_and_vars() {
+        let parent = ParentRun { task: "t".to_string(), run_id: "p1".to_string(), step: 1 };
+        let mut events = vec![Event::TaskStarted {
+            ts: ts(), run_id: "c1".to_string(), workflow: "build".to_string(),
+            parent: Some(parent.clone()),
+            vars: IndexMap::from([("dir".to_string(), "t".to_string())]),
+            workflow_hash: None,
+        }];
+        assert_eq!(run_parent(&events), Some(parent));
+        assert_eq!(run_vars(&events).get("dir").map(String::as_str), Some("t"));
+
+        events.push(Event::TaskReset { ts: ts() });
+        assert_eq!(run_parent(&events), None);
+        assert!(run_vars(&events).is_empty());
+    }
+
+    #[test]
+    fn test_rollback() {
+        let compensated = |step: usize, success: bool| Event::StepCompensated {
+            ts: ts(), step, success, exit_code: if success { 0 } else { 1 },
+            duration: None, stderr: None,
+        };
+        let mut events = vec![
+            task_started(),
+            finished(0, true, 0),
+            finished(1, true, 0),
+            finished(2, false, 1),
+            Event::RollbackStarted { ts: ts(), reason: "failed".to_string() },
+            compensated(1, false),
+        ];
+        let state = replay(&events, 3).unwrap();
+        assert_eq!(state.status, TaskStatus::Failed);
+        assert_eq!(state.message.as_deref(), Some(ROLLBACK_FAILED));
+        assert_eq!(state.step_status.get(&1), Some(&StepStatus::Success));
+
+        // Resumed: the step is compensated on the next attempt
+        events.push(compensated(1, true));
+        events.push(compensated(0, true));
+        events.push(Event::RollbackFinished { ts: ts() });
+        let state = replay(&events, 3).unwrap();
+        assert_eq!(state.message.as_deref(), Some(ROLLED_BACK));
+        assert_eq!(state.current_step, 2);
+        assert_eq!(state.step_status.get(&0), Some(&StepStatus::Compensated));
+        assert_eq!(state.step_status.get(&1), Some(&StepStatus::Compensated));
+        assert_eq!(state.step_status.get(&2), Some(&StepStatus::Failed));
+
+        // Rolling back doesn't count as the workflow ending again
+        let mut events = events[..4].to_vec();
+        events.push(Event::FinallyStarted { ts: ts(), status: "failed".to_string() });
+        events.push(Event::RollbackStarted { ts: ts(), reason: "manual".to_string() });
+        events.push(compensated(1, true));
+        assert!(!finally_due(&events));
+    }
+
+    #[test]
+    fn test_finally_events() {
+        let finally_finished = |success: bool| Event::FinallyFinished {
+            ts: ts(), step: 0, success, exit_code: if success { 0 } else { 1 },
+            duration: None, stderr: None,
+        };
+        let mut events = vec![
+            task_started(),
+            finished(0, false, 1),
+        ];
+        assert!(finally_due(&events));
+
+        // Finally events leave the main outcome alone
+        events.push(Event::FinallyStarted { ts: ts(), status: "failed".to_string() });
+        events.push(finally_finished(false));
+        let state = replay(&events, 2).unwrap();
+        assert_eq!(state.status, TaskStatus::Failed);
+        assert_eq!(state.current_step, 0);
+        assert!(!finally_due(&events));
+        assert_eq!(finally_results(&events).get(&0), Some(&false));
+
+        // A retry that ends the workflow again makes them due again
+        events.push(Event::StepReset { ts: ts(), step: 0, auto: false, retry_at: None, resumed: false });
+        events.push(finished(0, true, 0));
+        assert!(finally_due(&events));
+        events.push(Event::FinallyStarted { ts: ts(), status: "completed".to_string() });
+        assert!(finally_results(&events).is_empty());
+        events.push(finally_finished(true));
+        assert_eq!(replay(&events, 1).unwrap().status, TaskStatus::Completed);
+        assert_eq!(finally_results(&events).get(&0), Some(&true));
+    }
+
+    #[test]
+    fn test_step_outputs() {
+        let with_output = |step: usize, value: &str| Event::StepFinished {
+            ts: ts(), step, success: true, exit_code: 0,
+            duration: None, stdout: None, stderr: None, verify_output: None,
+            timed_out: false,
+            outputs: IndexMap::from([("version".to_string(), value.to_string())]),
+        };
+        let mut events = vec![
+            task_started(),
+            with_output(0, "1.0"),
+            finished(1, true, 0),
+        ];
+        let outputs = step_outputs(&events);
+        assert_eq!(outputs.len(), 1);
+        assert_eq!(outputs[&0]["version"], "1.0");
+
+        // A later attempt replaces the earlier capture
+        events.push(Event::StepReset { ts: ts(), step: 0, auto: false, retry_at: None, resumed: false });
+        events.push(with_output(0, "1.1"));
+        assert_eq!(step_outputs(&events)[&0]["version"], "1.1");
+
+        // A new run starts clean
+        events.push(task_started());
+        assert!(step_outputs(&events).is_empty());
+    }
+
+    #[test]
+    fn test_pending_retry_at() {
+        let at = ts() + chrono::Duration::seconds(30);
+        let mut events = vec![
+            task_started(),
+            started(0, 42),
+            finished(0, false, 1),
+        ];
+        assert_eq!(pending_retry_at(&events, 0), None);
+
+        events.push(Event::StepReset { ts: ts(), step: 0, auto: true, retry_at: Some(at), resumed: false });
+        assert_eq!(pending_retry_at(&events, 0), Some(at));
+        assert_eq!(pending_retry_at(&events, 1), None);
+
+        // Survives a crash during the backoff, and the resume keeps the retry count
+        events.push(Event::StepInterrupted { ts: ts(), step: 0 });
+        assert_eq!(pending_retry_at(&events, 0), Some(at));
+        events.push(Event::StepReset { ts: ts(), step: 0, auto: false, retry_at: Some(at), resumed: true });
+        assert_eq!(pending_retry_at(&events, 0), Some(at));
+        assert_eq!(count_auto_retries(&events, 0), 1);
+
+        // Once the next attempt starts, the backoff is spent
+        events.push(started(0, 43));
+        assert_eq!(pending_retry_at(&events, 0), None);
+    }
+
+    #[test]
+    fn test_step_owner() {
+        let mut events = vec![
+            task_started(),
+        ];
+        assert_eq!(step_owner(&events), None);
+
+        events.push(started(0, 42));
+        assert_eq!(step_owner(&events), Some(StepOwner {
+            step: 0, pid: 42, hostname: "host".to_string(), boot_id: None, pgid: Some(43),
+        }));
+
+        // Same process keeps driving through finished steps and auto-retries
+        events.push(finished(0, false, 1));
+        events.push(Event::StepReset { ts: ts(), step: 0, auto: true, retry_at: None, resumed: false });
+        assert_eq!(step_owner(&events).map(|o| o.pid), Some(42));
+
+        // Yield hands control away
+        events.push(Event::StepYielded { ts: ts(), step: 0, reason: "gate".to_string() });
+        assert_eq!(step_owner(&events), None);
+
+        events.push(Event::StepResumed { ts: ts(), step: 0, message: None });
+        events.push(started(1, 43));
+        assert_eq!(step_owner(&events).map(|o| (o.step, o.pid)), Some((1, 43)));
+
+        events.push(Event::StepInterrupted { ts: ts(), step: 1 });
+        assert_eq!(step_owner(&events), None);
+    }
+
     #[test]
     fn test_serialization_roundtrip() {
         let event = Event::StepFinished {
             ts: ts(), step: 0, success: true, exit_code: 0,
             duration: Some(5.2), stdout: Some("output".to_string()),
-            stderr: None, verify_output: None,
+            stderr: None, verify_output: None, timed_out: false, outputs: IndexMap::new(),
         };
         let json = serde_json::to_string(&event).unwrap();
         assert!(json.contains(r#""type":"step_finished""#));
         let _: Event = serde_json::from_str(&json).unwrap();
     }
 
+    #[test]
+    fn test_replay_from_checkpoint() {
+        let events = vec![
+            task_started(),
+            finished(0, true, 0),
+            Event::TaskReset { ts: ts() },
+            task_started(),
+            finished(0, true, 0),
+            finished(1, true, 0),
+        ];
+        // Completed is only derived at the end, whatever the split
+        for split in 0..=events.len() {
+            let checkpoint = apply_events(None, &events[..split]);
+            assert_eq!(replay_from(checkpoint, &events[split..], 2), replay(&events, 2), "split at {}", split);
+        }
+        assert_eq!(apply_events(None, &events).unwrap().status, TaskStatus::Running);
+        assert_eq!(replay(&events, 2).unwrap().status, TaskStatus::Completed);
+    }
+
+    #[test]
+    fn test_apply_migrations() {
+        // [build, review, ship] → [build, lint, review] (ship removed), waiting at review
+        let events = apply_migrations(vec![
+            task_started(),
+            finished(0, true, 0),
+            Event::StepYielded { ts: ts(), step: 1, reason: "gate".to_string() },
+            Event::WorkflowMigrated {
+                ts: ts(), workflow_hash: "new".to_string(), step: 2, steps: vec![Some(0), Some(2), None],
+            },
+        ]);
+        assert!(matches!(events[2], Event::StepYielded { step: 2, .. }));
+        assert_eq!(pinned_hash(&events).flatten().as_deref(), Some("new"));
+        assert_eq!(pinned_hash(&events[1..2]), None);
+        let state = replay(&events, 3).unwrap();
+        assert_eq!(state.status, TaskStatus::Waiting);
+        assert_eq!(state.current_step, 2);
+        assert_eq!(state.step_status.get(&0), Some(&StepStatus::Success));
+        assert_eq!(state.step_status.get(&1), None);
+
+        let dropped = apply_migrations(vec![
+            task_started(),
+            finished(2, false, 1),
+            Event::WorkflowMigrated { ts: ts(), workflow_hash: "h".to_string(), step: 0, steps: vec![Some(0), Some(1), None] },
+        ]);
+        assert_eq!(dropped.len(), 2);
+    }
+
     #[test]
     fn test_type_name_matches_serde_tag() {
         let events: Vec<Event> = vec![
-            Event::TaskStarted { ts: ts(), run_id: String::new(), workflow: String::new() },
+            task_started(),
+            started(0, 1),
             Event::StepFinished {
                 ts: ts(), step: 0, success: true, exit_code: 0,
-                duration: None, stdout: None, stderr: None, verify_output: None,
+                duration: Some(1.0), stdout: None, stderr: None, verify_output: None,
+                timed_out: false, outputs: IndexMap::new(),
             },
             Event::StepYielded { ts: ts(), step: 0, reason: "gate".to_string() },
-            Event::StepResumed { ts: ts(), step: 0, message: None },
+            Event::StepResumed { ts: ts(), step: 0, message: Some("ok".to_string()) },
+            Event::StepUnblocked { ts: ts(), step: 0 },
+            Event::StepRejected { ts: ts(), step: 0, message: Some("no".to_string()) },
             Event::ViewportLaunched { ts: ts(), step: 0 },
-            Event::StepSkipped { ts: ts(), step: 0 },
-            Event::StepReset { ts: ts(), step: 0, auto: false },
-            Event::TaskStopped { ts: ts(), step: 0 },
+            Event::StepSkipped { ts: ts(), step: 0, reason: Some("when".to_string()) },
+            Event::StepJumped { ts: ts(), step: 1, to: 0, reason: "on_fail".to_string() },
+            Event::StepReset { ts: ts(), step: 0, auto: false, retry_at: Some(ts()), resumed: false },
+            Event::TaskStopped { ts: ts(), step: 0, kill: Some("SIGTERM".to_string()) },
             Event::TaskReset { ts: ts() },
             Event::ViewportLost { ts: ts(), step: 0 },
+            Event::StepInterrupted { ts: ts(), step: 0 },
+            Event::StepExpanded { ts: ts(), step: 0, items: vec!["a".to_string()] },
+            Event::SubStepStarted { ts: ts(), step: 0, sub: 1, pgid: None },
+            Event::SubStepFinished {
+                ts: ts(), step: 0, sub: 1, success: false, exit_code: 1, duration: Some(1.0),
+                stdout: None, stderr: None, timed_out: false, cancelled: false, retry_at: Some(ts()),
+            },
+            Event::SubWorkflowStarted { ts: ts(), step: 0, child: "t/build".to_string(), run_id: "c".to_string() },
+            Event::RollbackStarted { ts: ts(), reason: "manual".to_string() },
+            Event::StepCompensated {
+                ts: ts(), step: 0, success: true, exit_code: 0, duration: None, stderr: None,
+            },
+            Event::RollbackFinished { ts: ts() },
+            Event::FinallyStarted { ts: ts(), status: "completed".to_string() },
+            Event::FinallyFinished {
+                ts: ts(), step: 0, success: true, exit_code: 0, duration: None, stderr: None,
+            },
+            Event::WorkflowMigrated { ts: ts(), workflow_hash: "h".to_string(), step: 0, steps: vec![Some(0)] },
         ];
         for event in &events {
             let json: serde_json::Value = serde_json::to_value(event).unwrap();
@@ -493,6 +1757,15 @@ mod tests {
                 event.type_name(), serde_tag,
                 "type_name() mismatch for {:?}", event.type_name()
             );
+            assert!(EVENT_TYPES.contains(&serde_tag), "{} missing from EVENT_TYPES", serde_tag);
+        }
+        assert_eq!(events.len(), EVENT_TYPES.len());
+
+        // HOOK_VARS is exactly what extra_vars produces (pawl check relies on it)
+        let produced: HashSet<String> = events.iter().flat_map(|e| e.extra_vars().into_keys()).collect();
+        for name in &produced {
+            assert!(HOOK_VARS.contains(&name.as_str()), "{} missing from HOOK_VARS", name);
         }
+        assert_eq!(produced.len(), HOOK_VARS.len());
     }
 }
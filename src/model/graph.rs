@@ -0,0 +1,160 @@
+use indexmap::IndexMap;
+use std::collections::{BTreeSet, HashSet};
+
+use super::Config;
+
+/// Task dependency graph across all workflows (`tasks.<name>.depends`), whatever
+/// each dependency's condition. Dependencies on undeclared tasks are kept as edges to nodes with no entry.
+#[derive(Debug, Clone, Default)]
+pub struct TaskGraph {
+    depends: IndexMap<String, Vec<String>>,
+}
+
+impl TaskGraph {
+    pub fn new(workflows: &IndexMap<String, Config>) -> Self {
+        let depends = workflows.values()
+            .flat_map(|config| config.tasks.iter())
+            .map(|(name, tc)| (name.clone(), tc.depends.iter().map(|d| d.task().to_string()).collect()))
+            .collect();
+        Self { depends }
+    }
+
+    /// Direct dependencies of a task (empty for undeclared tasks)
+    pub fn depends(&self, task: &str) -> &[String] {
+        self.depends.get(task).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Declared tasks that depend directly on `task`, sorted
+    pub fn dependents(&self, task: &str) -> Vec<&str> {
+        let mut dependents: Vec<&str> = self.depends.iter()
+            .filter(|(_, deps)| deps.iter().any(|d| d == task))
+            .map(|(name, _)| name.as_str())
+            .collect();
+        dependents.sort();
+        dependents
+    }
+
+    /// Every cycle, each as the path that closes it (`a → b → a` is `[a, b, a]`)
+    pub fn cycles(&self) -> Vec<Vec<&str>> {
+        fn visit<'a>(
+            graph: &'a TaskGraph,
+            task: &'a str,
+            path: &mut Vec<&'a str>,
+            done: &mut HashSet<&'a str>,
+            cycles: &mut Vec<Vec<&'a str>>,
+        ) {
+            if done.contains(task) {
+                return;
+            }
+            if let Some(start) = path.iter().position(|t| *t == task) {
+                let mut cycle = path[start..].to_vec();
+                cycle.push(task);
+                cycles.push(cycle);
+                return;
+            }
+            path.push(task);
+            for dep in graph.depends(task) {
+                visit(graph, dep, path, done, cycles);
+            }
+            path.pop();
+            done.insert(task);
+        }
+
+        let mut done = HashSet::new();
+        let mut cycles = Vec::new();
+        let mut names: Vec<&str> = self.depends.keys().map(String::as_str).collect();
+        names.sort();
+        for task in names {
+            visit(self, task, &mut Vec::new(), &mut done, &mut cycles);
+        }
+        cycles
+    }
+
+    /// Declared tasks, each after everything it depends on (ties by name).
+    /// Tasks on a cycle never become ready and are left out.
+    pub fn order(&self) -> Vec<&str> {
+        let mut order = Vec::with_capacity(self.depends.len());
+        let mut placed: HashSet<&str> = HashSet::new();
+        let is_ready = |name: &str, placed: &HashSet<&str>| {
+            self.depends(name).iter()
+                .all(|d| placed.contains(d.as_str()) || !self.depends.contains_key(d))
+        };
+        let mut pending: BTreeSet<&str> = self.depends.keys().map(String::as_str).collect();
+        while let Some(next) = pending.iter().copied().find(|name| is_ready(name, &placed)) {
+            pending.remove(next);
+            placed.insert(next);
+            order.push(next);
+        }
+        order
+    }
+
+    /// Declared tasks that depend on a task, directly or not — in dependency order
+    pub fn descendants(&self, task: &str) -> Vec<&str> {
+        let mut seen: HashSet<&str> = HashSet::new();
+        let mut stack = self.dependents(task);
+        while let Some(dependent) = stack.pop() {
+            if dependent != task && seen.insert(dependent) {
+                stack.extend(self.dependents(dependent));
+            }
+        }
+        self.order().into_iter().filter(|t| seen.contains(t)).collect()
+    }
+
+    /// Everything a task depends on, directly or not — dependencies first
+    pub fn ancestors(&self, task: &str) -> Vec<&str> {
+        let mut seen: HashSet<&str> = HashSet::new();
+        let mut stack: Vec<&str> = self.depends(task).iter().map(String::as_str).collect();
+        while let Some(dep) = stack.pop() {
+            if dep != task && seen.insert(dep) {
+                stack.extend(self.depends(dep).iter().map(String::as_str));
+            }
+        }
+        let mut ancestors: Vec<&str> = self.order().into_iter().filter(|t| seen.contains(t)).collect();
+        // Undeclared dependencies have no place in the order; list them last
+        let mut undeclared: Vec<&str> = seen.into_iter().filter(|t| !self.depends.contains_key(*t)).collect();
+        undeclared.sort();
+        ancestors.extend(undeclared);
+        ancestors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph(tasks: &str) -> TaskGraph {
+        let config = Config::from_str(&format!(r#"{{"tasks": {}, "workflow": []}}"#, tasks)).unwrap();
+        TaskGraph::new(&IndexMap::from([("default".to_string(), config)]))
+    }
+
+    #[test]
+    fn test_order_and_ancestors() {
+        let g = graph(r#"{
+            "deploy": {"depends": ["test", "build"]},
+            "test": {"depends": ["build"]},
+            "build": {},
+            "docs": {"depends": ["ghost"]},
+            "api": {}
+        }"#);
+        assert_eq!(g.order(), ["api", "build", "docs", "test", "deploy"]);
+        assert_eq!(g.ancestors("deploy"), ["build", "test"]);
+        assert_eq!(g.ancestors("docs"), ["ghost"]);
+        assert!(g.ancestors("build").is_empty());
+        assert_eq!(g.dependents("build"), ["deploy", "test"]);
+        assert_eq!(g.descendants("build"), ["test", "deploy"]);
+        assert!(g.descendants("deploy").is_empty());
+        assert!(g.cycles().is_empty());
+    }
+
+    #[test]
+    fn test_cycles() {
+        let g = graph(r#"{
+            "a": {"depends": ["b"]},
+            "b": {"depends": ["a"]},
+            "c": {"depends": ["a"]},
+            "d": {}
+        }"#);
+        assert_eq!(g.cycles(), [["a", "b", "a"]]);
+        assert_eq!(g.order(), ["d"]);
+    }
+}
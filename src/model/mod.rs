@@ -1,5 +1,6 @@
 pub mod config;
 pub mod event;
+pub mod graph;
 pub mod state;
 
 pub use config::Config;
@@ -5,7 +5,7 @@ use std::collections::HashMap;
 use crate::error::PawlError;
 
 /// Task state — pure projection type reconstructed by replay()
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct TaskState {
     /// Current step index (0-based)
     pub current_step: usize,
@@ -25,6 +25,10 @@ pub struct TaskState {
     #[serde(default)]
     pub step_status: HashMap<usize, StepStatus>,
 
+    /// Status of each finished sub-step of a parallel group (by step index, then sub-step index)
+    #[serde(default)]
+    pub sub_step_status: HashMap<usize, HashMap<usize, StepStatus>>,
+
     /// Optional message (e.g., failure reason)
     #[serde(default)]
     pub message: Option<String>,
@@ -33,6 +37,19 @@ pub struct TaskState {
     pub run_id: String,
 }
 
+/// Replay checkpoint of a task log (`.pawl/checkpoints/<task>.json`): what the
+/// log's first `offset` bytes replay to, so a replay only reads what follows
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// Length of the replayed prefix (ends at a line break)
+    pub offset: u64,
+    /// State after the prefix, before Completed is derived
+    pub state: Option<TaskState>,
+    /// Definition hash the prefix's run is pinned to
+    #[serde(default)]
+    pub pin: Option<String>,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
 #[serde(rename_all = "lowercase")]
 pub enum TaskStatus {
@@ -98,6 +115,10 @@ pub enum StepStatus {
     Success,
     Failed,
     Skipped,
+    /// Succeeded, then undone by its `compensate` command during a rollback
+    Compensated,
+    /// Parallel sub-step stopped because its group was already decided
+    Cancelled,
 }
 
 impl std::fmt::Display for StepStatus {
@@ -106,6 +127,8 @@ impl std::fmt::Display for StepStatus {
             Self::Success => write!(f, "success"),
             Self::Failed => write!(f, "failed"),
             Self::Skipped => write!(f, "skipped"),
+            Self::Compensated => write!(f, "compensated"),
+            Self::Cancelled => write!(f, "cancelled"),
         }
     }
 }
@@ -0,0 +1,202 @@
+use anyhow::Result;
+use std::cmp::Ordering;
+
+use super::shell::run_command;
+use super::variable::Context;
+
+/// Comparison operator in a `when` expression
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Eq,
+    Ne,
+    Le,
+    Ge,
+}
+
+impl Op {
+    /// Bare `<` and `>` are not operators: in a shell predicate they are
+    /// redirections (`echo > out`), so those run as shell commands
+    fn parse(token: &str) -> Option<Self> {
+        match token {
+            "==" => Some(Self::Eq),
+            "!=" => Some(Self::Ne),
+            "<=" => Some(Self::Le),
+            ">=" => Some(Self::Ge),
+            _ => None,
+        }
+    }
+
+    fn holds(self, ord: Ordering) -> bool {
+        match self {
+            Self::Eq => ord == Ordering::Equal,
+            Self::Ne => ord != Ordering::Equal,
+            Self::Le => ord != Ordering::Greater,
+            Self::Ge => ord != Ordering::Less,
+        }
+    }
+}
+
+/// One side of a comparison: bare names resolve as context variables
+#[derive(Debug, PartialEq)]
+struct Operand {
+    text: String,
+    quoted: bool,
+}
+
+/// Evaluate a step's `when` condition.
+/// `lhs OP rhs` (OP: == != <= >=) compares context values — numerically when
+/// both sides are numbers, as strings otherwise. Anything else runs as a shell
+/// predicate with the context as env vars (exit 0 = true).
+pub fn evaluate(expr: &str, ctx: &Context) -> Result<bool> {
+    if let Some((lhs, op, rhs)) = parse_comparison(expr) {
+        return Ok(compare(&resolve(&lhs, ctx), op, &resolve(&rhs, ctx)));
+    }
+    let result = run_command(&ctx.expand(expr), &ctx.to_env_vars(), |_| {})?;
+    Ok(result.success)
+}
+
+fn parse_comparison(expr: &str) -> Option<(Operand, Op, Operand)> {
+    let mut tokens = tokenize(expr)?.into_iter();
+    let (lhs, op, rhs) = (tokens.next()?, tokens.next()?, tokens.next()?);
+    if tokens.next().is_some() || op.quoted {
+        return None;
+    }
+    Some((lhs, Op::parse(&op.text)?, rhs))
+}
+
+/// Whitespace-separated tokens; single or double quotes group (None if malformed)
+fn tokenize(expr: &str) -> Option<Vec<Operand>> {
+    let mut tokens = Vec::new();
+    let mut chars = expr.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        if c == '"' || c == '\'' {
+            chars.next();
+            let mut text = String::new();
+            let mut closed = false;
+            for ch in chars.by_ref() {
+                if ch == c {
+                    closed = true;
+                    break;
+                }
+                text.push(ch);
+            }
+            if !closed {
+                return None;
+            }
+            tokens.push(Operand { text, quoted: true });
+            continue;
+        }
+        let mut text = String::new();
+        while let Some(&ch) = chars.peek() {
+            if ch.is_whitespace() {
+                break;
+            }
+            if ch == '"' || ch == '\'' {
+                return None;
+            }
+            text.push(ch);
+            chars.next();
+        }
+        tokens.push(Operand { text, quoted: false });
+    }
+    Some(tokens)
+}
+
+fn resolve(operand: &Operand, ctx: &Context) -> String {
+    if !operand.quoted
+        && is_identifier(&operand.text)
+        && let Some(value) = ctx.get(&operand.text)
+    {
+        return value.to_string();
+    }
+    ctx.expand(&operand.text)
+}
+
+fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+}
+
+fn compare(lhs: &str, op: Op, rhs: &str) -> bool {
+    let ord = match (lhs.trim().parse::<f64>(), rhs.trim().parse::<f64>()) {
+        (Ok(l), Ok(r)) => l.partial_cmp(&r),
+        _ => Some(lhs.cmp(rhs)),
+    };
+    ord.is_some_and(|ord| op.holds(ord))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ctx() -> Context {
+        Context::build()
+            .var("task", "auth")
+            .var("retry_count", "2")
+            .var_owned("branch".to_string(), "main".to_string())
+            .var_owned("empty".to_string(), String::new())
+    }
+
+    #[test]
+    fn test_compare_strings() {
+        let ctx = ctx();
+        assert!(evaluate("${branch} == main", &ctx).unwrap());
+        assert!(!evaluate("${branch} != main", &ctx).unwrap());
+        assert!(evaluate("branch == main", &ctx).unwrap());
+        assert!(evaluate("task != 'auth service'", &ctx).unwrap());
+        assert!(evaluate("\"${empty}\" == ''", &ctx).unwrap());
+        assert!(evaluate("${empty} == ''", &ctx).unwrap());
+    }
+
+    #[test]
+    fn test_compare_numbers() {
+        let ctx = ctx();
+        assert!(evaluate("retry_count >= 1", &ctx).unwrap());
+        assert!(evaluate("${retry_count} >= 2", &ctx).unwrap());
+        assert!(!evaluate("retry_count <= 1", &ctx).unwrap());
+        assert!(evaluate("retry_count <= 2.0", &ctx).unwrap());
+        // Numeric, not lexicographic
+        assert!(evaluate("10 >= 9", &ctx).unwrap());
+    }
+
+    #[test]
+    fn test_quoted_names_are_literal() {
+        let ctx = ctx();
+        assert!(!evaluate("'branch' == main", &ctx).unwrap());
+        assert!(evaluate("'main' == branch", &ctx).unwrap());
+    }
+
+    #[test]
+    fn test_shell_predicate() {
+        let ctx = ctx();
+        assert!(evaluate("true", &ctx).unwrap());
+        assert!(!evaluate("false", &ctx).unwrap());
+        assert!(evaluate("test \"$PAWL_TASK\" = auth", &ctx).unwrap());
+        assert!(evaluate("[ ${retry_count} -gt 1 ]", &ctx).unwrap());
+    }
+
+    #[test]
+    fn test_redirections_run_as_shell() {
+        let ctx = ctx();
+        for expr in ["echo > out", "true < /dev/null", "grep x > out", "test -f file", "cat < in"] {
+            assert!(parse_comparison(expr).is_none(), "{}", expr);
+        }
+        // As a string comparison, "true" < "/dev/null" would be false
+        assert!(evaluate("true < /dev/null", &ctx).unwrap());
+        assert!(!evaluate("test -f /nonexistent/pawl-when", &ctx).unwrap());
+    }
+
+    #[test]
+    fn test_parse_comparison() {
+        assert!(parse_comparison("a == b").is_some());
+        assert!(parse_comparison("a '==' b").is_none());
+        assert!(parse_comparison("a == b c").is_none());
+        assert!(parse_comparison("a = b").is_none());
+        assert!(parse_comparison("'a == b").is_none());
+    }
+}
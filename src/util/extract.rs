@@ -0,0 +1,122 @@
+use regex::Regex;
+use serde_json::Value;
+use std::path::Path;
+
+/// First capture group of `pattern` in `text` (whole match if the pattern has no groups)
+pub fn regex_capture(pattern: &str, text: &str) -> Result<Option<String>, regex::Error> {
+    let re = Regex::new(pattern)?;
+    Ok(re.captures(text).map(|caps| {
+        caps.get(1)
+            .or_else(|| caps.get(0))
+            .map(|m| m.as_str().to_string())
+            .unwrap_or_default()
+    }))
+}
+
+/// Look up a JSON path (`.a.b[0]`, `$.a.b`, or `a.b.0`) in `text`.
+/// `text` is parsed whole, falling back to its last non-empty line (for commands that
+/// log before printing JSON). Strings come back unquoted, other values as JSON.
+pub fn json_path(path: &str, text: &str) -> Option<String> {
+    let root: Value = serde_json::from_str(text)
+        .ok()
+        .or_else(|| serde_json::from_str(text.lines().rev().find(|l| !l.trim().is_empty())?).ok())?;
+
+    let mut node = &root;
+    for segment in path_segments(path)? {
+        node = match segment {
+            Segment::Key(key) => node.get(key)?,
+            Segment::Index(i) => node.get(i)?,
+        };
+    }
+    Some(match node {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    })
+}
+
+/// Last non-empty line of a file (trimmed)
+pub fn file_last_line(path: &Path) -> Option<String> {
+    let content = std::fs::read_to_string(path).ok()?;
+    content
+        .lines()
+        .rev()
+        .map(str::trim)
+        .find(|l| !l.is_empty())
+        .map(str::to_string)
+}
+
+#[derive(Debug, PartialEq)]
+enum Segment<'a> {
+    Key(&'a str),
+    Index(usize),
+}
+
+fn path_segments(path: &str) -> Option<Vec<Segment<'_>>> {
+    let path = path.trim();
+    let path = path.strip_prefix('$').unwrap_or(path);
+    let mut segments = Vec::new();
+    for part in path.split('.').filter(|p| !p.is_empty()) {
+        // "items[0][1]" → Key("items"), Index(0), Index(1)
+        let (key, mut rest) = match part.find('[') {
+            Some(i) => (&part[..i], &part[i..]),
+            None => (part, ""),
+        };
+        if !key.is_empty() {
+            match key.parse::<usize>() {
+                Ok(i) => segments.push(Segment::Index(i)),
+                Err(_) => segments.push(Segment::Key(key)),
+            }
+        }
+        while let Some(inner) = rest.strip_prefix('[') {
+            let end = inner.find(']')?;
+            segments.push(Segment::Index(inner[..end].trim().parse().ok()?));
+            rest = &inner[end + 1..];
+        }
+        if !rest.is_empty() {
+            return None;
+        }
+    }
+    Some(segments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_regex_capture() {
+        let out = "building...\nversion: 1.4.2\ndone\n";
+        assert_eq!(regex_capture(r"version: (\S+)", out).unwrap().as_deref(), Some("1.4.2"));
+        assert_eq!(regex_capture(r"\d+\.\d+", out).unwrap().as_deref(), Some("1.4"));
+        assert_eq!(regex_capture(r"sha: (\w+)", out).unwrap(), None);
+        assert!(regex_capture(r"(unclosed", out).is_err());
+    }
+
+    #[test]
+    fn test_json_path() {
+        let out = r#"{"build":{"id":42,"tags":["a","b"],"name":"web"}}"#;
+        assert_eq!(json_path(".build.name", out).as_deref(), Some("web"));
+        assert_eq!(json_path("$.build.id", out).as_deref(), Some("42"));
+        assert_eq!(json_path("build.tags[1]", out).as_deref(), Some("b"));
+        assert_eq!(json_path("build.tags.0", out).as_deref(), Some("a"));
+        assert_eq!(json_path(".build.tags", out).as_deref(), Some(r#"["a","b"]"#));
+        assert_eq!(json_path(".build.missing", out), None);
+        assert_eq!(json_path(".build.tags[x]", out), None);
+    }
+
+    #[test]
+    fn test_json_path_last_line() {
+        let out = "compiling\n{\"url\":\"https://example.test\"}\n\n";
+        assert_eq!(json_path(".url", out).as_deref(), Some("https://example.test"));
+        assert_eq!(json_path(".url", "not json"), None);
+    }
+
+    #[test]
+    fn test_file_last_line() {
+        let path = std::env::temp_dir().join(format!("pawl-test-lastline-{}", std::process::id()));
+        std::fs::write(&path, "first\nsecond  \n\n").unwrap();
+        assert_eq!(file_last_line(&path).as_deref(), Some("second"));
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(file_last_line(&path), None);
+    }
+}
@@ -1,3 +1,6 @@
+pub mod condition;
+pub mod extract;
+pub mod process;
 pub mod project;
 pub mod shell;
 pub mod variable;
@@ -0,0 +1,113 @@
+use std::process::Command;
+
+/// Identity of the process that executes a synchronous step.
+/// Recorded in `step_started` so a later `pawl start`/`pawl status` can tell
+/// whether the step is still being driven or was orphaned by a crash/reboot.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProcessOwner {
+    pub pid: u32,
+    pub hostname: String,
+    pub boot_id: Option<String>,
+}
+
+impl ProcessOwner {
+    /// Owner record for the current process
+    pub fn current() -> Self {
+        Self {
+            pid: std::process::id(),
+            hostname: hostname(),
+            boot_id: boot_id(),
+        }
+    }
+
+    /// Whether the owner is known to be gone.
+    /// Owners on another host are never presumed dead — we can't observe them.
+    pub fn is_dead(&self) -> bool {
+        if self.hostname != hostname() {
+            return false;
+        }
+        if let (Some(recorded), Some(current)) = (&self.boot_id, boot_id())
+            && *recorded != current {
+            return true;
+        }
+        !is_alive(self.pid)
+    }
+
+    /// Whether the owner was recorded on this host during the current boot
+    pub fn same_boot(&self) -> bool {
+        self.hostname == hostname() && self.boot_id == boot_id()
+    }
+}
+
+/// Host name of this machine (empty if unavailable)
+pub fn hostname() -> String {
+    let mut buf = [0u8; 256];
+    let rc = unsafe { libc::gethostname(buf.as_mut_ptr() as *mut libc::c_char, buf.len()) };
+    if rc != 0 {
+        return String::new();
+    }
+    let len = buf.iter().position(|b| *b == 0).unwrap_or(buf.len());
+    String::from_utf8_lossy(&buf[..len]).to_string()
+}
+
+/// Identifier that changes on every boot (Linux: boot_id, macOS: bootsessionuuid)
+pub fn boot_id() -> Option<String> {
+    if let Ok(id) = std::fs::read_to_string("/proc/sys/kernel/random/boot_id") {
+        return Some(id.trim().to_string());
+    }
+    let output = Command::new("sysctl")
+        .args(["-n", "kern.bootsessionuuid"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let id = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (!id.is_empty()).then_some(id)
+}
+
+/// Whether a process with this PID exists (signal 0 probe)
+pub fn is_alive(pid: u32) -> bool {
+    let rc = unsafe { libc::kill(pid as libc::pid_t, 0) };
+    rc == 0 || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_current_owner_is_alive() {
+        let owner = ProcessOwner::current();
+        assert_eq!(owner.pid, std::process::id());
+        assert!(!owner.is_dead());
+    }
+
+    #[test]
+    fn test_reaped_child_is_dead() {
+        let mut child = Command::new("true").spawn().unwrap();
+        let pid = child.id();
+        child.wait().unwrap();
+        let owner = ProcessOwner { pid, ..ProcessOwner::current() };
+        assert!(owner.is_dead());
+    }
+
+    #[test]
+    fn test_other_boot_is_dead() {
+        let owner = ProcessOwner {
+            boot_id: Some("previous-boot".to_string()),
+            ..ProcessOwner::current()
+        };
+        assert_eq!(owner.is_dead(), boot_id().is_some());
+    }
+
+    #[test]
+    fn test_other_host_not_presumed_dead() {
+        let owner = ProcessOwner {
+            pid: u32::MAX / 2,
+            hostname: "some-other-host.invalid".to_string(),
+            boot_id: None,
+        };
+        assert!(!owner.is_dead());
+    }
+}
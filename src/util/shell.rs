@@ -1,9 +1,12 @@
 use anyhow::{Context, Result};
 use std::collections::HashMap;
 use std::io::{BufRead, BufReader};
-use std::path::Path;
-use std::process::{Command, Stdio};
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::os::unix::process::CommandExt as _;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicI32, AtomicU64, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
 
 /// Result of a command execution
 #[derive(Debug)]
@@ -12,8 +15,13 @@ pub struct CommandResult {
     pub stderr: String,
     pub exit_code: i32,
     pub success: bool,
+    /// Killed for exceeding its timeout (exit_code is then TIMEOUT_EXIT_CODE)
+    pub timed_out: bool,
 }
 
+/// Exit code reported for a command killed by its timeout (same as coreutils `timeout`)
+pub const TIMEOUT_EXIT_CODE: i32 = 124;
+
 static STDERR_COUNTER: AtomicU64 = AtomicU64::new(0);
 
 /// Run a shell command with env, streaming stdout line-by-line through a callback.
@@ -62,6 +70,7 @@ pub fn run_command(
         stderr,
         exit_code: status.code().unwrap_or(-1),
         success: status.success(),
+        timed_out: false,
     })
 }
 
@@ -75,10 +84,19 @@ pub fn run_command_success(cmd: &str) -> bool {
 /// forks background processes that inherit stdout — child.wait() returns
 /// as soon as the direct child exits, regardless of grandchild fd inheritance.
 /// The stdout file doubles as the live stream file for dashboard consumption.
+///
+/// The command runs in its own process group; `on_spawn` receives the pgid so it can be
+/// recorded before waiting. If SIGINT/SIGTERM arrives while waiting, the whole group is
+/// terminated (SIGKILL after `grace`) and the signal is re-raised on this process.
+/// If `timeout` elapses first, the group is terminated the same way and the result is
+/// marked `timed_out`.
 pub fn run_command_to_file(
     cmd: &str,
     env: &HashMap<String, String>,
     stdout_path: &Path,
+    grace: Duration,
+    timeout: Option<Duration>,
+    on_spawn: impl FnOnce(u32) -> Result<()>,
 ) -> Result<CommandResult> {
     let id = STDERR_COUNTER.fetch_add(1, Ordering::Relaxed);
     let stderr_path = std::env::temp_dir().join(format!("pawl-{}-{}.stderr", std::process::id(), id));
@@ -95,26 +113,370 @@ pub fn run_command_to_file(
         command.env(key, value);
     }
 
+    let guard = InterruptGuard::install();
+
     let mut child = command
         .stdout(Stdio::from(stdout_out))
         .stderr(Stdio::from(stderr_out))
+        .process_group(0)
         .spawn()
         .with_context(|| format!("Failed to spawn command: {}", cmd))?;
 
-    let status = child.wait().with_context(|| "Failed to wait for child process")?;
+    if let Err(e) = on_spawn(child.id()) {
+        stop_child_group(&mut child, grace);
+        let _ = std::fs::remove_file(&stderr_path);
+        return Err(e);
+    }
+
+    let deadline = timeout.map(|t| Instant::now() + t);
+    let status = match wait_interruptible(&mut child, grace, deadline, guard) {
+        Ok(status) => status,
+        Err(e) => {
+            let _ = std::fs::remove_file(&stderr_path);
+            return Err(e);
+        }
+    };
 
     let stdout = std::fs::read_to_string(stdout_path).unwrap_or_default();
     let stderr = std::fs::read_to_string(&stderr_path).unwrap_or_default();
     let _ = std::fs::remove_file(&stderr_path);
 
-    Ok(CommandResult {
-        stdout,
-        stderr,
-        exit_code: status.code().unwrap_or(-1),
-        success: status.success(),
+    Ok(match status {
+        Some(status) => CommandResult {
+            stdout,
+            stderr,
+            exit_code: status.code().unwrap_or(-1),
+            success: status.success(),
+            timed_out: false,
+        },
+        None => CommandResult {
+            stdout,
+            stderr,
+            exit_code: TIMEOUT_EXIT_CODE,
+            success: false,
+            timed_out: true,
+        },
     })
 }
 
+/// How a process group ended up after terminate_group()
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Termination {
+    /// No process in the group was alive
+    NotRunning,
+    /// The group exited after SIGTERM within the grace period
+    Terminated,
+    /// The group outlived the grace period and was sent SIGKILL
+    Killed,
+}
+
+impl Termination {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::NotRunning => "not_running",
+            Self::Terminated => "terminated",
+            Self::Killed => "killed",
+        }
+    }
+}
+
+/// Whether any process in the group still exists (zombies included)
+pub fn group_alive(pgid: u32) -> bool {
+    let rc = unsafe { libc::kill(-(pgid as libc::pid_t), 0) };
+    rc == 0 || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+fn signal_group(pgid: u32, signal: libc::c_int) {
+    unsafe {
+        libc::kill(-(pgid as libc::pid_t), signal);
+    }
+}
+
+/// SIGTERM a process group we don't own, then SIGKILL whatever is left after `grace`.
+pub fn terminate_group(pgid: u32, grace: Duration) -> Termination {
+    if pgid <= 1 || !group_alive(pgid) {
+        return Termination::NotRunning;
+    }
+    signal_group(pgid, libc::SIGTERM);
+    let deadline = Instant::now() + grace;
+    while Instant::now() < deadline {
+        if !group_alive(pgid) {
+            return Termination::Terminated;
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+    signal_group(pgid, libc::SIGKILL);
+    Termination::Killed
+}
+
+/// Same as terminate_group, but for our own child: reaps the leader so its zombie
+/// doesn't keep the group looking alive.
+fn stop_child_group(child: &mut Child, grace: Duration) {
+    stop_child_groups(&mut [child], grace);
+}
+
+/// stop_child_group for several children at once, sharing one grace period.
+fn stop_child_groups(children: &mut [&mut Child], grace: Duration) {
+    for child in children.iter() {
+        signal_group(child.id(), libc::SIGTERM);
+    }
+    let deadline = Instant::now() + grace;
+    let mut reaped = vec![false; children.len()];
+    while Instant::now() < deadline {
+        for (child, reaped) in children.iter_mut().zip(reaped.iter_mut()) {
+            *reaped = *reaped || matches!(child.try_wait(), Ok(Some(_)));
+        }
+        let done = children
+            .iter()
+            .zip(&reaped)
+            .all(|(child, reaped)| *reaped && !group_alive(child.id()));
+        if done {
+            return;
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+    for child in children.iter_mut() {
+        signal_group(child.id(), libc::SIGKILL);
+        let _ = child.wait();
+    }
+}
+
+static PENDING_SIGNAL: AtomicI32 = AtomicI32::new(0);
+
+extern "C" fn record_signal(signal: libc::c_int) {
+    PENDING_SIGNAL.store(signal, Ordering::SeqCst);
+}
+
+/// While alive, SIGINT/SIGTERM are recorded instead of killing us, so the step's
+/// process group (which no longer shares our foreground group) can be taken down first.
+struct InterruptGuard {
+    previous: Vec<(libc::c_int, libc::sighandler_t)>,
+}
+
+impl InterruptGuard {
+    fn install() -> Self {
+        PENDING_SIGNAL.store(0, Ordering::SeqCst);
+        let handler = record_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        let previous = [libc::SIGINT, libc::SIGTERM]
+            .into_iter()
+            .map(|sig| (sig, unsafe { libc::signal(sig, handler) }))
+            .collect();
+        Self { previous }
+    }
+
+    fn pending(&self) -> Option<libc::c_int> {
+        match PENDING_SIGNAL.load(Ordering::SeqCst) {
+            0 => None,
+            sig => Some(sig),
+        }
+    }
+}
+
+impl Drop for InterruptGuard {
+    fn drop(&mut self) {
+        for (sig, handler) in &self.previous {
+            unsafe {
+                libc::signal(*sig, *handler);
+            }
+        }
+    }
+}
+
+/// Wait for a process-group leader, forwarding SIGINT/SIGTERM to its group.
+/// On interrupt: stop the group, restore the previous handlers and re-raise the signal.
+/// If the previous disposition ignores it, returns an error instead.
+/// Returns None if `deadline` passed and the group was stopped.
+fn wait_interruptible(
+    child: &mut Child,
+    grace: Duration,
+    deadline: Option<Instant>,
+    guard: InterruptGuard,
+) -> Result<Option<ExitStatus>> {
+    loop {
+        if let Some(sig) = guard.pending() {
+            stop_child_group(child, grace);
+            drop(guard);
+            unsafe {
+                libc::raise(sig);
+            }
+            anyhow::bail!("Interrupted by signal {}", sig);
+        }
+        if let Some(status) = child.try_wait().with_context(|| "Failed to wait for child process")? {
+            return Ok(Some(status));
+        }
+        if deadline.is_some_and(|d| Instant::now() >= d) {
+            stop_child_group(child, grace);
+            return Ok(None);
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+}
+
+/// One command of a concurrent batch (see run_batch_to_files)
+pub struct BatchCommand {
+    pub cmd: String,
+    pub env: HashMap<String, String>,
+    pub stdout_path: PathBuf,
+    pub timeout: Option<Duration>,
+}
+
+/// A batch command that has been spawned and not yet reaped
+struct BatchChild {
+    child: Child,
+    stderr_path: PathBuf,
+    deadline: Option<Instant>,
+}
+
+/// Run commands concurrently, each like run_command_to_file: its own process group,
+/// stdout to its file, an optional timeout. `on_spawn(i, pgid)` runs as each starts and
+/// `on_exit(i, result)` as each finishes; if `on_exit` returns true, the remaining
+/// groups are stopped and reported as None. SIGINT/SIGTERM stop every group and are
+/// re-raised, as for a single command.
+pub fn run_batch_to_files(
+    commands: &[BatchCommand],
+    grace: Duration,
+    mut on_spawn: impl FnMut(usize, u32) -> Result<()>,
+    mut on_exit: impl FnMut(usize, &CommandResult) -> Result<bool>,
+) -> Result<Vec<Option<CommandResult>>> {
+    let guard = InterruptGuard::install();
+    let mut running: Vec<Option<BatchChild>> = Vec::new();
+    let mut results: Vec<Option<CommandResult>> = commands.iter().map(|_| None).collect();
+
+    let spawned = (|| -> Result<()> {
+        for (i, command) in commands.iter().enumerate() {
+            let id = STDERR_COUNTER.fetch_add(1, Ordering::Relaxed);
+            let stderr_path = std::env::temp_dir().join(format!("pawl-{}-{}.stderr", std::process::id(), id));
+            let stderr_out = std::fs::File::create(&stderr_path)
+                .with_context(|| "Failed to create stderr temp file")?;
+            let stdout_out = std::fs::File::create(&command.stdout_path)
+                .with_context(|| "Failed to create stdout file")?;
+            let child = Command::new("sh")
+                .arg("-c")
+                .arg(&command.cmd)
+                .envs(&command.env)
+                .stdout(Stdio::from(stdout_out))
+                .stderr(Stdio::from(stderr_out))
+                .process_group(0)
+                .spawn()
+                .with_context(|| format!("Failed to spawn command: {}", command.cmd))?;
+            let pgid = child.id();
+            running.push(Some(BatchChild {
+                child,
+                stderr_path,
+                deadline: command.timeout.map(|t| Instant::now() + t),
+            }));
+            on_spawn(i, pgid)?;
+        }
+        Ok(())
+    })();
+
+    let outcome = spawned.and_then(|()| loop {
+        if let Some(sig) = guard.pending() {
+            stop_batch(&mut running, grace);
+            drop(guard);
+            unsafe {
+                libc::raise(sig);
+            }
+            anyhow::bail!("Interrupted by signal {}", sig);
+        }
+
+        let mut cancel = false;
+        for (i, slot) in running.iter_mut().enumerate() {
+            let Some(batch_child) = slot.as_mut() else { continue };
+            let status = batch_child.child.try_wait().with_context(|| "Failed to wait for child process")?;
+            let timed_out = status.is_none() && batch_child.deadline.is_some_and(|d| Instant::now() >= d);
+            if status.is_none() && !timed_out {
+                continue;
+            }
+            if timed_out {
+                stop_child_group(&mut batch_child.child, grace);
+            }
+            let stdout = std::fs::read_to_string(&commands[i].stdout_path).unwrap_or_default();
+            let stderr = std::fs::read_to_string(&batch_child.stderr_path).unwrap_or_default();
+            let _ = std::fs::remove_file(&batch_child.stderr_path);
+            *slot = None;
+
+            let result = match status {
+                Some(status) => CommandResult {
+                    stdout,
+                    stderr,
+                    exit_code: status.code().unwrap_or(-1),
+                    success: status.success(),
+                    timed_out: false,
+                },
+                None => CommandResult {
+                    stdout,
+                    stderr,
+                    exit_code: TIMEOUT_EXIT_CODE,
+                    success: false,
+                    timed_out: true,
+                },
+            };
+            cancel = on_exit(i, &result)?;
+            results[i] = Some(result);
+            if cancel {
+                break;
+            }
+        }
+        if cancel || running.iter().all(Option::is_none) {
+            break Ok(());
+        }
+        thread::sleep(Duration::from_millis(20));
+    });
+
+    // Cancelled, or bailing out on an error: nothing may outlive the batch
+    stop_batch(&mut running, grace);
+    outcome.map(|()| results)
+}
+
+/// Stop every still-running command of a batch and drop its stderr capture
+fn stop_batch(running: &mut [Option<BatchChild>], grace: Duration) {
+    let mut children: Vec<&mut Child> = Vec::new();
+    for batch_child in running.iter_mut().flatten() {
+        let _ = std::fs::remove_file(&batch_child.stderr_path);
+        children.push(&mut batch_child.child);
+    }
+    if !children.is_empty() {
+        stop_child_groups(&mut children, grace);
+    }
+    for slot in running.iter_mut() {
+        *slot = None;
+    }
+}
+
+/// Wait for a child that shares our process group (viewport steps keep the terminal's
+/// foreground group). If `timeout` elapses, SIGTERM the child itself, SIGKILL after `grace`,
+/// and return None.
+pub fn wait_with_timeout(child: &mut Child, timeout: Option<Duration>, grace: Duration) -> Result<Option<ExitStatus>> {
+    let Some(timeout) = timeout else {
+        return Ok(Some(child.wait().with_context(|| "Failed to wait for child process")?));
+    };
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(status) = child.try_wait().with_context(|| "Failed to wait for child process")? {
+            return Ok(Some(status));
+        }
+        if Instant::now() >= deadline {
+            break;
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+    let pid = child.id() as libc::pid_t;
+    unsafe {
+        libc::kill(pid, libc::SIGTERM);
+    }
+    let deadline = Instant::now() + grace;
+    while Instant::now() < deadline {
+        if child.try_wait().with_context(|| "Failed to wait for child process")?.is_some() {
+            return Ok(None);
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+    let _ = child.kill();
+    let _ = child.wait();
+    Ok(None)
+}
+
 /// Spawn a command in the background (fire-and-forget)
 pub fn spawn_background(cmd: &str) -> Result<()> {
     Command::new("sh")
@@ -162,6 +524,122 @@ mod tests {
         assert_eq!(result.stdout.trim(), "line1\nline2\nline3");
     }
 
+    #[test]
+    fn test_run_command_to_file_reports_pgid() {
+        let dir = std::env::temp_dir().join(format!("pawl-test-pgid-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let out = dir.join("out");
+        let mut spawned = None;
+        let result = run_command_to_file(
+            "echo $$",
+            &HashMap::new(),
+            &out,
+            Duration::from_secs(1),
+            None,
+            |pgid| {
+                spawned = Some(pgid);
+                Ok(())
+            },
+        )
+        .unwrap();
+        // The shell leads its own process group: pid == pgid
+        assert_eq!(result.stdout.trim().parse::<u32>().ok(), spawned);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_run_batch_cancels_rest() {
+        let dir = std::env::temp_dir().join(format!("pawl-test-batch-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let command = |cmd: &str, name: &str| BatchCommand {
+            cmd: cmd.to_string(),
+            env: HashMap::new(),
+            stdout_path: dir.join(name),
+            timeout: None,
+        };
+        let commands = [command("echo fast; exit 3", "a"), command("sleep 30", "b")];
+        let start = Instant::now();
+        let mut spawned = Vec::new();
+        let results = run_batch_to_files(
+            &commands,
+            Duration::from_secs(1),
+            |i, _| {
+                spawned.push(i);
+                Ok(())
+            },
+            |_, result| Ok(!result.success),
+        )
+        .unwrap();
+        assert!(start.elapsed() < Duration::from_secs(10));
+        assert_eq!(spawned, vec![0, 1]);
+        let first = results[0].as_ref().unwrap();
+        assert_eq!(first.exit_code, 3);
+        assert_eq!(first.stdout.trim(), "fast");
+        assert!(results[1].is_none());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_run_command_to_file_timeout() {
+        let dir = std::env::temp_dir().join(format!("pawl-test-timeout-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let out = dir.join("out");
+        let start = Instant::now();
+        let result = run_command_to_file(
+            "echo started; sleep 30 & wait",
+            &HashMap::new(),
+            &out,
+            Duration::from_secs(1),
+            Some(Duration::from_millis(300)),
+            |_| Ok(()),
+        )
+        .unwrap();
+        assert!(start.elapsed() < Duration::from_secs(10));
+        assert!(result.timed_out);
+        assert!(!result.success);
+        assert_eq!(result.exit_code, TIMEOUT_EXIT_CODE);
+        assert_eq!(result.stdout.trim(), "started");
+
+        let result = run_command_to_file(
+            "exit 3", &HashMap::new(), &out, Duration::from_secs(1), Some(Duration::from_secs(30)), |_| Ok(()),
+        )
+        .unwrap();
+        assert!(!result.timed_out);
+        assert_eq!(result.exit_code, 3);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_terminate_group() {
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg("sleep 30")
+            .process_group(0)
+            .spawn()
+            .unwrap();
+        let pgid = child.id();
+        let reaper = thread::spawn(move || child.wait());
+        assert_eq!(terminate_group(pgid, Duration::from_secs(5)), Termination::Terminated);
+        reaper.join().unwrap().unwrap();
+        assert!(!group_alive(pgid));
+        assert_eq!(terminate_group(pgid, Duration::from_secs(1)), Termination::NotRunning);
+    }
+
+    #[test]
+    fn test_terminate_group_escalates_to_kill() {
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg("trap '' TERM; while :; do sleep 0.1; done")
+            .process_group(0)
+            .spawn()
+            .unwrap();
+        let pgid = child.id();
+        let reaper = thread::spawn(move || child.wait());
+        thread::sleep(Duration::from_millis(200));
+        assert_eq!(terminate_group(pgid, Duration::from_millis(300)), Termination::Killed);
+        reaper.join().unwrap().unwrap();
+    }
+
     #[test]
     fn test_run_command_stderr() {
         let result = run_command("echo out; echo err >&2; exit 42", &HashMap::new(), |_| {}).unwrap();
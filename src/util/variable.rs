@@ -1,5 +1,13 @@
 use std::collections::HashMap;
 
+/// Variables `Project::context_for` gives every command, in the order it sets them
+pub const CONTEXT_VARS: [&str; 8] = [
+    "task", "workflow", "session", "project_root", "step", "step_index", "log_file", "run_id",
+];
+
+/// A step's retry state, on top of the context (see `Context::retry`)
+pub const RETRY_VARS: [&str; 2] = ["retry_count", "last_verify_output"];
+
 /// Context for variable expansion (builder pattern)
 #[derive(Debug, Clone)]
 pub struct Context {
@@ -16,6 +24,16 @@ impl Context {
         self
     }
 
+    /// Add a step's retry state: its retry count, and the last failure's feedback if any
+    pub fn retry(self, retry_count: usize, feedback: Option<&str>) -> Self {
+        let [count_var, feedback_var] = RETRY_VARS;
+        let ctx = self.var(count_var, retry_count.to_string());
+        match feedback {
+            Some(fb) => ctx.var(feedback_var, fb),
+            None => ctx,
+        }
+    }
+
     /// Add a variable with an owned key (for user-defined vars from config.vars)
     pub fn var_owned(mut self, name: String, value: String) -> Self {
         let key: &'static str = Box::leak(name.into_boxed_str());
@@ -36,18 +54,18 @@ impl Context {
         let bytes = template.as_bytes();
         let mut i = 0;
         while i < bytes.len() {
-            if i + 1 < bytes.len() && bytes[i] == b'$' && bytes[i + 1] == b'{' {
-                if let Some(end) = template[i + 2..].find('}') {
-                    let key = &template[i + 2..i + 2 + end];
-                    if let Some(val) = self.get(key) {
-                        result.push_str(val);
-                    } else {
-                        // Unknown var — keep literal
-                        result.push_str(&template[i..i + 2 + end + 1]);
-                    }
-                    i += 2 + end + 1;
-                    continue;
+            if i + 1 < bytes.len() && bytes[i] == b'$' && bytes[i + 1] == b'{'
+                && let Some(end) = template[i + 2..].find('}')
+            {
+                let key = &template[i + 2..i + 2 + end];
+                if let Some(val) = self.get(key) {
+                    result.push_str(val);
+                } else {
+                    // Unknown var — keep literal
+                    result.push_str(&template[i..i + 2 + end + 1]);
                 }
+                i += 2 + end + 1;
+                continue;
             }
             result.push(bytes[i] as char);
             i += 1;
@@ -55,11 +73,16 @@ impl Context {
         result
     }
 
-    /// Convert to environment variables for subprocess
+    /// Convert to environment variables for subprocess.
+    /// Keys are uppercased and anything outside [A-Z0-9_] becomes '_'
+    /// (steps.build.version → PAWL_STEPS_BUILD_VERSION).
     pub fn to_env_vars(&self) -> HashMap<String, String> {
         self.vars.iter().map(|(k, v)| {
-            let env_key = format!("PAWL_{}", k.to_ascii_uppercase());
-            (env_key, v.clone())
+            let name: String = k
+                .chars()
+                .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+                .collect();
+            (format!("PAWL_{}", name), v.clone())
         }).collect()
     }
 
@@ -162,6 +185,18 @@ mod tests {
         assert_eq!(env.get("PAWL_BASE_BRANCH"), Some(&"main".to_string()));
     }
 
+    #[test]
+    fn test_env_vars_step_outputs() {
+        let ctx = Context::build()
+            .var_owned("steps.build.version".to_string(), "1.2.0".to_string())
+            .var_owned("steps.unit-test.report".to_string(), "ok".to_string());
+
+        assert_eq!(ctx.expand("v${steps.build.version}"), "v1.2.0");
+        let env = ctx.to_env_vars();
+        assert_eq!(env.get("PAWL_STEPS_BUILD_VERSION"), Some(&"1.2.0".to_string()));
+        assert_eq!(env.get("PAWL_STEPS_UNIT_TEST_REPORT"), Some(&"ok".to_string()));
+    }
+
     #[test]
     fn test_get() {
         let ctx = Context::build()
@@ -254,4 +289,12 @@ mod tests {
         // b resolves to "${a}", single-pass stops there
         assert_eq!(ctx.expand("${b}"), "${a}");
     }
+
+    #[test]
+    fn test_retry_vars() {
+        let ctx = Context::build().retry(0, None);
+        assert_eq!(ctx.expand("${retry_count}:${last_verify_output}"), "0:${last_verify_output}");
+        let ctx = Context::build().retry(2, Some("boom"));
+        assert_eq!(ctx.expand("${retry_count}:${last_verify_output}"), "2:boom");
+    }
 }
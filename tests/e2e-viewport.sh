@@ -469,6 +469,46 @@ test_vp_last_feedback_propagated() {
   rm -f "$marker" "$counter"
 }
 
+test_vp_fail_terminal() {
+  setup_vp_project "fail-term" '{"workflow":[{"name":"work","run":"sleep 60","in_viewport":true}]}'
+  create_task t1
+  pawl start t1 >/dev/null 2>&1
+  wait_status t1 "running" 5 || return 1
+  sleep 0.3
+  local out
+  out=$(pawl fail -m "agent gave up" --exit-code 3 t1 2>/dev/null)
+  assert_json "$out" ".status" "failed" || return 1
+  local log_out
+  log_out=$(pawl log t1 2>/dev/null)
+  assert_json_num "$log_out" ".exit_code" "3" || return 1
+  assert_contains "$log_out" "agent gave up"
+}
+
+test_vp_fail_on_fail_manual() {
+  setup_vp_project "fail-ofm" '{"workflow":[{"name":"work","run":"sleep 60","in_viewport":true,"on_fail":"manual"}]}'
+  create_task t1
+  pawl start t1 >/dev/null 2>&1
+  wait_status t1 "running" 5 || return 1
+  sleep 0.3
+  local out
+  out=$(pawl fail t1 2>/dev/null)
+  assert_json "$out" ".status" "waiting" || return 1
+  assert_json "$out" ".message" "on_fail_manual"
+}
+
+test_vp_fail_retry_feedback() {
+  local marker="${E2E_TMP}/pawl-e2e-vp-fail-marker"
+  local first="${E2E_TMP}/pawl-e2e-vp-fail-first"
+  rm -f "$marker" "$first"
+  setup_vp_project "fail-retry" "{\"workflow\":[{\"name\":\"build\",\"run\":\"if [ -f $first ]; then echo \$PAWL_LAST_VERIFY_OUTPUT > $marker; else touch $first; pawl fail -m AGENT_REASON \$PAWL_TASK; fi\",\"in_viewport\":true,\"on_fail\":\"retry\"}]}"
+  create_task t1
+  pawl start t1 >/dev/null 2>&1
+  wait_status t1 "completed" 15 || return 1
+  [ -f "$marker" ] || { echo "marker file not created"; return 1; }
+  assert_contains "$(cat "$marker")" "AGENT_REASON" || return 1
+  rm -f "$marker" "$first"
+}
+
 # ═══════════════════════════════════════════════════════
 # Test Registry — label shown in report
 # ═══════════════════════════════════════════════════════
@@ -501,6 +541,9 @@ TESTS=(
   "test_vp_multi_task|two tasks in same session → isolated windows"
   "test_vp_events_follow|events --follow captures live events"
   "test_vp_last_feedback_propagated|last_feedback available in retry context"
+  "test_vp_fail_terminal|fail -m --exit-code → failed, reason logged"
+  "test_vp_fail_on_fail_manual|fail with on_fail=manual → waiting"
+  "test_vp_fail_retry_feedback|fail with on_fail=retry → reason in last_verify_output"
 )
 
 # ═══════════════════════════════════════════════════════
@@ -311,8 +311,82 @@ test_verify_manual_done_completes() {
   pass
 }
 
+test_reject_verify_manual_terminal() {
+  begin_test "reject verify=manual → failed/rejected"
+  setup_project "rej1" '{"workflow":[{"name":"build","run":"true","verify":"manual"}]}'
+  create_task t1
+  pawl start t1 >/dev/null 2>&1
+  local out
+  out=$(pawl reject t1 -m "not good enough" 2>/dev/null)
+  assert_json "$out" ".status" "failed" || return
+  assert_json "$out" ".message" "rejected" || return
+  local log
+  log=$(pawl log t1 2>/dev/null)
+  assert_json "$log" ".type" "step_rejected" || return
+  assert_json "$log" ".message" "not good enough" || return
+  pass
+}
+
+test_reject_retry_feedback() {
+  begin_test "reject + on_fail=retry → re-run sees reason as last_verify_output"
+  local marker="${E2E_TMP}/pawl-e2e-reject-marker"
+  rm -f "$marker"
+  setup_project "rej2" "{\"workflow\":[{\"name\":\"build\",\"run\":\"echo \\\"[\$PAWL_LAST_VERIFY_OUTPUT]\\\" >> $marker\",\"verify\":\"manual\",\"on_fail\":\"retry\"}]}"
+  create_task t1
+  pawl start t1 >/dev/null 2>&1
+  local out
+  out=$(pawl reject t1 -m "add tests" 2>/dev/null)
+  assert_json "$out" ".status" "waiting" || return
+  assert_json "$out" ".message" "verify_manual" || return
+  assert_json_num "$out" ".retry_count" "1" || return
+  [ "$(tail -n 1 "$marker")" = "[add tests]" ] || { fail "feedback not propagated: $(cat "$marker")"; return; }
+  out=$(pawl done t1 2>/dev/null)
+  assert_json "$out" ".status" "completed" || return
+  rm -f "$marker"
+  pass
+}
+
+test_reject_gate() {
+  begin_test "reject gate → failed"
+  setup_project "rej3" '{"workflow":[{"name":"approve"},{"name":"ship","run":"true"}]}'
+  create_task t1
+  pawl start t1 >/dev/null 2>&1
+  local out
+  out=$(pawl reject t1 -m "no" 2>/dev/null)
+  assert_json "$out" ".status" "failed" || return
+  assert_json_num "$out" ".current_step" "0" || return
+  pass
+}
+
+test_reject_on_fail_manual() {
+  begin_test "reject on_fail=manual decision → failed"
+  setup_project "rej4" '{"workflow":[{"name":"build","run":"false","on_fail":"manual"}]}'
+  create_task t1
+  pawl start t1 >/dev/null 2>&1
+  local out
+  out=$(pawl reject t1 -m "give up" 2>/dev/null)
+  assert_json "$out" ".status" "failed" || return
+  pass
+}
+
+test_reject_not_waiting() {
+  begin_test "reject completed → exit 2"
+  setup_project "rej5" '{"workflow":[{"name":"a","run":"true"}]}'
+  create_task t1
+  pawl start t1 >/dev/null 2>&1
+  local rc=0
+  pawl reject t1 -m "late" >/dev/null 2>&1 || rc=$?
+  assert_exit 2 "$rc" || return
+  pass
+}
+
 test_verify_manual_yields
 test_verify_manual_done_completes
+test_reject_verify_manual_terminal
+test_reject_retry_feedback
+test_reject_gate
+test_reject_on_fail_manual
+test_reject_not_waiting
 
 # ═══════════════════════════════════════════════════════
 # 5. Verify Command
@@ -396,9 +470,57 @@ test_retry_verify_fail() {
   pass
 }
 
+test_retry_delay_recorded() {
+  begin_test "retry_delay → step_reset records retry_at, waits"
+  local counter="${E2E_TMP}/pawl-e2e-retry-delay-count"
+  rm -f "$counter"
+  setup_project "retry4" "{\"workflow\":[{\"name\":\"build\",\"run\":\"count=\$(cat $counter 2>/dev/null || echo 0); count=\$((count+1)); echo \$count > $counter; [ \$count -ge 2 ]\",\"on_fail\":\"retry\",\"retry_delay\":1}]}"
+  create_task t1
+  local start_s out
+  start_s=$(date +%s)
+  out=$(pawl start t1 2>/dev/null)
+  assert_json "$out" ".status" "completed" || return
+  [ $(( $(date +%s) - start_s )) -ge 1 ] || { fail "retried without waiting"; return; }
+  local reset
+  reset=$(pawl log --all t1 2>/dev/null | jq -c 'select(.type == "step_reset")')
+  assert_json "$reset" ".auto" "true" || return
+  [ "$(echo "$reset" | jq -r '.retry_at')" != "null" ] || { fail "retry_at missing"; return; }
+  rm -f "$counter"
+  pass
+}
+
+test_no_retry_on_exit_code() {
+  begin_test "no_retry_on_exit_codes → fails without retrying"
+  setup_project "retry5" '{"workflow":[{"name":"build","run":"exit 2","on_fail":"retry","no_retry_on_exit_codes":[2]}]}'
+  create_task t1
+  local out
+  out=$(pawl start t1 2>/dev/null)
+  assert_json "$out" ".status" "failed" || return
+  assert_json_num "$out" ".retry_count" "0" || return
+  pass
+}
+
+test_retry_on_exit_codes_filter() {
+  begin_test "retry_on_exit_codes → only listed codes retry"
+  local counter="${E2E_TMP}/pawl-e2e-retry-on-count"
+  rm -f "$counter"
+  setup_project "retry6" "{\"workflow\":[{\"name\":\"build\",\"run\":\"count=\$(cat $counter 2>/dev/null || echo 0); count=\$((count+1)); echo \$count > $counter; exit \$((count == 1 ? 75 : 1))\",\"on_fail\":\"retry\",\"retry_on_exit_codes\":[75]}]}"
+  create_task t1
+  local out
+  out=$(pawl start t1 2>/dev/null)
+  assert_json "$out" ".status" "failed" || return
+  # 75 retried once, then 1 is terminal
+  [ "$(cat "$counter")" = "2" ] || { fail "expected 2 attempts, got $(cat "$counter")"; return; }
+  rm -f "$counter"
+  pass
+}
+
 test_retry_eventual_success
 test_retry_exhaustion
 test_retry_verify_fail
+test_retry_delay_recorded
+test_no_retry_on_exit_code
+test_retry_on_exit_codes_filter
 
 # ═══════════════════════════════════════════════════════
 # 7. on_fail manual
@@ -470,8 +592,59 @@ test_skip_multiple() {
   pass
 }
 
+test_when_false_skips() {
+  begin_test "when comparison false → skipped with reason"
+  setup_project "when1" '{"vars":{"branch":"feature"},"workflow":[{"name":"deploy","run":"false","when":"${branch} == main"},{"name":"fin","run":"true"}]}'
+  create_task t1
+  local out
+  out=$(pawl start t1 2>/dev/null)
+  assert_json "$out" ".status" "completed" || return
+  local detail
+  detail=$(pawl status t1 2>/dev/null)
+  assert_json "$detail" ".workflow[0].status" "skipped" || return
+  assert_json "$detail" ".workflow[0].reason" "when '\${branch} == main' is false" || return
+  pass
+}
+
+test_when_true_runs() {
+  begin_test "when comparison true → step runs"
+  setup_project "when2" '{"vars":{"branch":"main"},"workflow":[{"name":"deploy","run":"false","when":"${branch} == main"}]}'
+  create_task t1
+  local out
+  out=$(pawl start t1 2>/dev/null)
+  assert_json "$out" ".status" "failed" || return
+  pass
+}
+
+test_when_shell_predicate() {
+  begin_test "when shell predicate → evaluated with PAWL_ env"
+  setup_project "when3" '{"workflow":[{"name":"only-t2","run":"false","when":"test \"$PAWL_TASK\" = t2"},{"name":"fin","run":"true"}]}'
+  create_task t1
+  local out
+  out=$(pawl start t1 2>/dev/null)
+  assert_json "$out" ".status" "completed" || return
+  pass
+}
+
+test_when_retry_count() {
+  begin_test "when retry_count >= 1 → skipped on first attempt"
+  setup_project "when4" '{"workflow":[{"name":"build","run":"true"},{"name":"cleanup","run":"false","when":"retry_count >= 1"}]}'
+  create_task t1
+  local out
+  out=$(pawl start t1 2>/dev/null)
+  assert_json "$out" ".status" "completed" || return
+  local detail
+  detail=$(pawl status t1 2>/dev/null)
+  assert_json "$detail" ".workflow[1].status" "skipped" || return
+  pass
+}
+
 test_skip_step
 test_skip_multiple
+test_when_false_skips
+test_when_true_runs
+test_when_shell_predicate
+test_when_retry_count
 
 # ═══════════════════════════════════════════════════════
 # 9. Stop
@@ -510,9 +683,33 @@ test_stop_completed() {
   pass
 }
 
+test_stop_running_sync_step() {
+  begin_test "stop running sync step → terminates process group"
+  local pidfile="${E2E_TMP}/pawl-e2e-stop4-pid"
+  rm -f "$pidfile"
+  setup_project "stop4" "{\"workflow\":[{\"name\":\"slow\",\"run\":\"sleep 300 & echo \$! > $pidfile; wait\"}]}"
+  create_task t1
+  pawl start t1 >/dev/null 2>&1 &
+  local pawl_pid=$!
+  for _ in $(seq 1 50); do [ -s "$pidfile" ] && break; sleep 0.1; done
+  local out
+  out=$(pawl stop t1 2>/dev/null)
+  wait "$pawl_pid" 2>/dev/null || true
+  assert_json "$out" ".status" "stopped" || return
+  local log
+  log=$(cat .pawl/logs/t1.jsonl)
+  assert_contains "$log" '"kill":"terminated"' || return
+  assert_not_contains "$log" '"type":"step_finished"' || return
+  # The backgrounded grandchild must be gone too
+  if kill -0 "$(cat "$pidfile")" 2>/dev/null; then fail "grandchild still running"; return; fi
+  rm -f "$pidfile"
+  pass
+}
+
 test_stop_waiting
 test_stop_pending
 test_stop_completed
+test_stop_running_sync_step
 
 # ═══════════════════════════════════════════════════════
 # 10. Reset
@@ -731,10 +928,52 @@ test_config_vars_as_env() {
   pass
 }
 
+test_step_outputs_regex_json() {
+  begin_test "step outputs (regex, json) → \${steps.*} and PAWL_STEPS_* in later steps"
+  local marker="${E2E_TMP}/pawl-e2e-outputs-marker"
+  rm -f "$marker"
+  setup_project "outputs1" "{\"workflow\":[{\"name\":\"build\",\"run\":\"echo 'version: 2.3.1'\",\"outputs\":{\"version\":{\"regex\":\"version: (\\\\S+)\"}}},{\"name\":\"meta\",\"run\":\"echo '{\\\"id\\\":7}'\",\"outputs\":{\"id\":{\"json\":\".id\"}}},{\"name\":\"use\",\"run\":\"echo \${steps.build.version}:\$PAWL_STEPS_META_ID > $marker\"}]}"
+  create_task t1
+  local out
+  out=$(pawl start t1 2>/dev/null)
+  assert_json "$out" ".status" "completed" || return
+  [ "$(cat "$marker")" = "2.3.1:7" ] || { fail "got '$(cat "$marker")'"; return; }
+  local finished
+  finished=$(pawl log --step 0 t1 2>/dev/null | jq -c 'select(.type == "step_finished")')
+  assert_json "$finished" ".outputs.version" "2.3.1" || return
+  rm -f "$marker"
+  pass
+}
+
+test_step_outputs_file() {
+  begin_test "step output from file last line"
+  local marker="${E2E_TMP}/pawl-e2e-outputs-file"
+  rm -f "$marker"
+  setup_project "outputs2" "{\"workflow\":[{\"name\":\"write\",\"run\":\"printf 'a\\\\nsha-123\\\\n' > out-\${task}.txt\",\"outputs\":{\"sha\":{\"file\":\"out-\${task}.txt\"}}},{\"name\":\"use\",\"run\":\"echo \${steps.write.sha} > $marker\"}]}"
+  create_task t1
+  pawl start t1 >/dev/null 2>&1
+  [ "$(cat "$marker")" = "sha-123" ] || { fail "got '$(cat "$marker")'"; return; }
+  rm -f "$marker"
+  pass
+}
+
+test_step_outputs_invalid_spec() {
+  begin_test "output with two sources → load error"
+  setup_project "outputs3" '{"workflow":[{"name":"a","run":"true","outputs":{"x":{"regex":"a","json":".a"}}}]}'
+  create_task t1
+  local rc=0
+  pawl start t1 >/dev/null 2>&1 || rc=$?
+  [ "$rc" != "0" ] || { fail "expected failure"; return; }
+  pass
+}
+
 test_intrinsic_vars
 test_env_vars
 test_config_vars
 test_config_vars_as_env
+test_step_outputs_regex_json
+test_step_outputs_file
+test_step_outputs_invalid_spec
 
 # ═══════════════════════════════════════════════════════
 # 14. Event Hooks
@@ -849,11 +1088,64 @@ test_log_pending() {
   pass
 }
 
+test_fail_pending() {
+  begin_test "fail pending → exit 2"
+  setup_project "failp" '{"workflow":[{"name":"a","run":"true"}]}'
+  create_task t1
+  local rc=0
+  pawl fail t1 >/dev/null 2>&1 || rc=$?
+  assert_exit 2 "$rc" || return
+  pass
+}
+
+test_fail_waiting() {
+  begin_test "fail waiting gate → exit 2"
+  setup_project "failw" '{"workflow":[{"name":"gate"}]}'
+  create_task t1
+  pawl start t1 >/dev/null 2>&1
+  local rc=0
+  pawl fail t1 >/dev/null 2>&1 || rc=$?
+  assert_exit 2 "$rc" || return
+  pass
+}
+
+test_fail_sync_step() {
+  begin_test "fail on a running synchronous step → exit 2, step keeps running"
+  setup_project "fails" '{"workflow":[{"name":"a","run":"touch marker; sleep 30","on_fail":"retry"}]}'
+  create_task t1
+  pawl start t1 >/dev/null 2>&1 &
+  local pawl_pid=$!
+  for _ in $(seq 1 50); do [ -f marker ] && break; sleep 0.1; done
+  local rc=0
+  pawl fail t1 >/dev/null 2>&1 || rc=$?
+  local finished
+  finished=$(jq -s 'map(select(.type == "step_finished")) | length' .pawl/logs/t1.jsonl)
+  pawl stop t1 >/dev/null 2>&1 || true
+  wait "$pawl_pid" 2>/dev/null || true
+  assert_exit 2 "$rc" || return
+  [ "$finished" = "0" ] || { fail "fail settled a synchronous step"; return; }
+  pass
+}
+
+test_fail_exit_code_zero() {
+  begin_test "fail --exit-code 0 → exit 6"
+  setup_project "failz" '{"workflow":[{"name":"gate"}]}'
+  create_task t1
+  local rc=0
+  pawl fail --exit-code 0 t1 >/dev/null 2>&1 || rc=$?
+  assert_exit 6 "$rc" || return
+  pass
+}
+
 test_done_pending
 test_done_completed
 test_done_failed
 test_done_stopped
 test_log_pending
+test_fail_pending
+test_fail_waiting
+test_fail_sync_step
+test_fail_exit_code_zero
 
 # ═══════════════════════════════════════════════════════
 # 16. Log & Events
@@ -882,10 +1174,10 @@ test_log_all() {
   pawl start t1 >/dev/null 2>&1
   local out
   out=$(pawl log --all t1 2>/dev/null)
-  # Should have: task_started + step_finished(a) + step_finished(b) = 3 events
+  # Should have: task_started + (step_started + step_finished) x 2 = 5 events
   local lines
   lines=$(echo "$out" | wc -l | tr -d ' ')
-  [ "$lines" = "3" ] || { fail "expected 3 lines, got $lines"; return; }
+  [ "$lines" = "5" ] || { fail "expected 5 lines, got $lines"; return; }
   assert_contains "$out" '"type":"task_started"' || return
   pass
 }
@@ -899,10 +1191,10 @@ test_log_step_filter() {
   pawl start t1 >/dev/null 2>&1
   local out
   out=$(pawl log --all --step 0 t1 2>/dev/null)
-  # Only step 0 events: step_finished for step 0
+  # Only step 0 events: step_started + step_finished for step 0
   local lines
   lines=$(echo "$out" | wc -l | tr -d ' ')
-  [ "$lines" = "1" ] || { fail "expected 1 line, got $lines"; return; }
+  [ "$lines" = "2" ] || { fail "expected 2 lines, got $lines"; return; }
   assert_contains "$out" '"step":0' || return
   pass
 }
@@ -1122,6 +1414,1391 @@ test_wait_terminal_mismatch
 test_wait_timeout
 test_wait_multi_status
 
+# ═══════════════════════════════════════════════════════
+# 19. Crash Recovery
+# ═══════════════════════════════════════════════════════
+echo "── Crash Recovery ──"
+
+# Start t1 in the background, wait until its step writes $1, then SIGKILL pawl and its child
+crash_during_step() {
+  local marker="$1"
+  pawl start t1 "${@:2}" >/dev/null 2>&1 &
+  local pawl_pid=$!
+  for _ in $(seq 1 50); do [ -f "$marker" ] && break; sleep 0.1; done
+  # Kill pawl first so it can't record the child's death, then the orphaned step
+  local kids
+  kids=$(pgrep -P "$pawl_pid" || true)
+  kill -9 "$pawl_pid" 2>/dev/null || true
+  wait "$pawl_pid" 2>/dev/null || true
+  for kid in $kids; do
+    pkill -9 -P "$kid" 2>/dev/null || true
+    kill -9 "$kid" 2>/dev/null || true
+  done
+}
+
+# Start t1 in the background, wait until every file in $@ exists and each sub-step's
+# start is logged, then SIGKILL only pawl: its step processes keep running, for crash
+# detection to find
+kill_owner_during_step() {
+  pawl start t1 >/dev/null 2>&1 &
+  local pawl_pid=$!
+  local f logged
+  for _ in $(seq 1 50); do
+    for f in "$@"; do [ -f "$f" ] || { sleep 0.1; continue 2; }; done
+    logged=$(grep -c '"sub_step_started"' .pawl/logs/t1.jsonl 2>/dev/null || true)
+    [ "${logged:-0}" -ge $# ] || { sleep 0.1; continue; }
+    break
+  done
+  kill -9 "$pawl_pid" 2>/dev/null || true
+  wait "$pawl_pid" 2>/dev/null || true
+}
+
+# Whether a process is still running (an unreaped zombie doesn't count)
+process_alive() {
+  local stat
+  stat=$(ps -o stat= -p "$1" 2>/dev/null)
+  [ -n "$stat" ] && [[ "$stat" != Z* ]]
+}
+
+test_crash_status_interrupted() {
+  begin_test "owner killed mid-step → status stopped/interrupted"
+  local marker="${E2E_TMP}/pawl-e2e-crash1-marker"
+  rm -f "$marker"
+  setup_project "crash1" "{\"workflow\":[{\"name\":\"slow\",\"run\":\"touch $marker; sleep 30\"}]}"
+  create_task t1
+  crash_during_step "$marker"
+  local out
+  out=$(pawl status t1 2>/dev/null)
+  assert_json "$out" ".status" "stopped" || return
+  assert_json "$out" ".message" "interrupted" || return
+  assert_json_num "$out" ".current_step" "0" || return
+  local log
+  log=$(cat .pawl/logs/t1.jsonl)
+  assert_contains "$log" '"type":"step_started"' || return
+  assert_contains "$log" '"type":"step_interrupted"' || return
+  rm -f "$marker"
+  pass
+}
+
+test_crash_start_resumes() {
+  begin_test "start after crash → re-runs interrupted step, keeps progress"
+  local marker="${E2E_TMP}/pawl-e2e-crash2-marker"
+  local count="${E2E_TMP}/pawl-e2e-crash2-count"
+  rm -f "$marker" "$count"
+  setup_project "crash2" "{\"workflow\":[{\"name\":\"first\",\"run\":\"echo x >> $count\"},{\"name\":\"slow\",\"run\":\"if [ -f $marker ]; then true; else touch $marker; sleep 30; fi\"}]}"
+  create_task t1
+  crash_during_step "$marker"
+  local out
+  out=$(pawl start t1 2>/dev/null)
+  assert_json "$out" ".status" "completed" || return
+  # Step "first" must not have been re-run
+  [ "$(wc -l < "$count" | tr -d ' ')" = "1" ] || { fail "first step re-ran"; return; }
+  rm -f "$marker" "$count"
+  pass
+}
+
+test_crash_during_backoff() {
+  begin_test "crash during retry backoff → resume keeps retry_at"
+  local counter="${E2E_TMP}/pawl-e2e-crash3-count"
+  rm -f "$counter"
+  setup_project "crash3" "{\"workflow\":[{\"name\":\"flaky\",\"run\":\"count=\$(cat $counter 2>/dev/null || echo 0); count=\$((count+1)); echo \$count > $counter; [ \$count -ge 2 ]\",\"on_fail\":\"retry\",\"retry_delay\":2}]}"
+  create_task t1
+  pawl start t1 >/dev/null 2>&1 &
+  local pawl_pid=$!
+  for _ in $(seq 1 50); do grep -q step_reset .pawl/logs/t1.jsonl 2>/dev/null && break; sleep 0.1; done
+  kill -9 "$pawl_pid" 2>/dev/null || true
+  wait "$pawl_pid" 2>/dev/null || true
+  local out
+  out=$(pawl status t1 2>/dev/null)
+  assert_json "$out" ".message" "interrupted" || return
+  out=$(pawl start t1 2>/dev/null)
+  assert_json "$out" ".status" "completed" || return
+  local resets
+  resets=$(pawl log --all t1 2>/dev/null | jq -s -c '[.[] | select(.type == "step_reset")]')
+  assert_json_num "$resets" "length" "2" || return
+  assert_json "$resets" ".[1].retry_at == .[0].retry_at" "true" || return
+  rm -f "$counter"
+  pass
+}
+
+test_crash_keeps_outputs() {
+  begin_test "crash after output captured → resumed step still sees it"
+  local marker="${E2E_TMP}/pawl-e2e-crash4-marker"
+  local result="${E2E_TMP}/pawl-e2e-crash4-result"
+  rm -f "$marker" "$result"
+  setup_project "crash4" "{\"workflow\":[{\"name\":\"tag\",\"run\":\"echo tag=v9\",\"outputs\":{\"tag\":{\"regex\":\"tag=(.*)\"}}},{\"name\":\"slow\",\"run\":\"if [ -f $marker ]; then echo \${steps.tag.tag} > $result; else touch $marker; sleep 30; fi\"}]}"
+  create_task t1
+  crash_during_step "$marker"
+  local out
+  out=$(pawl start t1 2>/dev/null)
+  assert_json "$out" ".status" "completed" || return
+  [ "$(cat "$result")" = "v9" ] || { fail "got '$(cat "$result")'"; return; }
+  rm -f "$marker" "$result"
+  pass
+}
+
+test_crash_keeps_retry_budget() {
+  begin_test "crash mid-retry → resume keeps the retry count"
+  setup_project "crash5" '{"workflow":[{"name":"flaky","run":"n=$(cat count 2>/dev/null || echo 0); echo $((n+1)) > count; if [ $n -eq 1 ]; then touch marker; sleep 30; fi; false","on_fail":"retry","max_retries":2}]}'
+  create_task t1
+  crash_during_step marker
+  local out
+  out=$(pawl start t1 2>/dev/null)
+  assert_json "$out" ".status" "failed" || return
+  # Attempts: fail, crash, then only the one retry left of two
+  [ "$(cat count)" = "4" ] || { fail "ran $(cat count) times, expected 4"; return; }
+  assert_json "$(pawl log --all t1 2>/dev/null | jq -s -c '[.[] | select(.type == "step_reset" and .resumed)] | length')" "." "1" || return
+  pass
+}
+
+test_crash_status_interrupted
+test_crash_start_resumes
+test_crash_keeps_outputs
+test_crash_during_backoff
+test_crash_keeps_retry_budget
+
+# ═══════════════════════════════════════════════════════
+# 20. Timeouts
+# ═══════════════════════════════════════════════════════
+echo "── Timeouts ──"
+
+test_timeout_run_terminal() {
+  begin_test "run exceeds timeout → failed/timed_out, group killed"
+  local child="${E2E_TMP}/pawl-e2e-timeout1-child"
+  rm -f "$child"
+  setup_project "timeout1" "{\"workflow\":[{\"name\":\"hang\",\"run\":\"sleep 30 & echo \$! > $child; wait\",\"timeout\":1}]}"
+  create_task t1
+  local out
+  out=$(pawl start t1 2>/dev/null)
+  assert_json "$out" ".status" "failed" || return
+  assert_json "$out" ".message" "timed_out" || return
+  local log
+  log=$(pawl log t1 2>/dev/null)
+  assert_json "$log" ".timed_out" "true" || return
+  assert_json_num "$log" ".exit_code" "124" || return
+  # The backgrounded grandchild went down with the group
+  if kill -0 "$(cat "$child")" 2>/dev/null; then fail "grandchild still alive"; return; fi
+  rm -f "$child"
+  pass
+}
+
+test_timeout_retry() {
+  begin_test "timeout + on_fail=retry → retried"
+  local counter="${E2E_TMP}/pawl-e2e-timeout2-count"
+  rm -f "$counter"
+  setup_project "timeout2" "{\"workflow\":[{\"name\":\"flaky\",\"run\":\"count=\$(cat $counter 2>/dev/null || echo 0); count=\$((count+1)); echo \$count > $counter; [ \$count -ge 2 ] || sleep 30\",\"timeout\":1,\"on_fail\":\"retry\"}]}"
+  create_task t1
+  local out
+  out=$(pawl start t1 2>/dev/null)
+  assert_json "$out" ".status" "completed" || return
+  rm -f "$counter"
+  pass
+}
+
+test_verify_timeout_manual() {
+  begin_test "verify_timeout + on_fail=manual → waiting/on_fail_manual"
+  setup_project "timeout3" '{"workflow":[{"name":"build","run":"true","verify":"sleep 30","verify_timeout":1,"on_fail":"manual"}]}'
+  create_task t1
+  local out
+  out=$(pawl start t1 2>/dev/null)
+  assert_json "$out" ".status" "waiting" || return
+  assert_json "$out" ".message" "on_fail_manual" || return
+  local log
+  log=$(pawl log t1 --all 2>/dev/null | jq -c 'select(.type == "step_finished")')
+  assert_json "$log" ".timed_out" "true" || return
+  assert_contains "$log" "Verify timed out after 1s" || return
+  pass
+}
+
+test_timeout_run_terminal
+test_timeout_retry
+test_verify_timeout_manual
+
+# ═══════════════════════════════════════════════════════
+# 21. Jumps
+# ═══════════════════════════════════════════════════════
+echo "── Jumps ──"
+
+test_goto_fix_loop() {
+  begin_test "on_fail goto fix → on_success goto test loop, feedback passed"
+  setup_project "goto1" '{"workflow":[{"name":"test","run":"[ -f fixed2 ] || { echo still-failing; exit 1; }","on_fail":{"goto":"fix"},"on_success":{"goto":"ship"}},{"name":"fix","run":"echo \"$PAWL_LAST_VERIFY_OUTPUT\" >> seen; if [ -f fixed1 ]; then touch fixed2; else touch fixed1; fi","on_success":{"goto":"test"}},{"name":"skipped","run":"touch ran"},{"name":"ship","run":"true"}]}'
+  create_task t1
+  local out
+  out=$(pawl start t1 2>/dev/null)
+  assert_json "$out" ".status" "completed" || return
+  local jumps
+  jumps=$(pawl log t1 --all 2>/dev/null | jq -s '[.[] | select(.type == "step_jumped")] | length')
+  [ "$jumps" = "5" ] || { fail "expected 5 jumps, got $jumps"; return; }
+  assert_contains "$(cat seen)" "still-failing" || return
+  if [ -f ran ]; then fail "jumped-over step ran"; return; fi
+  pass
+}
+
+test_goto_jump_limit() {
+  begin_test "max_jumps exhausted → on_fail goto fails the step"
+  setup_project "goto2" '{"max_jumps":2,"workflow":[{"name":"test","run":"false","on_fail":{"goto":"fix"}},{"name":"fix","run":"true","on_success":{"goto":"test"}}]}'
+  create_task t1
+  local out
+  out=$(pawl start t1 2>/dev/null)
+  assert_json "$out" ".status" "failed" || return
+  assert_json_num "$out" ".current_step" "0" || return
+  local jumps
+  jumps=$(pawl log t1 --all 2>/dev/null | jq -s '[.[] | select(.type == "step_jumped")] | length')
+  [ "$jumps" = "2" ] || { fail "expected 2 jumps, got $jumps"; return; }
+  pass
+}
+
+test_goto_unknown_step() {
+  begin_test "goto to an unknown step → config error"
+  setup_project "goto3" '{"workflow":[{"name":"test","run":"false","on_fail":{"goto":"nope"}}]}'
+  create_task t1
+  if pawl start t1 >/dev/null 2>&1; then fail "expected error"; return; fi
+  pass
+}
+
+test_reject_goto() {
+  begin_test "reject with on_fail goto → jumps and resumes"
+  setup_project "goto4" '{"workflow":[{"name":"fix","run":"true"},{"name":"review","verify":"manual","run":"true","on_fail":{"goto":"fix"}}]}'
+  create_task t1
+  pawl start t1 >/dev/null 2>&1
+  local out
+  out=$(pawl reject t1 -m "missing tests" 2>/dev/null)
+  # fix re-ran, review is waiting again
+  assert_json "$out" ".status" "waiting" || return
+  assert_json_num "$out" ".current_step" "1" || return
+  pass
+}
+
+test_goto_fix_loop
+test_goto_jump_limit
+test_goto_unknown_step
+test_reject_goto
+
+# ═══════════════════════════════════════════════════════
+# 22. Finally
+# ═══════════════════════════════════════════════════════
+echo "── Finally ──"
+
+test_finally_completed() {
+  begin_test "completed → finally runs once with \${status}"
+  setup_project "finally1" '{"workflow":[{"name":"work","run":"touch work"}],"finally":[{"name":"cleanup","run":"echo ${status} >> cleaned"}]}'
+  create_task t1
+  local out
+  out=$(pawl start t1 2>/dev/null)
+  assert_json "$out" ".status" "completed" || return
+  [ "$(cat cleaned)" = "completed" ] || { fail "cleanup ran: $(cat cleaned 2>/dev/null)"; return; }
+  local detail
+  detail=$(pawl status t1 2>/dev/null)
+  assert_json "$detail" ".finally[0].status" "success" || return
+  pass
+}
+
+test_finally_failure_keeps_outcome() {
+  begin_test "failed + failing cleanup → task stays failed, cleanup failure reported"
+  setup_project "finally2" '{"workflow":[{"name":"work","run":"exit 3"}],"finally":[{"name":"broken","run":"exit 1"},{"name":"cleanup","run":"touch cleaned"}]}'
+  create_task t1
+  local out
+  out=$(pawl start t1 2>/dev/null)
+  assert_json "$out" ".status" "failed" || return
+  assert_json_num "$out" ".current_step" "0" || return
+  [ -f cleaned ] || { fail "later finally step did not run"; return; }
+  local detail
+  detail=$(pawl status t1 2>/dev/null)
+  assert_json "$detail" ".finally[0].status" "failed" || return
+  assert_json "$detail" ".finally[1].status" "success" || return
+  local log
+  log=$(pawl log t1 --all 2>/dev/null | jq -c 'select(.type == "step_finished")')
+  assert_json_num "$log" ".exit_code" "3" || return
+  pass
+}
+
+test_finally_after_stop() {
+  begin_test "stop → finally runs with status stopped"
+  setup_project "finally3" '{"workflow":[{"name":"approve"},{"name":"work","run":"true"}],"finally":[{"name":"cleanup","run":"echo ${status} > cleaned"}]}'
+  create_task t1
+  pawl start t1 >/dev/null 2>&1
+  [ ! -f cleaned ] || { fail "cleanup ran while waiting"; return; }
+  local out
+  out=$(pawl stop t1 2>/dev/null)
+  assert_json "$out" ".status" "stopped" || return
+  [ "$(cat cleaned)" = "stopped" ] || { fail "cleanup did not run on stop"; return; }
+  pass
+}
+
+test_finally_per_end() {
+  begin_test "reset --step after failure → finally runs again when the workflow ends again"
+  setup_project "finally4" '{"workflow":[{"name":"work","run":"[ -f ok ]"}],"finally":[{"name":"cleanup","run":"echo ${status} >> cleaned"}]}'
+  create_task t1
+  pawl start t1 >/dev/null 2>&1
+  touch ok
+  local out
+  out=$(pawl reset --step t1 2>/dev/null)
+  assert_json "$out" ".status" "completed" || return
+  [ "$(tr '\n' ' ' < cleaned)" = "failed completed " ] || { fail "cleanup runs: $(cat cleaned)"; return; }
+  pass
+}
+
+test_finally_completed
+test_finally_failure_keeps_outcome
+test_finally_after_stop
+test_finally_per_end
+
+# ═══════════════════════════════════════════════════════
+# 23. Rollback
+# ═══════════════════════════════════════════════════════
+echo "── Rollback ──"
+
+test_rollback_on_failure() {
+  begin_test "terminal failure → compensations run newest first"
+  setup_project "rollback1" '{"workflow":[{"name":"provision","run":"true","compensate":"echo provision >> undone"},{"name":"note","run":"true"},{"name":"deploy","run":"true","compensate":"echo ${step} >> undone"},{"name":"verify","run":"false"}]}'
+  create_task t1
+  local out
+  out=$(pawl start t1 2>/dev/null)
+  assert_json "$out" ".status" "failed" || return
+  assert_json "$out" ".message" "rolled_back" || return
+  [ "$(tr '\n' ' ' < undone)" = "deploy provision " ] || { fail "compensation order: $(cat undone)"; return; }
+  local detail
+  detail=$(pawl status t1 2>/dev/null)
+  assert_json "$detail" ".workflow[0].status" "compensated" || return
+  assert_json "$detail" ".workflow[1].status" "success" || return
+  assert_json "$detail" ".workflow[2].status" "compensated" || return
+  assert_json "$detail" ".suggest[0]" "pawl reset t1" || return
+  pass
+}
+
+test_rollback_resume() {
+  begin_test "failed compensation → start refused, pawl rollback continues where it stopped"
+  setup_project "rollback2" '{"workflow":[{"name":"a","run":"true","compensate":"echo a >> undone"},{"name":"b","run":"true","compensate":"[ -f ok ] && echo b >> undone"},{"name":"c","run":"false"}]}'
+  create_task t1
+  local out
+  out=$(pawl start t1 2>/dev/null)
+  assert_json "$out" ".message" "rollback_failed" || return
+  [ ! -f undone ] || { fail "rollback went past the failed compensation"; return; }
+  if pawl start t1 >/dev/null 2>&1; then fail "start allowed mid-rollback"; return; fi
+  touch ok
+  out=$(pawl rollback t1 2>/dev/null)
+  assert_json "$out" ".message" "rolled_back" || return
+  [ "$(tr '\n' ' ' < undone)" = "b a " ] || { fail "compensations: $(cat undone)"; return; }
+  pass
+}
+
+test_rollback_manual() {
+  begin_test "pawl rollback on a completed task"
+  setup_project "rollback3" '{"workflow":[{"name":"release","run":"echo v2 > current","compensate":"echo v1 > current"}]}'
+  create_task t1
+  pawl start t1 >/dev/null 2>&1
+  local out
+  out=$(pawl rollback t1 2>/dev/null)
+  assert_json "$out" ".status" "failed" || return
+  assert_json "$out" ".message" "rolled_back" || return
+  [ "$(cat current)" = "v1" ] || { fail "not compensated"; return; }
+  local rb
+  rb=$(pawl log t1 --all 2>/dev/null | jq -c 'select(.type == "rollback_started")')
+  assert_json "$rb" ".reason" "manual" || return
+  pass
+}
+
+test_rollback_on_failure
+test_rollback_resume
+test_rollback_manual
+
+# ═══════════════════════════════════════════════════════
+# 24. Parallel Groups
+# ═══════════════════════════════════════════════════════
+echo "── Parallel Groups ──"
+
+test_parallel_all() {
+  begin_test "parallel join=all → sub-steps run concurrently, group completes"
+  # Each sub-step only passes if it sees the other one running
+  setup_project "par1" '{"workflow":[{"name":"checks","parallel":[{"name":"lint","run":"touch lint; for i in $(seq 50); do [ -f test ] && exit 0; sleep 0.1; done; exit 1"},{"name":"test","run":"touch test; for i in $(seq 50); do [ -f lint ] && exit 0; sleep 0.1; done; exit 1"}]},{"name":"after","run":"true"}]}'
+  create_task t1
+  local out
+  out=$(pawl start t1 2>/dev/null)
+  assert_json "$out" ".status" "completed" || return
+  local detail
+  detail=$(pawl status t1 2>/dev/null)
+  assert_json "$detail" ".workflow[0].step_type" "parallel" || return
+  assert_json "$detail" ".workflow[0].sub_steps[0].status" "success" || return
+  assert_json "$detail" ".workflow[0].sub_steps[1].status" "success" || return
+  pass
+}
+
+test_parallel_fail_fast() {
+  begin_test "fail_fast → siblings cancelled, failure becomes feedback"
+  setup_project "par2" '{"workflow":[{"name":"checks","fail_fast":true,"parallel":[{"name":"typecheck","run":"echo type error >&2; exit 2"},{"name":"slow","run":"sleep 30; touch slow"}]}]}'
+  create_task t1
+  local start_s end_s out
+  start_s=$(date +%s)
+  out=$(pawl start t1 2>/dev/null)
+  end_s=$(date +%s)
+  assert_json "$out" ".status" "failed" || return
+  [ $((end_s - start_s)) -lt 15 ] || { fail "siblings not cancelled"; return; }
+  local detail
+  detail=$(pawl status t1 2>/dev/null)
+  assert_json "$detail" ".workflow[0].sub_steps[0].status" "failed" || return
+  assert_json "$detail" ".workflow[0].sub_steps[1].status" "cancelled" || return
+  assert_contains "$(jq -r '.last_feedback' <<< "$detail")" "typecheck: exit code 2" || return
+  assert_contains "$(jq -r '.last_feedback' <<< "$detail")" "type error" || return
+  pass
+}
+
+test_parallel_any() {
+  begin_test "join=any → one passing sub-step is enough"
+  setup_project "par3" '{"workflow":[{"name":"mirror","join":"any","parallel":[{"name":"a","run":"exit 1"},{"name":"b","run":"sleep 0.3"}]}]}'
+  create_task t1
+  local out
+  out=$(pawl start t1 2>/dev/null)
+  assert_json "$out" ".status" "completed" || return
+  pass
+}
+
+test_parallel_crash_resume() {
+  begin_test "crash mid-group → resume re-runs only unfinished sub-steps"
+  setup_project "par4" '{"workflow":[{"name":"checks","parallel":[{"name":"fast","run":"echo run >> fast_runs"},{"name":"slow","run":"sleep 0.5; touch marker; [ -f go ] || sleep 30"}]}]}'
+  create_task t1
+  crash_during_step marker
+  touch go
+  local out
+  out=$(pawl start t1 2>/dev/null)
+  assert_json "$out" ".status" "completed" || return
+  [ "$(wc -l < fast_runs | tr -d ' ')" = "1" ] || { fail "fast sub-step ran again"; return; }
+  pass
+}
+
+test_parallel_owner_killed() {
+  begin_test "owner killed mid-group → crash detection terminates the sub-steps"
+  setup_project "par5" '{"workflow":[{"name":"checks","parallel":[{"name":"a","run":"echo $$ > pid_a; sleep 30"},{"name":"b","run":"echo $$ > pid_b; sleep 30"}]}]}'
+  create_task t1
+  kill_owner_during_step pid_a pid_b
+  local pids
+  pids="$(cat pid_a pid_b 2>/dev/null || true)"
+  [ "$(echo "$pids" | wc -w)" = "2" ] || { fail "sub-steps did not start"; return; }
+  local out pid survivor=""
+  out=$(pawl status t1 2>/dev/null)
+  assert_json "$out" ".message" "interrupted" || return
+  for pid in $pids; do
+    process_alive "$pid" && survivor="$pid"
+    kill -9 "$pid" 2>/dev/null || true
+  done
+  [ -z "$survivor" ] || { fail "sub-step process $survivor survived"; return; }
+  pass
+}
+
+test_parallel_all
+test_parallel_fail_fast
+test_parallel_any
+test_parallel_crash_resume
+test_parallel_owner_killed
+
+# ═══════════════════════════════════════════════════════
+# 25. Sub-workflows
+# ═══════════════════════════════════════════════════════
+echo "── Sub-workflows ──"
+
+test_sub_workflow_runs_child() {
+  begin_test "sub-workflow step → child run linked to parent, vars passed"
+  setup_project "subwf1" '{"workflow":[{"name":"build","workflow":"build","vars":{"target":"${task}-x"}},{"name":"ship","run":"true"}]}'
+  echo '{"workflow":[{"name":"compile","run":"echo ${target} > compiled"}]}' > .pawl/workflows/build.json
+  create_task t1
+  local out
+  out=$(pawl start t1 2>/dev/null)
+  assert_json "$out" ".status" "completed" || return
+  [ "$(cat compiled)" = "t1-x" ] || { fail "vars not passed: $(cat compiled)"; return; }
+  local started
+  started=$(jq -s -c 'map(select(.type == "task_started"))[0]' .pawl/logs/t1/build.jsonl)
+  assert_json "$started" ".parent.task" "t1" || return
+  assert_json "$started" ".parent.step" "0" || return
+  assert_json "$started" ".workflow" "build" || return
+  local detail
+  detail=$(pawl status t1 2>/dev/null)
+  assert_json "$detail" ".workflow[0].step_type" "workflow" || return
+  assert_json "$detail" ".workflow[0].sub_workflow.name" "t1/build" || return
+  assert_json "$detail" ".workflow[0].sub_workflow.status" "completed" || return
+  assert_json "$detail" ".workflow[0].sub_workflow.parent" "t1" || return
+  pass
+}
+
+test_sub_workflow_failure_retries() {
+  begin_test "failed child run → parent step fails, retry starts a new child run"
+  setup_project "subwf2" '{"workflow":[{"name":"build","workflow":"build","on_fail":"retry","max_retries":1}]}'
+  echo '{"workflow":[{"name":"compile","run":"echo run >> runs; echo broken >&2; exit 3"}]}' > .pawl/workflows/build.json
+  create_task t1
+  local out
+  out=$(pawl start t1 2>/dev/null)
+  assert_json "$out" ".status" "failed" || return
+  [ "$(wc -l < runs | tr -d ' ')" = "2" ] || { fail "expected 2 child runs"; return; }
+  assert_contains "$(jq -r '.last_feedback' <<< "$out")" "sub-workflow 't1/build' failed" || return
+  assert_contains "$(jq -r '.last_feedback' <<< "$out")" "broken" || return
+  local child_runs
+  child_runs=$(jq -s 'map(select(.type == "sub_workflow_started")) | length' .pawl/logs/t1.jsonl)
+  [ "$child_runs" = "2" ] || { fail "expected 2 sub_workflow_started, got $child_runs"; return; }
+  pass
+}
+
+test_sub_workflow_gate_resumes_parent() {
+  begin_test "child waits at a gate → done on the child resumes the parent"
+  setup_project "subwf3" '{"workflow":[{"name":"release","workflow":"release"},{"name":"announce","run":"touch announced"}]}'
+  echo '{"workflow":[{"name":"approve"},{"name":"tag","run":"touch tagged"}]}' > .pawl/workflows/release.json
+  create_task t1
+  local out
+  out=$(pawl start t1 2>/dev/null)
+  assert_json "$out" ".status" "running" || return
+  local child
+  child=$(pawl status t1/release 2>/dev/null)
+  assert_json "$child" ".status" "waiting" || return
+  pawl start t1 >/dev/null 2>&1 && { fail "start should refuse while the child runs"; return; }
+  pawl done t1/release >/dev/null 2>&1
+  [ -f tagged ] && [ -f announced ] || { fail "parent did not resume"; return; }
+  assert_json "$(pawl status t1 2>/dev/null)" ".status" "completed" || return
+  pass
+}
+
+test_sub_workflow_stop() {
+  begin_test "stopping the parent stops its child run"
+  setup_project "subwf4" '{"workflow":[{"name":"release","workflow":"release"}]}'
+  echo '{"workflow":[{"name":"approve"}],"finally":[{"name":"cleanup","run":"touch child_cleanup"}]}' > .pawl/workflows/release.json
+  create_task t1
+  pawl start t1 >/dev/null 2>&1
+  pawl stop t1 >/dev/null 2>&1
+  assert_json "$(pawl status t1 2>/dev/null)" ".status" "stopped" || return
+  assert_json "$(pawl status t1/release 2>/dev/null)" ".status" "stopped" || return
+  [ -f child_cleanup ] || { fail "child finally did not run"; return; }
+  pass
+}
+
+test_sub_workflow_crash_resume() {
+  begin_test "crash inside a child run → start resumes the child, finished steps kept"
+  setup_project "subwf6" '{"workflow":[{"name":"build","workflow":"build"}]}'
+  echo '{"workflow":[{"name":"one","run":"echo run >> ones"},{"name":"two","run":"touch marker; [ -f go ] || sleep 30"}]}' > .pawl/workflows/build.json
+  create_task t1
+  crash_during_step marker
+  assert_json "$(pawl status t1 2>/dev/null)" ".message" "interrupted" || return
+  touch go
+  local out
+  out=$(pawl start t1 2>/dev/null)
+  assert_json "$out" ".status" "completed" || return
+  [ "$(wc -l < ones | tr -d ' ')" = "1" ] || { fail "finished child step ran again"; return; }
+  pass
+}
+
+test_sub_workflow_cycle() {
+  begin_test "sub-workflow cycle → validation error"
+  setup_project "subwf5" '{"workflow":[{"name":"a","workflow":"other"}]}'
+  echo '{"workflow":[{"name":"b","workflow":"default"}]}' > .pawl/workflows/other.json
+  create_task t1
+  local rc=0
+  pawl status >/dev/null 2>&1 || rc=$?
+  assert_exit 6 "$rc" || return
+  pass
+}
+
+test_sub_workflow_runs_child
+test_sub_workflow_failure_retries
+test_sub_workflow_gate_resumes_parent
+test_sub_workflow_stop
+test_sub_workflow_crash_resume
+test_sub_workflow_cycle
+
+# ═══════════════════════════════════════════════════════
+# 26. Foreach
+# ═══════════════════════════════════════════════════════
+echo "── Foreach ──"
+
+test_foreach_list() {
+  begin_test "foreach list → one run per item with \${item} / \${item_index}"
+  setup_project "foreach1" '{"workflow":[{"name":"build","run":"echo ${item}-${item_index} >> out","foreach":["linux","mac","win"]}]}'
+  create_task t1
+  local out
+  out=$(pawl start t1 2>/dev/null)
+  assert_json "$out" ".status" "completed" || return
+  [ "$(tr '\n' ' ' < out)" = "linux-0 mac-1 win-2 " ] || { fail "got: $(cat out)"; return; }
+  local detail
+  detail=$(pawl status t1 2>/dev/null)
+  assert_json "$detail" ".workflow[0].step_type" "foreach" || return
+  assert_json "$detail" ".workflow[0].sub_steps[1].name" "mac" || return
+  assert_json "$detail" ".workflow[0].sub_steps[2].status" "success" || return
+  pass
+}
+
+test_foreach_command_item_retry() {
+  begin_test "foreach command items → a failing item retries on its own budget"
+  setup_project "foreach2" '{"workflow":[{"name":"pkg","foreach":{"command":"printf \"x\\n\\ny\\n\""},"run":"echo ${item} >> runs; [ ${item} = x ] || [ ${retry_count} = 1 ]","on_fail":"retry","max_retries":2}]}'
+  create_task t1
+  local out
+  out=$(pawl start t1 2>/dev/null)
+  assert_json "$out" ".status" "completed" || return
+  [ "$(grep -c x runs)" = "1" ] || { fail "x should run once"; return; }
+  [ "$(grep -c y runs)" = "2" ] || { fail "y should run twice"; return; }
+  local resets
+  resets=$(jq -s 'map(select(.type == "step_reset")) | length' .pawl/logs/t1.jsonl)
+  [ "$resets" = "0" ] || { fail "item retry should not reset the step"; return; }
+  pass
+}
+
+test_foreach_item_exhausted() {
+  begin_test "foreach item out of retries → step fails without a step-level retry"
+  setup_project "foreach3" '{"vars":{"targets":"p q"},"workflow":[{"name":"pkg","foreach":{"var":"targets"},"run":"echo ${item} >> runs; [ ${item} = p ] || { echo bad-${item} >&2; exit 4; }","on_fail":"retry","max_retries":1}]}'
+  create_task t1
+  local out
+  out=$(pawl start t1 2>/dev/null)
+  assert_json "$out" ".status" "failed" || return
+  [ "$(tr '\n' ' ' < runs)" = "p q q " ] || { fail "got: $(cat runs)"; return; }
+  assert_contains "$(jq -r '.last_feedback' <<< "$out")" "q: exit code 4" || return
+  assert_contains "$(jq -r '.last_feedback' <<< "$out")" "bad-q" || return
+  pass
+}
+
+test_foreach_crash_resume() {
+  begin_test "crash mid-foreach → resume skips items that already succeeded"
+  setup_project "foreach4" '{"workflow":[{"name":"each","foreach":["a","b"],"run":"echo ${item} >> runs; if [ ${item} = b ]; then touch marker; [ -f go ] || sleep 30; fi"}]}'
+  create_task t1
+  crash_during_step marker
+  touch go
+  local out
+  out=$(pawl start t1 2>/dev/null)
+  assert_json "$out" ".status" "completed" || return
+  [ "$(grep -c a runs)" = "1" ] || { fail "item a ran again"; return; }
+  [ "$(grep -c b runs)" = "2" ] || { fail "item b should re-run"; return; }
+  pass
+}
+
+test_foreach_crash_during_item_backoff() {
+  begin_test "crash during a foreach item's backoff → resume waits out its retry_at"
+  setup_project "foreach5" '{"workflow":[{"name":"each","foreach":["a","b"],"run":"echo ${item} >> runs; [ ${item} = a ] || [ ${retry_count} = 1 ]","on_fail":"retry","retry_delay":3}]}'
+  create_task t1
+  pawl start t1 >/dev/null 2>&1 &
+  local pawl_pid=$!
+  for _ in $(seq 1 50); do grep -q '"retry_at"' .pawl/logs/t1.jsonl 2>/dev/null && break; sleep 0.1; done
+  kill -9 "$pawl_pid" 2>/dev/null || true
+  wait "$pawl_pid" 2>/dev/null || true
+  local scheduled
+  scheduled=$(jq -s -c 'map(select(.type == "sub_step_finished" and .retry_at != null))' .pawl/logs/t1.jsonl)
+  assert_json_num "$scheduled" "length" "1" || return
+  assert_json "$scheduled" ".[0].sub" "1" || return
+  local began out elapsed
+  began=$(date +%s%N)
+  out=$(pawl start t1 2>/dev/null)
+  elapsed=$(( ($(date +%s%N) - began) / 1000000 ))
+  assert_json "$out" ".status" "completed" || return
+  [ "$elapsed" -ge 1500 ] || { fail "resumed after ${elapsed}ms, before retry_at"; return; }
+  [ "$(tr '\n' ' ' < runs)" = "a b b " ] || { fail "got: $(cat runs)"; return; }
+  pass
+}
+
+test_foreach_owner_killed() {
+  begin_test "owner killed mid-foreach → crash detection terminates the running item"
+  setup_project "foreach6" '{"workflow":[{"name":"each","foreach":["a","b"],"run":"echo $$ > pid_${item}; sleep 30"}]}'
+  create_task t1
+  kill_owner_during_step pid_a
+  local pid
+  pid="$(cat pid_a 2>/dev/null || true)"
+  [ -n "$pid" ] || { fail "item did not start"; return; }
+  local out alive=false
+  out=$(pawl status t1 2>/dev/null)
+  assert_json "$out" ".message" "interrupted" || return
+  process_alive "$pid" && alive=true
+  kill -9 "$pid" 2>/dev/null || true
+  [ "$alive" = false ] || { fail "item process $pid survived"; return; }
+  [ ! -f pid_b ] || { fail "next item started"; return; }
+  pass
+}
+
+test_foreach_list
+test_foreach_command_item_retry
+test_foreach_item_exhausted
+test_foreach_crash_resume
+test_foreach_crash_during_item_backoff
+test_foreach_owner_killed
+
+# ═══════════════════════════════════════════════════════
+# 27. Task Matrix
+# ═══════════════════════════════════════════════════════
+echo "── Task Matrix ──"
+
+test_matrix_tasks() {
+  begin_test "matrix template → one task per value, with its var and depends"
+  setup_project "matrix1" '{"tasks":{"lib":{},"svc-*":{"matrix":{"svc":["auth","billing"]},"depends":["lib"],"vars":{"dir":"services/${svc}"}}},"workflow":[{"name":"build","run":"echo ${dir} > out-${task}"}]}'
+  local out
+  out=$(pawl list 2>/dev/null)
+  assert_json "$out" "map(.name) | join(\" \")" "lib svc-auth svc-billing" || return
+  out=$(pawl list --ready 2>/dev/null)
+  assert_json "$out" "map(.name) | join(\" \")" "lib" || return
+  local rc=0
+  pawl start svc-auth >/dev/null 2>&1 || rc=$?
+  assert_exit 3 "$rc" || return
+  pawl start lib >/dev/null 2>&1
+  out=$(pawl list --ready 2>/dev/null)
+  assert_json "$out" "map(.name) | join(\" \")" "svc-auth svc-billing" || return
+  out=$(pawl start svc-billing 2>/dev/null)
+  assert_json "$out" ".status" "completed" || return
+  [ "$(cat out-svc-billing)" = "services/billing" ] || { fail "got: $(cat out-svc-billing)"; return; }
+  pass
+}
+
+test_tasks_from() {
+  begin_test "tasks_from → tasks generated by a command"
+  setup_project "matrix2" '{"tasks":{"base":{}},"tasks_from":"cat tasks.json","workflow":[{"name":"a","run":"echo ${region} > out-${task}"}]}'
+  echo '{"deploy-*":{"matrix":{"region":["eu","us"]},"depends":["base"]},"extra":{}}' > tasks.json
+  local out
+  out=$(pawl list 2>/dev/null)
+  assert_json "$out" "map(.name) | join(\" \")" "base deploy-eu deploy-us extra" || return
+  echo '["one","two"]' > tasks.json
+  out=$(pawl list 2>/dev/null)
+  assert_json "$out" "map(.name) | join(\" \")" "base one two" || return
+  echo '["base"]' > tasks.json
+  local rc=0
+  pawl list >/dev/null 2>&1 || rc=$?
+  assert_exit 6 "$rc" || return
+  rc=0
+  echo 'nope' > tasks.json
+  pawl list >/dev/null 2>&1 || rc=$?
+  assert_exit 6 "$rc" || return
+  pass
+}
+
+test_matrix_collision() {
+  begin_test "generated task name clashing across workflows → validation error"
+  setup_project "matrix3" '{"tasks":{"svc-*":{"matrix":{"svc":["auth"]}}},"workflow":[{"name":"a","run":"true"}]}'
+  echo '{"tasks":{"svc-auth":{}},"workflow":[{"name":"b","run":"true"}]}' > .pawl/workflows/other.json
+  local rc=0
+  pawl list >/dev/null 2>&1 || rc=$?
+  assert_exit 6 "$rc" || return
+  pass
+}
+
+test_matrix_tasks
+test_tasks_from
+test_matrix_collision
+
+# ═══════════════════════════════════════════════════════
+# 28. Run Vars
+# ═══════════════════════════════════════════════════════
+echo "── Run Vars ──"
+
+test_start_var() {
+  begin_test "start --var → shadows config vars, survives a gate, reaches hooks"
+  setup_project "runvars1" '{"vars":{"branch":"main","wt":"wt/${branch}"},"on":{"step_finished":"echo ${branch} >> hook.log"},"workflow":[{"name":"a","run":"echo ${wt} $PAWL_ENV > a.out"},{"name":"gate"},{"name":"b","run":"echo ${branch} > b.out"}]}'
+  create_task t1 '{"vars":{"branch":"task-branch"}}'
+  local out
+  out=$(pawl start t1 --var branch=fix/x --var "env=a b" 2>/dev/null)
+  assert_json "$out" ".status" "waiting" || return
+  [ "$(cat a.out)" = "wt/fix/x a b" ] || { fail "got: $(cat a.out)"; return; }
+  out=$(pawl status t1 2>/dev/null)
+  assert_json "$out" ".vars.branch" "fix/x" || return
+  pawl done t1 >/dev/null 2>&1
+  [ "$(cat b.out)" = "fix/x" ] || { fail "got: $(cat b.out)"; return; }
+  assert_contains "$(cat hook.log)" "fix/x" || return
+  assert_json "$(head -1 .pawl/logs/t1.jsonl)" ".vars.env" "a b" || return
+  pass
+}
+
+test_start_vars_file() {
+  begin_test "start --vars-file → file vars, --var wins"
+  setup_project "runvars2" '{"workflow":[{"name":"a","run":"echo ${x}-${y} > out"}]}'
+  create_task t1
+  echo '{"x":"file","y":"file"}' > vars.json
+  pawl start t1 --vars-file vars.json --var y=flag >/dev/null 2>&1
+  [ "$(cat out)" = "file-flag" ] || { fail "got: $(cat out)"; return; }
+  local rc=0
+  pawl start t1 --reset --var novalue >/dev/null 2>&1 || rc=$?
+  assert_exit 6 "$rc" || return
+  echo '["x"]' > bad.json
+  rc=0
+  pawl start t1 --reset --vars-file bad.json >/dev/null 2>&1 || rc=$?
+  assert_exit 6 "$rc" || return
+  pass
+}
+
+test_start_var_resume() {
+  begin_test "crash → resume keeps run vars; new --var needs --reset"
+  setup_project "runvars3" '{"workflow":[{"name":"a","run":"echo ${v} >> runs; touch marker; [ -f go ] || sleep 30"}]}'
+  create_task t1
+  crash_during_step marker --var v=first
+  touch go
+  local rc=0
+  pawl start t1 --var v=second >/dev/null 2>&1 || rc=$?
+  assert_exit 2 "$rc" || return
+  local out
+  out=$(pawl start t1 2>/dev/null)
+  assert_json "$out" ".status" "completed" || return
+  [ "$(tr '\n' ' ' < runs)" = "first first " ] || { fail "got: $(cat runs)"; return; }
+  pass
+}
+
+test_start_var
+test_start_vars_file
+test_start_var_resume
+
+# ═══════════════════════════════════════════════════════
+# 29. Pinned Definitions
+# ═══════════════════════════════════════════════════════
+echo "── Pinned Definitions ──"
+
+test_pinned_definition() {
+  begin_test "config edited while waiting → run keeps its pinned definition"
+  setup_project "pin1" '{"workflow":[{"name":"a","run":"true"},{"name":"gate"},{"name":"b","run":"echo old > b.out"}]}'
+  create_task t1
+  pawl start t1 >/dev/null 2>&1
+  local hash
+  hash=$(head -1 .pawl/logs/t1.jsonl | jq -r .workflow_hash)
+  [ -f ".pawl/snapshots/${hash}.json" ] || { fail "no snapshot for $hash"; return; }
+  echo '{"workflow":[{"name":"a","run":"true"},{"name":"x","run":"touch x.ran"},{"name":"gate"},{"name":"b","run":"echo new > b.out"}]}' > .pawl/workflows/default.json
+  local out
+  out=$(pawl status t1 2>/dev/null)
+  assert_json "$out" ".workflow_changed" "true" || return
+  assert_json "$out" ".current_step" "1" || return
+  assert_json "$out" ".workflow | map(.name) | join(\",\")" "a,gate,b" || return
+  out=$(pawl done t1 2>/dev/null)
+  assert_json "$out" ".status" "completed" || return
+  [ "$(cat b.out)" = "old" ] || { fail "got: $(cat b.out)"; return; }
+  [ ! -f x.ran ] || { fail "new step ran"; return; }
+  pass
+}
+
+test_migrate() {
+  begin_test "migrate → waiting run moves onto the new definition by step name"
+  setup_project "pin2" '{"workflow":[{"name":"a","run":"true"},{"name":"gate"},{"name":"b","run":"echo old > b.out"}]}'
+  create_task t1
+  pawl start t1 >/dev/null 2>&1
+  echo '{"workflow":[{"name":"a","run":"true"},{"name":"x","run":"touch x.ran"},{"name":"gate"},{"name":"b","run":"echo new > b.out"}]}' > .pawl/workflows/default.json
+  local out
+  out=$(pawl migrate t1 2>/dev/null)
+  assert_json "$out" ".status" "waiting" || return
+  assert_json "$out" ".current_step" "2" || return
+  out=$(pawl status t1 2>/dev/null)
+  assert_json "$out" ".workflow_changed" "null" || return
+  assert_json "$out" ".workflow[0].status" "success" || return
+  out=$(pawl done t1 2>/dev/null)
+  assert_json "$out" ".status" "completed" || return
+  [ "$(cat b.out)" = "new" ] || { fail "got: $(cat b.out)"; return; }
+  [ ! -f x.ran ] || { fail "skipped-over new step ran"; return; }
+  assert_json "$(jq -s 'map(select(.type == "workflow_migrated")) | .[0].steps | map(tostring) | join(",")' .pawl/logs/t1.jsonl)" "." "0,2,3" || return
+  pass
+}
+
+test_migrate_renamed_step() {
+  begin_test "migrate with the current step renamed → needs --map"
+  setup_project "pin3" '{"workflow":[{"name":"a","run":"true"},{"name":"gate"},{"name":"b","run":"true"}]}'
+  create_task t1
+  pawl start t1 >/dev/null 2>&1
+  echo '{"workflow":[{"name":"a","run":"true"},{"name":"review"},{"name":"b","run":"true"}]}' > .pawl/workflows/default.json
+  local rc=0
+  pawl migrate t1 >/dev/null 2>&1 || rc=$?
+  assert_exit 3 "$rc" || return
+  rc=0
+  pawl migrate t1 --map gate=nope >/dev/null 2>&1 || rc=$?
+  assert_exit 6 "$rc" || return
+  local out
+  out=$(pawl migrate t1 --map gate=review 2>/dev/null)
+  assert_json "$out" ".step_name" "review" || return
+  out=$(pawl done t1 2>/dev/null)
+  assert_json "$out" ".status" "completed" || return
+  pass
+}
+
+test_pinned_definition
+test_migrate
+test_migrate_renamed_step
+
+# ═══════════════════════════════════════════════════════
+# 30. Check
+# ═══════════════════════════════════════════════════════
+echo "── Check ──"
+
+test_check_clean() {
+  begin_test "check on a clean config → valid, exit 0"
+  setup_project "check1" '{"vars":{"branch":"pawl/${task}"},"workflow":[{"name":"build","run":"git checkout ${branch}"}]}'
+  create_task t1
+  local out
+  out=$(pawl check 2>/dev/null) || { fail "check failed"; return; }
+  assert_json "$out" ".valid" "true" || return
+  assert_json "$out" ".diagnostics | length" "0" || return
+  pass
+}
+
+test_check_errors() {
+  begin_test "check reports every problem with file and JSON path → exit 6"
+  setup_project "check2" '{"tasks":{"a":{"depends":["b"],"skip":["ghost"]},"b":{"depends":["a","nobody"]}},"on":{"step_done":"true"},"workflow":[{"name":"build","run":"true","on_fail":"retyr"},{"name":"build","run":"true"}]}'
+  local out rc=0
+  out=$(pawl check 2>/dev/null) || rc=$?
+  assert_exit 6 "$rc" || return
+  assert_json "$out" ".valid" "false" || return
+  assert_json "$out" ".errors" "6" || return
+  assert_json "$out" ".diagnostics[0].file" ".pawl/workflows/default.json" || return
+  local paths
+  paths=$(echo "$out" | jq -r '[.diagnostics[].path] | join(" ")')
+  assert_contains "$paths" '$.workflow[0].on_fail' || return
+  assert_contains "$paths" '$.workflow[1].name' || return
+  assert_contains "$paths" '$.tasks.a.skip[0]' || return
+  assert_contains "$paths" '$.on.step_done' || return
+  assert_contains "$paths" '$.tasks.b.depends[1]' || return
+  assert_contains "$(echo "$out" | jq -r '.diagnostics[].message')" "dependency cycle: a → b → a" || return
+  pass
+}
+
+test_check_warnings() {
+  begin_test "check: unresolved var (error with --strict) and parse error per file"
+  setup_project "check3" '{"workflow":[{"name":"build","run":"make ${target} $HOME ${HOME}"}]}'
+  local out
+  out=$(pawl check 2>/dev/null) || { fail "warnings should not fail check"; return; }
+  assert_json "$out" ".valid" "true" || return
+  assert_json "$out" ".diagnostics[0].severity" "warning" || return
+  assert_json "$out" ".diagnostics[0].path" '$.workflow[0].run' || return
+  local rc=0
+  out=$(pawl check --strict 2>/dev/null) || rc=$?
+  assert_exit 6 "$rc" || return
+  assert_json "$out" ".diagnostics[0].severity" "error" || return
+  rc=0
+  echo '{"workflow": [' > .pawl/workflows/broken.json
+  out=$(pawl check 2>/dev/null) || rc=$?
+  assert_exit 6 "$rc" || return
+  assert_json "$out" '.diagnostics | map(select(.severity == "error")) | .[0].file' ".pawl/workflows/broken.json" || return
+  assert_json "$out" '.diagnostics | map(select(.severity == "warning")) | length' "1" || return
+  pass
+}
+
+test_check_clean
+test_check_errors
+test_check_warnings
+
+# ═══════════════════════════════════════════════════════
+# 31. Dependency Graph
+# ═══════════════════════════════════════════════════════
+echo "── Dependency Graph ──"
+
+test_dependency_cycle_rejected() {
+  begin_test "dependency cycle → rejected at load with the cycle path"
+  setup_project "dag1" '{"workflow":[{"name":"s","run":"true"}]}'
+  create_task a '{"depends":["b"]}'
+  create_task b '{"depends":["a"]}'
+  create_task c
+  local out rc=0
+  out=$(pawl list 2>&1) || rc=$?
+  assert_exit 6 "$rc" || return
+  assert_contains "$out" "dependency cycle: a → b → a" || return
+  pass
+}
+
+test_dependency_order() {
+  begin_test "list in dependency order with transitive blockers and unlocks"
+  setup_project "dag2" '{"workflow":[{"name":"s","run":"true"}]}'
+  create_task deploy '{"depends":["test"]}'
+  create_task test '{"depends":["build"]}'
+  create_task build
+  create_task api
+  local out
+  out=$(pawl list 2>/dev/null)
+  assert_json "$out" "map(.name) | join(\",\")" "api,build,test,deploy" || return
+  assert_json "$out" ".[3].blocked_by | join(\",\")" "test" || return
+  assert_json "$out" ".[3].blockers | join(\",\")" "build,test" || return
+  assert_json "$out" ".[1].unlocks | join(\",\")" "test" || return
+  assert_json "$out" ".[3].unlocks" "null" || return
+  pawl start build >/dev/null 2>&1
+  out=$(pawl status deploy 2>/dev/null)
+  assert_json "$out" ".blockers | join(\",\")" "test" || return
+  out=$(pawl status test 2>/dev/null)
+  assert_json "$out" ".unlocks | join(\",\")" "deploy" || return
+  assert_json "$out" ".blockers" "null" || return
+  pass
+}
+
+test_dependency_cycle_rejected
+test_dependency_order
+
+# ═══════════════════════════════════════════════════════
+# 32. Run All
+# ═══════════════════════════════════════════════════════
+echo "── Run All ──"
+
+test_run_all_dag() {
+  begin_test "run-all → every task runs after its dependencies"
+  setup_project "runall1" '{"workflow":[{"name":"s","run":"sleep 0.2; test -z \"${needs}\" || test -f ${needs}.out; touch ${task}.out"}]}'
+  create_task a
+  create_task b
+  create_task c '{"depends":["a","b"],"vars":{"needs":"a"}}'
+  create_task d '{"depends":["c"],"vars":{"needs":"c"}}'
+  local out
+  out=$(pawl run-all --jobs 2 2>/dev/null)
+  assert_json "$out" ".success" "true" || return
+  assert_json "$out" ".completed | join(\",\")" "a,b,c,d" || return
+  assert_json "$out" ".started | join(\",\")" "a,b,c,d" || return
+  out=$(pawl run-all 2>/dev/null)
+  assert_json "$out" ".started | length" "0" || return
+  assert_json "$out" ".success" "true" || return
+  pass
+}
+
+test_run_all_jobs_cap() {
+  begin_test "run-all --jobs 1 → tasks never overlap"
+  setup_project "runall2" '{"workflow":[{"name":"s","run":"echo start >> order.log; sleep 0.3; echo end >> order.log"}]}'
+  create_task a
+  create_task b
+  create_task c
+  pawl run-all --jobs 1 >/dev/null 2>&1
+  local order
+  order=$(tr '\n' ' ' < order.log)
+  [ "$order" = "start end start end start end " ] || { fail "got: $order"; return; }
+  pass
+}
+
+test_run_all_failure() {
+  begin_test "run-all with a failing task → downstream blocked, others finish"
+  setup_project "runall3" '{"workflow":[{"name":"s","run":"test ${task} != bad"}]}'
+  create_task bad
+  create_task after '{"depends":["bad"]}'
+  create_task later '{"depends":["after"]}'
+  create_task other
+  local out
+  out=$(pawl run-all 2>/dev/null)
+  assert_json "$out" ".success" "false" || return
+  assert_json "$out" ".failed | join(\",\")" "bad" || return
+  assert_json "$out" ".blocked | join(\",\")" "after,later" || return
+  assert_json "$out" ".completed | join(\",\")" "other" || return
+  [ ! -f .pawl/logs/after.jsonl ] || { fail "downstream task started"; return; }
+  pass
+}
+
+test_run_all_resume() {
+  begin_test "run-all after a crash → resumes the interrupted task, then its dependents"
+  local marker="${E2E_TMP}/pawl-e2e-runall4-marker"
+  rm -f "$marker"
+  setup_project "runall4" "{\"workflow\":[{\"name\":\"s\",\"run\":\"[ \${task} != t1 ] || [ -f ran ] || { touch ran $marker; sleep 30; }\"}]}"
+  create_task t1
+  create_task t2 '{"depends":["t1"]}'
+  crash_during_step "$marker"
+  assert_json "$(pawl status t1 2>/dev/null)" ".message" "interrupted" || return
+  local out
+  out=$(pawl run-all 2>/dev/null)
+  assert_json "$out" ".success" "true" || return
+  assert_json "$out" ".started | join(\",\")" "t1,t2" || return
+  assert_json "$(pawl status t1 2>/dev/null)" ".run_id" "$(head -1 .pawl/logs/t1.jsonl | jq -r .run_id)" || return
+  pass
+}
+
+test_run_all_dag
+test_run_all_jobs_cap
+test_run_all_failure
+test_run_all_resume
+
+# ═══════════════════════════════════════════════════════
+# 33. Auto Start
+# ═══════════════════════════════════════════════════════
+echo "── Auto Start ──"
+
+test_auto_start_chain() {
+  begin_test "auto_start → dependents start once their last dependency completes"
+  setup_project "auto1" '{"workflow":[{"name":"s","run":"touch ${task}.out"}]}'
+  create_task a
+  create_task x
+  create_task b '{"depends":["a"],"auto_start":true}'
+  create_task c '{"depends":["b"],"auto_start":true}'
+  create_task d '{"depends":["a","x"],"auto_start":true}'
+  create_task manual '{"depends":["a"]}'
+  pawl start a >/dev/null 2>&1
+  pawl wait c --until completed -t 10 >/dev/null 2>&1 || { fail "c did not complete"; return; }
+  assert_json "$(pawl status b 2>/dev/null)" ".status" "completed" || return
+  assert_json "$(pawl status d 2>/dev/null)" ".status" "pending" || return
+  assert_json "$(pawl status manual 2>/dev/null)" ".status" "pending" || return
+  pawl start x >/dev/null 2>&1
+  pawl wait d --until completed -t 10 >/dev/null 2>&1 || { fail "d did not complete"; return; }
+  pass
+}
+
+test_start_when_ready() {
+  begin_test "start --when-ready → waits for dependencies, then runs"
+  setup_project "auto2" '{"workflow":[{"name":"s","run":"test -f lib.out || test ${task} = lib; touch ${task}.out"}]}'
+  create_task lib
+  create_task api '{"depends":["lib"]}'
+  pawl start api --when-ready >/dev/null 2>&1 &
+  local waiter=$!
+  sleep 0.5
+  assert_json "$(pawl status api 2>/dev/null)" ".status" "pending" || return
+  pawl start lib >/dev/null 2>&1
+  wait "$waiter" || { fail "--when-ready start failed"; return; }
+  assert_json "$(pawl status api 2>/dev/null)" ".status" "completed" || return
+  pass
+}
+
+test_start_when_ready_failed_dep() {
+  begin_test "start --when-ready with a failed dependency → exit 3"
+  setup_project "auto3" '{"workflow":[{"name":"s","run":"test ${task} != lib"}]}'
+  create_task lib
+  create_task api '{"depends":["lib"]}'
+  pawl start lib >/dev/null 2>&1
+  local out rc=0
+  out=$(pawl start api --when-ready 2>&1 >/dev/null) || rc=$?
+  assert_exit 3 "$rc" || return
+  assert_contains "$out" "Dependency 'lib' of 'api' is failed" || return
+  pass
+}
+
+test_auto_start_chain
+test_start_when_ready
+test_start_when_ready_failed_dep
+
+# ═══════════════════════════════════════════════════════
+# 34. Cascade Reset
+# ═══════════════════════════════════════════════════════
+echo "── Cascade Reset ──"
+
+setup_diamond() {
+  setup_project "$1" '{"workflow":[{"name":"s","run":"true"}]}'
+  create_task lib
+  create_task api '{"depends":["lib"]}'
+  create_task web '{"depends":["lib"]}'
+  create_task ship '{"depends":["api","web"]}'
+  create_task other
+}
+
+test_reset_cascade() {
+  begin_test "reset --cascade → task and every started dependent reset, downstream first"
+  setup_diamond "cascade1"
+  pawl run-all >/dev/null 2>&1
+  local out
+  out=$(pawl reset lib --cascade --dry-run 2>/dev/null)
+  assert_json "$out" ".reset | join(\",\")" "lib,api,web,ship" || return
+  assert_json "$(pawl status ship 2>/dev/null)" ".status" "completed" || return
+  out=$(pawl reset lib --cascade 2>/dev/null)
+  assert_json "$out" ".status" "pending" || return
+  for t in api web ship; do
+    assert_json "$(pawl status $t 2>/dev/null)" ".status" "pending" || return
+  done
+  assert_json "$(pawl status other 2>/dev/null)" ".status" "completed" || return
+  local rc=0
+  pawl reset lib --cascade --step >/dev/null 2>&1 || rc=$?
+  assert_exit 6 "$rc" || return
+  pass
+}
+
+test_invalidate_on_upstream_reset() {
+  begin_test "invalidate_on_upstream_reset → completed dependent goes stale when upstream re-runs"
+  setup_project "cascade2" '{"workflow":[{"name":"s","run":"true"}]}'
+  create_task lib
+  create_task api '{"depends":["lib"],"invalidate_on_upstream_reset":true}'
+  create_task web '{"depends":["lib"]}'
+  create_task ship '{"depends":["api"]}'
+  pawl start lib >/dev/null 2>&1
+  pawl start api >/dev/null 2>&1
+  pawl start web >/dev/null 2>&1
+  assert_json "$(pawl status api 2>/dev/null)" ".stale" "null" || return
+  pawl start lib --reset >/dev/null 2>&1
+  assert_json "$(pawl status api 2>/dev/null)" ".stale" "true" || return
+  assert_json "$(pawl status web 2>/dev/null)" ".stale" "null" || return
+  local rc=0
+  pawl start ship >/dev/null 2>&1 || rc=$?
+  assert_exit 3 "$rc" || return
+  pawl start api --reset >/dev/null 2>&1
+  assert_json "$(pawl status api 2>/dev/null)" ".stale" "null" || return
+  pawl start ship >/dev/null 2>&1
+  assert_json "$(pawl status ship 2>/dev/null)" ".status" "completed" || return
+  pass
+}
+
+test_reset_cascade
+test_invalidate_on_upstream_reset
+
+# ═══════════════════════════════════════════════════════
+# 35. Wait For
+# ═══════════════════════════════════════════════════════
+echo "── Wait For ──"
+
+test_wait_for_blocks_and_resumes() {
+  begin_test "wait_for → step blocks, resumes by itself when the other task's step succeeds"
+  setup_project "waitfor1" '{"workflow":[{"name":"migrate","run":"true"},{"name":"approve"},{"name":"deploy","run":"touch ${task}.deployed","wait_for":["api:approve"]}]}'
+  create_task api
+  create_task web
+  pawl start api >/dev/null 2>&1
+  pawl start web >/dev/null 2>&1
+  local out
+  out=$(pawl done web 2>/dev/null)
+  assert_json "$out" ".status" "waiting" || return
+  assert_json "$out" ".message" "blocked" || return
+  out=$(pawl status web 2>/dev/null)
+  assert_json "$out" ".blocked_on | join(\",\")" "api:approve" || return
+  local rc=0
+  pawl done web >/dev/null 2>&1 || rc=$?
+  assert_exit 2 "$rc" || return
+  [ ! -f web.deployed ] || { fail "blocked step ran"; return; }
+  pawl done api >/dev/null 2>&1
+  pawl wait web --until completed -t 10 >/dev/null 2>&1 || { fail "web was not resumed"; return; }
+  [ -f web.deployed ] || { fail "deploy did not run"; return; }
+  assert_json "$(jq -s 'map(.type) | map(select(. == "step_unblocked")) | length' .pawl/logs/web.jsonl)" "." "1" || return
+  pass
+}
+
+test_wait_for_run_all() {
+  begin_test "wait_for under run-all → waiting step runs after the other task's step"
+  setup_project "waitfor2" '{"workflow":[{"name":"s1","run":"test ${task} = b || sleep 0.5; touch ${task}.s1"},{"name":"s2","run":"test -f a.s1","wait_for":["a:s1"]}]}'
+  create_task a
+  create_task b
+  local out
+  out=$(pawl run-all --jobs 2 2>/dev/null)
+  assert_json "$out" ".success" "true" || return
+  pass
+}
+
+test_wait_for_check() {
+  begin_test "check → wait_for naming an unknown task or step is an error"
+  setup_project "waitfor3" '{"workflow":[{"name":"s","run":"true","wait_for":["ghost:s","t1:nope","bad"]}]}'
+  create_task t1
+  local out rc=0
+  out=$(pawl check 2>/dev/null) || rc=$?
+  assert_exit 6 "$rc" || return
+  assert_json "$out" ".errors" "3" || return
+  assert_contains "$(echo "$out" | jq -r '.diagnostics[].path')" '$.workflow[0].wait_for[1]' || return
+  pass
+}
+
+test_wait_for_reject_refused() {
+  begin_test "reject on a step blocked on wait_for → exit 2, nothing settled"
+  setup_project "waitfor4" '{"workflow":[{"name":"approve"},{"name":"deploy","run":"true","wait_for":["api:approve"]}]}'
+  create_task api
+  create_task web
+  pawl start api >/dev/null 2>&1
+  pawl start web >/dev/null 2>&1
+  pawl done web >/dev/null 2>&1
+  local out rc=0
+  out=$(pawl reject web -m "no" 2>&1 >/dev/null) || rc=$?
+  assert_exit 2 "$rc" || return
+  assert_contains "$out" "blocked on api:approve" || return
+  out=$(pawl status web 2>/dev/null)
+  assert_json "$out" ".status" "waiting" || return
+  assert_json "$out" ".message" "blocked" || return
+  pass
+}
+
+test_wait_for_blocks_and_resumes
+test_wait_for_reject_refused
+test_wait_for_run_all
+test_wait_for_check
+
+# ═══════════════════════════════════════════════════════
+# 36. Conditional Depends
+# ═══════════════════════════════════════════════════════
+echo "── Conditional Depends ──"
+
+test_depends_on_failed() {
+  begin_test "depends on: failed → task is ready only once the dependency fails"
+  setup_project "conddep1" '{"workflow":[{"name":"s","run":"test ${task} != api"}]}'
+  create_task api
+  create_task triage '{"depends":[{"task":"api","on":"failed"}]}'
+  local out rc=0
+  out=$(pawl start triage 2>&1 >/dev/null) || rc=$?
+  assert_exit 3 "$rc" || return
+  assert_contains "$out" "api (on failed)" || return
+  out=$(pawl list 2>/dev/null)
+  assert_json "$out" ".[] | select(.name == \"triage\") | .blocked_by[0].on" "failed" || return
+  pawl start api >/dev/null 2>&1
+  out=$(pawl list --ready 2>/dev/null)
+  assert_json "$out" "map(.name) | join(\",\")" "triage" || return
+  assert_json "$(pawl start triage 2>/dev/null)" ".status" "completed" || return
+  pass
+}
+
+test_depends_on_finished_auto_start() {
+  begin_test "depends on: finished + auto_start → starts after the dependency fails"
+  setup_project "conddep2" '{"workflow":[{"name":"s","run":"test ${task} != api"}]}'
+  create_task api
+  create_task cleanup '{"depends":[{"task":"api","on":"finished"}],"auto_start":true}'
+  create_task deploy '{"depends":["api"],"auto_start":true}'
+  pawl start api >/dev/null 2>&1
+  pawl wait cleanup --until completed -t 10 >/dev/null 2>&1 || { fail "cleanup did not complete"; return; }
+  assert_json "$(pawl status deploy 2>/dev/null)" ".status" "pending" || return
+  pass
+}
+
+test_depends_on_failed_when_ready_completed() {
+  begin_test "start --when-ready on a failure dependency that completed → exit 3"
+  setup_project "conddep3" '{"workflow":[{"name":"s","run":"true"}]}'
+  create_task api
+  create_task triage '{"depends":[{"task":"api","on":"failed"}]}'
+  pawl start api >/dev/null 2>&1
+  local out rc=0
+  out=$(pawl start triage --when-ready 2>&1 >/dev/null) || rc=$?
+  assert_exit 3 "$rc" || return
+  assert_contains "$out" "Dependency 'api' of 'triage' is completed" || return
+  pass
+}
+
+test_run_all_skips_failure_dependents() {
+  begin_test "run-all on a green run → failure dependents skipped, success true"
+  setup_project "conddep4" '{"workflow":[{"name":"s","run":"true"}]}'
+  create_task api
+  create_task triage '{"depends":[{"task":"api","on":"failed"}]}'
+  create_task report '{"depends":["triage"]}'
+  local out
+  out=$(pawl run-all 2>/dev/null)
+  assert_json "$out" ".success" "true" || return
+  assert_json "$out" ".completed | join(\",\")" "api" || return
+  assert_json "$out" ".skipped | join(\",\")" "triage,report" || return
+  assert_json "$out" ".blocked | length" "0" || return
+  pass
+}
+
+test_depends_on_failed
+test_depends_on_finished_auto_start
+test_depends_on_failed_when_ready_completed
+test_run_all_skips_failure_dependents
+
+# ═══════════════════════════════════════════════════════
+# 37. Compact
+# ═══════════════════════════════════════════════════════
+echo "── Compact ──"
+
+test_compact_archives_earlier_runs() {
+  begin_test "compact → runs before the last reset move to the archive, state unchanged"
+  setup_project "compact1" '{"workflow":[{"name":"s","run":"true"}]}'
+  create_task t
+  pawl start t >/dev/null 2>&1
+  pawl reset t >/dev/null 2>&1
+  pawl start t >/dev/null 2>&1
+  local before out
+  before=$(wc -l < .pawl/logs/t.jsonl)
+  out=$(pawl compact 2>/dev/null)
+  assert_json "$out" ".[0].task" "t" || return
+  assert_json "$out" ".[0].archived + .[0].kept" "$before" || return
+  assert_json "$(head -1 .pawl/logs/t.jsonl)" ".type" "task_reset" || return
+  [ "$(wc -l < .pawl/archive/t.jsonl)" -gt 0 ] || { fail "archive is empty"; return; }
+  [ -f .pawl/checkpoints/t.json ] || { fail "no checkpoint written"; return; }
+  assert_json "$(pawl status t 2>/dev/null)" ".status" "completed" || return
+  out=$(pawl compact --verify 2>/dev/null) || { fail "verify failed"; return; }
+  assert_json "$out" ".[0].consistent" "true" || return
+  assert_json "$out" ".[0].events" "$before" || return
+  assert_json "$(pawl compact t 2>/dev/null)" ".[0].archived" "0" || return
+  pass
+}
+
+test_compact_checkpoint_used_and_verified() {
+  begin_test "checkpoint → replay resumes from it; --verify catches a diverging one"
+  setup_project "compact2" '{"workflow":[{"name":"s","run":"true"},{"name":"gate"}]}'
+  create_task t
+  pawl start t >/dev/null 2>&1
+  pawl compact t >/dev/null 2>&1
+  pawl done t >/dev/null 2>&1
+  assert_json "$(pawl status t 2>/dev/null)" ".status" "completed" || return
+  pawl compact --verify t >/dev/null 2>&1 || { fail "verify failed after new events"; return; }
+  jq -c '.state.message = "tampered"' .pawl/checkpoints/t.json > cp.tmp && mv cp.tmp .pawl/checkpoints/t.json
+  local rc=0
+  pawl compact --verify t >/dev/null 2>&1 || rc=$?
+  assert_exit 6 "$rc" || return
+  pass
+}
+
+test_checkpoint_written_for_long_logs() {
+  begin_test "long log → replay saves a checkpoint by itself"
+  setup_project "compact3" '{"workflow":[{"name":"s","run":"false","on_fail":"retry","max_retries":40}]}'
+  create_task t
+  pawl start t >/dev/null 2>&1
+  assert_json "$(pawl status t 2>/dev/null)" ".status" "failed" || return
+  [ -f .pawl/checkpoints/t.json ] || { fail "no checkpoint written"; return; }
+  pawl compact --verify t >/dev/null 2>&1 || { fail "verify failed"; return; }
+  pass
+}
+
+test_compact_archives_earlier_runs
+test_compact_checkpoint_used_and_verified
+test_checkpoint_written_for_long_logs
+
 # ═══════════════════════════════════════════════════════
 # Cleanup and Report
 # ═══════════════════════════════════════════════════════